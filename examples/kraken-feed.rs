@@ -316,6 +316,7 @@ fn main() {
                     None,
                     config.validate,
                 )
+                .expect("invalid order")
                 .expect("api call failed");
             match block_on(result).expect("Failed to submit order") {
                 Ok(tx_id) => log::info!("Success: tx_id = {}", tx_id),
@@ -340,6 +341,7 @@ fn main() {
                     None,
                     config.validate,
                 )
+                .expect("invalid order")
                 .expect("api call failed");
             match block_on(result).expect("Failed to submit order") {
                 Ok(tx_id) => log::info!("Success: tx_id = {}", tx_id),
@@ -367,6 +369,7 @@ fn main() {
                     None,
                     config.validate,
                 )
+                .expect("invalid order")
                 .expect("api call failed");
             match block_on(result).expect("Failed to submit order") {
                 Ok(tx_id) => log::info!("Success: tx_id = {}", tx_id),
@@ -394,6 +397,7 @@ fn main() {
                     None,
                     config.validate,
                 )
+                .expect("invalid order")
                 .expect("api call failed");
             match block_on(result).expect("Failed to submit order") {
                 Ok(tx_id) => log::info!("Success: tx_id = {}", tx_id),
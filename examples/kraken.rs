@@ -67,6 +67,16 @@ enum Command {
         #[conf(pos)]
         since: Option<String>,
     },
+    /// Get all recent trades since some timestamp, following pages: {pair}, {since:?}
+    AllRecentTrades {
+        #[conf(pos)]
+        pair: String,
+        #[conf(pos)]
+        since: Option<String>,
+        /// Optional timestamp (unix time) to stop at
+        #[arg(long)]
+        until: Option<Decimal>,
+    },
     /// Get account balance
     GetBalance,
     /// Get account trade volume (and fees): {pairs:?}
@@ -83,6 +93,55 @@ enum Command {
     },
     /// Get open orders list
     GetOpenOrders,
+    /// Get ledger entries
+    GetLedgers {
+        /// Optional comma-separated list of assets to filter by
+        #[arg(long)]
+        asset: Option<String>,
+        /// Optional start timestamp (unix time) or ledger id
+        #[arg(long)]
+        start: Option<String>,
+        /// Optional end timestamp (unix time) or ledger id
+        #[arg(long)]
+        end: Option<String>,
+        /// Optional result offset for pagination
+        #[arg(long)]
+        ofs: Option<u64>,
+    },
+    /// Query specific ledger entries by ledger id
+    QueryLedgers {
+        #[conf(repeat, pos)]
+        ledger_ids: Vec<String>,
+    },
+    /// Get closed orders list
+    GetClosedOrders {
+        /// Optional start timestamp (unix time) or order tx id
+        #[arg(long)]
+        start: Option<String>,
+        /// Optional end timestamp (unix time) or order tx id
+        #[arg(long)]
+        end: Option<String>,
+        /// Optional result offset for pagination
+        #[arg(long)]
+        ofs: Option<u64>,
+    },
+    /// Get trades history
+    GetTradesHistory {
+        /// Optional start timestamp (unix time) or trade tx id
+        #[arg(long)]
+        start: Option<String>,
+        /// Optional end timestamp (unix time) or trade tx id
+        #[arg(long)]
+        end: Option<String>,
+        /// Optional result offset for pagination
+        #[arg(long)]
+        ofs: Option<u64>,
+    },
+    /// Query specific trades by trade id
+    QueryTrades {
+        #[conf(repeat, pos)]
+        trade_ids: Vec<String>,
+    },
     /// Cancel order: {id}
     CancelOrder {
         #[conf(pos)]
@@ -307,6 +366,14 @@ fn main() {
             let result = api.get_recent_trades(pair, since).expect("api call failed");
             log_value(&result);
         }
+        Command::AllRecentTrades { pair, since, until } => {
+            let mut iter = api.recent_trades_since(pair, since);
+            if let Some(until) = until {
+                iter = iter.until(until);
+            }
+            let result = iter.collect::<Result<Vec<_>, _>>().expect("api call failed");
+            log_value(&result);
+        }
         Command::GetBalance => {
             let result = api.get_account_balance().expect("api call failed");
             let sorted_result = result.into_iter().collect::<BTreeMap<_, _>>();
@@ -329,6 +396,57 @@ fn main() {
             let sorted_result = result.open.into_iter().collect::<BTreeMap<_, _>>();
             log_value(&sorted_result);
         }
+        Command::GetClosedOrders { start, end, ofs } => {
+            use krakenrs::GetClosedOrdersRequest;
+            let result = api
+                .get_closed_orders(GetClosedOrdersRequest {
+                    start,
+                    end,
+                    ofs,
+                    ..Default::default()
+                })
+                .expect("api call failed");
+            log_value(&result.count);
+            let sorted_result = result.closed.into_iter().collect::<BTreeMap<_, _>>();
+            log_value(&sorted_result);
+        }
+        Command::GetTradesHistory { start, end, ofs } => {
+            use krakenrs::TradesHistoryRequest;
+            let result = api
+                .get_trades_history(TradesHistoryRequest {
+                    start,
+                    end,
+                    ofs,
+                    ..Default::default()
+                })
+                .expect("api call failed");
+            log_value(&result.count);
+            let sorted_result = result.trades.into_iter().collect::<BTreeMap<_, _>>();
+            log_value(&sorted_result);
+        }
+        Command::QueryTrades { trade_ids } => {
+            let result = api.query_trades(trade_ids).expect("api call failed");
+            log_value(&result);
+        }
+        Command::GetLedgers { asset, start, end, ofs } => {
+            use krakenrs::LedgersRequest;
+            let result = api
+                .get_ledgers(LedgersRequest {
+                    asset,
+                    start,
+                    end,
+                    ofs,
+                    ..Default::default()
+                })
+                .expect("api call failed");
+            log_value(&result.count);
+            let sorted_result = result.ledger.into_iter().collect::<BTreeMap<_, _>>();
+            log_value(&sorted_result);
+        }
+        Command::QueryLedgers { ledger_ids } => {
+            let result = api.query_ledgers(ledger_ids).expect("api call failed");
+            log_value(&result);
+        }
         Command::CancelOrder { id } => {
             let result = api.cancel_order(id).expect("api call failed");
             log_value(&result);
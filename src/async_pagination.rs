@@ -0,0 +1,356 @@
+//! Streams which follow the `last`, `ofs` and `cursor` pagination schemes of the Kraken REST API,
+//! like the iterators of [crate::PageIter], for use with [AsyncKrakenRestAPI].
+
+use crate::{
+    AsyncKrakenRestAPI, Candle, CursorPage, DEFAULT_PAGE_PAUSE, DepositStatus, DepositStatusRequest,
+    GetClosedOrdersRequest, LedgerEntry, LedgersRequest, OrderInfo, PublicTrade, Result, TradeInfo,
+    TradesHistoryRequest, TxId, WithdrawStatusRequest, WithdrawalStatus,
+    messages::{KrakenResult, unpack_kraken_result},
+    pagination::{closed_order_time, end_or_until},
+};
+use core::{
+    cmp::Reverse,
+    pin::Pin,
+    task::{Context, Poll},
+};
+use futures::{
+    future::{FutureExt, LocalBoxFuture},
+    stream::Stream,
+};
+use rust_decimal::Decimal;
+use std::{
+    collections::VecDeque,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+use tokio::time::Sleep;
+
+// A function which fetches the next page of results, or returns None if there are no more pages.
+// It is passed the `until` timestamp of the stream, so that it can be sent to kraken where supported.
+// Only one page is fetched at a time, so the state it shares with the future is never contended.
+// The futures are not Send, because the futures of AsyncKrakenRestClient are not Send for all lifetimes.
+type FetchPage<'a, T> = Box<dyn FnMut(Option<Decimal>) -> LocalBoxFuture<'a, Result<Option<Vec<T>>>> + 'a>;
+
+/// A stream over the items of a paginated Kraken REST API.
+///
+/// This is the async equivalent of [crate::PageIter], and is configured the same way,
+/// with `pause` and `until`. The pause between pages is waited for on the tokio timer.
+pub struct PageStream<'a, T> {
+    fetch_page: FetchPage<'a, T>,
+    timestamp: fn(&T) -> Decimal,
+    chronological: bool,
+    buffer: VecDeque<T>,
+    pause: Duration,
+    until: Option<Decimal>,
+    last_fetch: Option<Instant>,
+    sleep: Option<Pin<Box<Sleep>>>,
+    pending: Option<LocalBoxFuture<'a, Result<Option<Vec<T>>>>>,
+    done: bool,
+}
+
+impl<'a, T> PageStream<'a, T> {
+    fn new(fetch_page: FetchPage<'a, T>, timestamp: fn(&T) -> Decimal, chronological: bool) -> Self {
+        Self {
+            fetch_page,
+            timestamp,
+            chronological,
+            buffer: Default::default(),
+            pause: DEFAULT_PAGE_PAUSE,
+            until: None,
+            last_fetch: None,
+            sleep: None,
+            pending: None,
+            done: false,
+        }
+    }
+
+    /// Set the minimum time to wait between requests for consecutive pages
+    pub fn pause(mut self, pause: Duration) -> Self {
+        self.pause = pause;
+        self
+    }
+
+    /// Set a unix timestamp, such that later items are not yielded
+    pub fn until(mut self, until: Decimal) -> Self {
+        self.until = Some(until);
+        self
+    }
+}
+
+// Items are never pinned, only the pending request and the sleep, which are boxed
+impl<T> Unpin for PageStream<'_, T> {}
+
+impl<T> Stream for PageStream<'_, T> {
+    type Item = Result<T>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = self.get_mut();
+        loop {
+            if let Some(item) = this.buffer.pop_front() {
+                if let Some(until) = this.until
+                    && (this.timestamp)(&item) > until
+                {
+                    if this.chronological {
+                        this.done = true;
+                        this.buffer.clear();
+                        return Poll::Ready(None);
+                    }
+                    continue;
+                }
+                return Poll::Ready(Some(Ok(item)));
+            }
+            if this.done {
+                return Poll::Ready(None);
+            }
+            if let Some(pending) = this.pending.as_mut() {
+                let result = futures::ready!(pending.as_mut().poll(cx));
+                this.pending = None;
+                this.last_fetch = Some(Instant::now());
+                match result {
+                    Ok(Some(page)) => {
+                        if page.is_empty() {
+                            this.done = true;
+                        }
+                        this.buffer.extend(page);
+                    }
+                    Ok(None) => {
+                        this.done = true;
+                    }
+                    Err(err) => {
+                        this.done = true;
+                        return Poll::Ready(Some(Err(err)));
+                    }
+                }
+                continue;
+            }
+            // Wait until the pause has elapsed since the last fetch, then fetch the next page
+            if this.sleep.is_none()
+                && let Some(last_fetch) = this.last_fetch
+                && last_fetch.elapsed() < this.pause
+            {
+                this.sleep = Some(Box::pin(tokio::time::sleep(this.pause - last_fetch.elapsed())));
+            }
+            if let Some(sleep) = this.sleep.as_mut() {
+                futures::ready!(sleep.as_mut().poll(cx));
+                this.sleep = None;
+            }
+            this.pending = Some((this.fetch_page)(this.until));
+        }
+    }
+}
+
+impl AsyncKrakenRestAPI {
+    /// (Public) Stream all recent trades in an asset pair since a particular timestamp,
+    /// following the `last` value of each page of Get Recent Trades.
+    ///
+    /// See [crate::KrakenRestAPI::recent_trades_since].
+    pub fn recent_trades_since(&self, pair: String, since: Option<String>) -> PageStream<'_, PublicTrade> {
+        let since = Arc::new(Mutex::new(since));
+        let fetch_page = move |_until| {
+            let (pair, since) = (pair.clone(), since.clone());
+            async move {
+                let current = since.lock().expect("mutex poisoned").clone();
+                let page = self.get_recent_trades(pair, current.clone()).await?;
+                if current.as_ref() == Some(&page.last) {
+                    return Ok(None);
+                }
+                *since.lock().expect("mutex poisoned") = Some(page.last);
+                Ok(Some(page.data))
+            }
+            .boxed_local()
+        };
+        PageStream::new(Box::new(fetch_page), |trade| trade.timestamp, true)
+    }
+
+    /// (Public) Stream OHLC data for an asset pair since a particular timestamp,
+    /// following the `last` value of each page.
+    ///
+    /// See [crate::KrakenRestAPI::ohlc_since].
+    pub fn ohlc_since(&self, pair: String, interval: u16, since: Option<String>) -> PageStream<'_, Candle> {
+        let since = Arc::new(Mutex::new(since));
+        let fetch_page = move |_until| {
+            let (pair, since) = (pair.clone(), since.clone());
+            async move {
+                let current = since.lock().expect("mutex poisoned").clone();
+                let page = self.ohlc_at_interval(pair, interval, current.clone()).await?;
+                if current.as_ref() == Some(&page.last) {
+                    return Ok(None);
+                }
+                *since.lock().expect("mutex poisoned") = Some(page.last);
+                Ok(Some(page.data))
+            }
+            .boxed_local()
+        };
+        PageStream::new(Box::new(fetch_page), |candle| candle.timestamp, true)
+    }
+
+    /// (Private) Stream all closed orders matching a request, following `ofs`.
+    ///
+    /// See [crate::KrakenRestAPI::closed_orders_iter].
+    pub fn closed_orders_iter(&self, request: GetClosedOrdersRequest) -> PageStream<'_, (TxId, OrderInfo)> {
+        let request = Arc::new(Mutex::new(request));
+        let fetch_page = move |until| {
+            let request = request.clone();
+            async move {
+                let current = request.lock().expect("mutex poisoned").clone();
+                let ofs = current.ofs.unwrap_or(0);
+                let page = self
+                    .get_closed_orders(GetClosedOrdersRequest {
+                        ofs: Some(ofs),
+                        end: end_or_until(&current.end, until),
+                        ..current
+                    })
+                    .await?;
+                if page.closed.is_empty() || ofs >= page.count {
+                    return Ok(None);
+                }
+                let mut items: Vec<_> = page.closed.into_iter().collect();
+                items.sort_by_key(|item| Reverse(closed_order_time(item)));
+                let mut request = request.lock().expect("mutex poisoned");
+                request.ofs = Some(ofs + items.len() as u64);
+                if request.end.is_none() && until.is_none() {
+                    request.end = items.first().map(|item| closed_order_time(item).to_string());
+                }
+                Ok(Some(items))
+            }
+            .boxed_local()
+        };
+        PageStream::new(Box::new(fetch_page), closed_order_time, false)
+    }
+
+    /// (Private) Stream all of our trades (fills) matching a request, following `ofs`.
+    ///
+    /// See [crate::KrakenRestAPI::trades_history_iter].
+    pub fn trades_history_iter(&self, request: TradesHistoryRequest) -> PageStream<'_, (TxId, TradeInfo)> {
+        let request = Arc::new(Mutex::new(request));
+        let fetch_page = move |until| {
+            let request = request.clone();
+            async move {
+                let current = request.lock().expect("mutex poisoned").clone();
+                let ofs = current.ofs.unwrap_or(0);
+                let page = self
+                    .get_trades_history(TradesHistoryRequest {
+                        ofs: Some(ofs),
+                        end: end_or_until(&current.end, until),
+                        ..current
+                    })
+                    .await?;
+                if page.trades.is_empty() || ofs >= page.count {
+                    return Ok(None);
+                }
+                let mut items: Vec<_> = page.trades.into_iter().collect();
+                items.sort_by_key(|item| Reverse(item.1.time));
+                let mut request = request.lock().expect("mutex poisoned");
+                request.ofs = Some(ofs + items.len() as u64);
+                if request.end.is_none() && until.is_none() {
+                    request.end = items.first().map(|item| item.1.time.to_string());
+                }
+                Ok(Some(items))
+            }
+            .boxed_local()
+        };
+        PageStream::new(Box::new(fetch_page), |(_, trade)| trade.time, false)
+    }
+
+    /// (Private) Stream all ledger entries matching a request, following `ofs`.
+    ///
+    /// See [crate::KrakenRestAPI::ledgers_iter].
+    pub fn ledgers_iter(&self, request: LedgersRequest) -> PageStream<'_, (String, LedgerEntry)> {
+        let request = Arc::new(Mutex::new(request));
+        let fetch_page = move |until| {
+            let request = request.clone();
+            async move {
+                let current = request.lock().expect("mutex poisoned").clone();
+                let ofs = current.ofs.unwrap_or(0);
+                let page = self
+                    .get_ledgers(LedgersRequest {
+                        ofs: Some(ofs),
+                        end: end_or_until(&current.end, until),
+                        ..current
+                    })
+                    .await?;
+                if page.ledger.is_empty() || page.count.is_some_and(|count| ofs >= count) {
+                    return Ok(None);
+                }
+                let mut items: Vec<_> = page.ledger.into_iter().collect();
+                items.sort_by_key(|item| Reverse(item.1.time));
+                let mut request = request.lock().expect("mutex poisoned");
+                request.ofs = Some(ofs + items.len() as u64);
+                if request.end.is_none() && until.is_none() {
+                    request.end = items.first().map(|item| item.1.time.to_string());
+                }
+                Ok(Some(items))
+            }
+            .boxed_local()
+        };
+        PageStream::new(Box::new(fetch_page), |(_, entry)| entry.time, false)
+    }
+
+    /// (Private) Stream the status of all withdrawals matching a request, following `next_cursor`.
+    ///
+    /// See [crate::KrakenRestAPI::withdraw_status_iter].
+    pub fn withdraw_status_iter(&self, request: WithdrawStatusRequest) -> PageStream<'_, WithdrawalStatus> {
+        let mut request = request;
+        let cursor = Arc::new(Mutex::new(Some(
+            request.cursor.take().unwrap_or_else(|| "true".to_owned()),
+        )));
+        let fetch_page = move |until| {
+            let (request, cursor) = (request.clone(), cursor.clone());
+            async move {
+                let Some(current) = cursor.lock().expect("mutex poisoned").take() else {
+                    return Ok(None);
+                };
+                let result: Result<KrakenResult<CursorPage<WithdrawalStatus>>> = self
+                    .client
+                    .query_private(
+                        "WithdrawStatus",
+                        WithdrawStatusRequest {
+                            cursor: Some(current),
+                            end: end_or_until(&request.end, until),
+                            ..request
+                        },
+                    )
+                    .await;
+                let page = result.and_then(unpack_kraken_result)?;
+                *cursor.lock().expect("mutex poisoned") = page.next_cursor;
+                Ok(Some(page.data))
+            }
+            .boxed_local()
+        };
+        PageStream::new(Box::new(fetch_page), |status| Decimal::from(status.time), false)
+    }
+
+    /// (Private) Stream the status of all deposits matching a request, following `next_cursor`.
+    ///
+    /// See [crate::KrakenRestAPI::deposit_status_iter].
+    pub fn deposit_status_iter(&self, request: DepositStatusRequest) -> PageStream<'_, DepositStatus> {
+        let mut request = request;
+        let cursor = Arc::new(Mutex::new(Some(
+            request.cursor.take().unwrap_or_else(|| "true".to_owned()),
+        )));
+        let fetch_page = move |until| {
+            let (request, cursor) = (request.clone(), cursor.clone());
+            async move {
+                let Some(current) = cursor.lock().expect("mutex poisoned").take() else {
+                    return Ok(None);
+                };
+                let result: Result<KrakenResult<CursorPage<DepositStatus>>> = self
+                    .client
+                    .query_private(
+                        "DepositStatus",
+                        DepositStatusRequest {
+                            cursor: Some(current),
+                            end: end_or_until(&request.end, until),
+                            ..request
+                        },
+                    )
+                    .await;
+                let page = result.and_then(unpack_kraken_result)?;
+                *cursor.lock().expect("mutex poisoned") = page.next_cursor;
+                Ok(Some(page.data))
+            }
+            .boxed_local()
+        };
+        PageStream::new(Box::new(fetch_page), |status| Decimal::from(status.time), false)
+    }
+}
@@ -0,0 +1,93 @@
+//! An async version of [crate::KrakenRestAPI], for use within a tokio runtime.
+//!
+//! This has the same methods as the blocking API, which are generated from the same source (see `rest_api.rs`),
+//! and uses the same request and response types.
+//! The paginating iterators of the blocking API return a [crate::PageStream] instead.
+//! Private calls made through one instance are serialized, to avoid `EAPI:Invalid nonce` errors
+//! when it is shared between many tasks. (See [AsyncKrakenRestClient].)
+
+use crate::{
+    AddOrderBatchResponse, AddOrderResponse, AmendOrderRequest, AmendOrderResponse, AssetPairInfo, AssetPairsResponse,
+    AssetsResponse, AsyncKrakenRestClient, BalanceResponse, BookData, CancelAllOrdersAfterResponse,
+    CancelAllOrdersResponse, CancelOrderResponse, DepositAddressesRequest, DepositAddressesResponse,
+    DepositMethodsResponse, DepositStatusRequest, DepositStatusResponse, EditOrderRequest, EditOrderResponse, Error,
+    GetClosedOrdersRequest, GetClosedOrdersResponse, GetOHLCDataResponse, GetOpenOrdersResponse, GetOrderBookResponse,
+    GetRecentSpreadsResponse, GetRecentTradesResponse, GetTradeVolumeResponse, GetWebSocketsTokenResponse,
+    KrakenRestConfig, LedgersRequest, LedgersResponse, LimitOrder, MarketOrder, OpenPositionsResponse, Order,
+    QueryLedgersResponse, QueryOrdersResponse, QueryTradesResponse, Result, SystemStatusResponse, TickerResponse,
+    TimeResponse, TradeBalanceResponse, TradesHistoryRequest, TradesHistoryResponse, UserRefId,
+    WithdrawAddressesResponse, WithdrawInfoRequest, WithdrawInfoResponse, WithdrawRequest, WithdrawResponse,
+    WithdrawStatusRequest, WithdrawStatusResponse,
+    messages::{
+        AddOrderRequest, AssetPairsRequest, CancelAllOrdersAfterRequest, CancelOrderRequest, DepositMethodsRequest,
+        Empty, GetOHLCDataRequest, GetOpenOrdersRequest, GetOrderBookRequest, GetRecentSpreadsRequest,
+        GetRecentTradesRequest, GetTradeVolumeRequest, KrakenResult, OpenPositionsRequest, QueryLedgersRequest,
+        QueryOrdersRequest, QueryTradesRequest, TickerRequest, TradeBalanceRequest, WithdrawAddressesRequest,
+        unpack_kraken_result,
+    },
+};
+use core::convert::TryFrom;
+
+/// An async connection to the Kraken REST API
+pub struct AsyncKrakenRestAPI {
+    pub(crate) client: AsyncKrakenRestClient,
+}
+
+impl AsyncKrakenRestAPI {
+    /// Try to create RestAPI instance from config
+    pub fn new(src: KrakenRestConfig) -> Result<Self> {
+        Ok(AsyncKrakenRestAPI {
+            client: AsyncKrakenRestClient::try_from(src)?,
+        })
+    }
+
+    rest_api_methods!(async);
+}
+
+impl TryFrom<KrakenRestConfig> for AsyncKrakenRestAPI {
+    type Error = Error;
+    fn try_from(src: KrakenRestConfig) -> Result<Self> {
+        Self::new(src)
+    }
+}
+
+#[cfg(all(test, feature = "mock"))]
+mod tests {
+    use super::*;
+    use crate::{KrakenErrorKind, testing::MockKrakenServer};
+    use futures::future::join_all;
+    use serde_json::json;
+
+    #[tokio::test]
+    async fn test_async_public() {
+        let server = MockKrakenServer::start().unwrap();
+        server.respond(
+            "Time",
+            json!({ "unixtime": 1688669448, "rfc1123": "Thu, 06 Jul 23 18:50:48 +0000" }),
+        );
+
+        let api = AsyncKrakenRestAPI::try_from(server.rest_config().build().unwrap()).unwrap();
+        assert_eq!(api.time().await.unwrap().unixtime, 1688669448);
+
+        match api.system_status().await {
+            Err(Error::KrakenErrors(errors)) => assert_eq!(errors[0].kind, KrakenErrorKind::UnknownMethod),
+            other => panic!("unexpected result: {:?}", other.map(|_| ())),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_async_private_calls_are_serialized() {
+        let server = MockKrakenServer::start().unwrap();
+        server.set_strict_nonces(true);
+        server.respond("Balance", json!({ "ZUSD": "171288.6158" }));
+
+        let api = AsyncKrakenRestAPI::try_from(server.rest_config().build().unwrap()).unwrap();
+        // Many concurrent private calls, all made within a few milliseconds, are all accepted
+        let results = join_all((0..20).map(|_| api.get_account_balance())).await;
+        assert!(results.iter().all(|result| result.is_ok()), "{:?}", results);
+
+        let requests = server.requests_for("Balance");
+        assert_eq!(requests.len(), 20);
+        assert!(requests.iter().all(|req| req.rejected.is_none()));
+    }
+}
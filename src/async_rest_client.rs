@@ -0,0 +1,179 @@
+//! This module provides a low-level async client for kraken API, analogous to [crate::KrakenRestClient].
+//! It shares the configuration, signing, and response handling of the blocking client,
+//! but uses the async reqwest client, and so must be used within a tokio runtime.
+//!
+//! Kraken requires the nonces of private calls (using the same API key) to be increasing,
+//! in the order that the calls are received. When many tasks make private calls concurrently,
+//! the calls can reach Kraken in a different order than the nonces were generated, causing
+//! `EAPI:Invalid nonce` errors. To avoid this, private calls pass through a submission queue:
+//! a nonce is only generated once all earlier private calls have received a response.
+
+use crate::{
+    Error, KrakenCredentials, KrakenRestConfig, OrderValidator, Result,
+    kraken_rest_client::{is_success_status, parse_response, sign_query, user_agent},
+    rate_limit::RateLimiter,
+};
+use reqwest::header::{HeaderMap, HeaderValue};
+use serde::{Serialize, de::DeserializeOwned};
+use std::convert::TryFrom;
+use tokio::sync::Mutex;
+use url::Url;
+
+/// A low-level async https connection to kraken that can execute public or private methods.
+pub struct AsyncKrakenRestClient {
+    /// Http client
+    client: reqwest::Client,
+    /// Our configuration
+    config: KrakenRestConfig,
+    /// Base url to contact kraken at
+    base_url: Url,
+    /// Kraken Api version to connect to
+    version: u16,
+    /// Client-side rate limiter (if enabled)
+    rate_limiter: Option<RateLimiter>,
+    /// The submission queue for private calls
+    submission_queue: Mutex<()>,
+}
+
+impl TryFrom<KrakenRestConfig> for AsyncKrakenRestClient {
+    type Error = Error;
+    fn try_from(config: KrakenRestConfig) -> Result<Self> {
+        let base_url = config.parsed_base_url()?;
+        let version = config.api_version;
+        let client = reqwest::ClientBuilder::new()
+            .user_agent(user_agent())
+            .timeout(config.timeout)
+            .build()?;
+        let rate_limiter = config.rate_limit.map(RateLimiter::new);
+        Ok(Self {
+            base_url,
+            version,
+            client,
+            config,
+            rate_limiter,
+            submission_queue: Mutex::new(()),
+        })
+    }
+}
+
+impl AsyncKrakenRestClient {
+    /// Try to create a new AsyncKrakenRestClient from config
+    ///
+    /// Note: This is the same as using `TryFrom::try_from` to construct an instance
+    pub fn new(config: KrakenRestConfig) -> Result<Self> {
+        Self::try_from(config)
+    }
+
+    /// Change the credentials used
+    pub fn set_creds(&mut self, creds: KrakenCredentials) {
+        self.config.creds = creds;
+    }
+
+    /// Get the client-side rate limiter, if rate limiting is enabled
+    pub fn rate_limiter(&self) -> Option<&RateLimiter> {
+        self.rate_limiter.as_ref()
+    }
+
+    /// Get the order validator, if order validation is enabled
+    pub fn order_validator(&self) -> Option<&OrderValidator> {
+        self.config.order_validator.as_deref()
+    }
+
+    /// Execute a public API, given method, and object matching the expected schema, and returning expected schema or an error.
+    pub async fn query_public<D: Serialize, R: DeserializeOwned>(&self, method: &str, query_data: D) -> Result<R> {
+        let url_path = format!("/{}/public/{}", self.version, method);
+
+        let post_data = serde_qs::to_string(&query_data)?;
+
+        // Public APIs are all idempotent
+        self.with_retries(method, true, async || {
+            self.query(&url_path, HeaderMap::new(), post_data.clone()).await
+        })
+        .await
+    }
+
+    /// Execute a private API, given method, and object matching the expected schema, and returning expected schema or an error.
+    ///
+    /// Private calls made through this client are serialized, so that they reach Kraken in nonce order.
+    pub async fn query_private<D: Serialize, R: DeserializeOwned>(&self, method: &str, query_data: D) -> Result<R> {
+        if self.config.creds.key.is_empty() || self.config.creds.secret.is_empty() {
+            return Err(Error::MissingCredentials);
+        }
+
+        let url_path = format!("/{}/private/{}", self.version, method);
+
+        // Convert the data to a query string
+        let qs = serde_qs::to_string(&query_data)?;
+
+        let retryable_call = self
+            .config
+            .retry_policy
+            .as_ref()
+            .is_some_and(|policy| policy.is_retryable_call(method, &qs));
+
+        self.with_retries(method, retryable_call, async || {
+            if let Some(rate_limiter) = self.rate_limiter.as_ref() {
+                rate_limiter.acquire_async(method).await?;
+            }
+
+            // Wait for our turn in the submission queue, and hold it until we have a response
+            let _turn = self.submission_queue.lock().await;
+            let nonce = self.config.nonce_provider.next_nonce()?;
+
+            // Sign the query data and url path, resulting in encoded post_data with nonce, and a signature.
+            // This happens on every attempt, so that each attempt has a fresh nonce.
+            let (post_data, sig) = sign_query(&self.config.creds.secret, &qs, &url_path, nonce)?;
+
+            let mut headers = HeaderMap::new();
+            headers.insert("API-Key", HeaderValue::from_str(&self.config.creds.key)?);
+            headers.insert("API-Sign", HeaderValue::from_str(&sig)?);
+
+            self.query(&url_path, headers, post_data).await
+        })
+        .await
+    }
+
+    /// Make attempts at a call until it succeeds, or the retry policy says to stop
+    async fn with_retries<R>(
+        &self,
+        method: &str,
+        retryable_call: bool,
+        mut attempt: impl AsyncFnMut() -> Result<R>,
+    ) -> Result<R> {
+        let Some(policy) = self.config.retry_policy.as_ref().filter(|_| retryable_call) else {
+            return attempt().await;
+        };
+        let mut attempts = 0;
+        loop {
+            attempts += 1;
+            match attempt().await {
+                Err(err) if attempts < policy.max_attempts && policy.is_retryable(&err) => {
+                    let backoff = policy.backoff(attempts);
+                    log::warn!(
+                        "{} failed (attempt {}/{}), retrying in {:?}: {}",
+                        method,
+                        attempts,
+                        policy.max_attempts,
+                        backoff,
+                        err
+                    );
+                    tokio::time::sleep(backoff).await;
+                }
+                result => return result,
+            }
+        }
+    }
+
+    /// Send a query (public or private) to kraken API, and interpret response as JSON
+    async fn query<R: DeserializeOwned>(&self, url_path: &str, headers: HeaderMap, post_data: String) -> Result<R> {
+        let url = self.base_url.join(url_path.trim_start_matches('/'))?;
+
+        let response = self.client.post(url).headers(headers).body(post_data).send().await?;
+        if !is_success_status(response.status()) {
+            return Err(Error::AsyncBadStatus(response));
+        }
+
+        let text = response.text().await?;
+        parse_response(self.config.retry_policy.as_ref(), text)
+    }
+}
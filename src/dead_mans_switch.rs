@@ -0,0 +1,346 @@
+//! A keeper for Kraken's dead man's switch, which cancels all of our orders if we stop checking in.
+//! <https://docs.kraken.com/api/docs/rest-api/cancel-all-orders-after>
+//!
+//! `CancelAllOrdersAfter` arms a countdown timer, after which all orders are canceled. To be useful,
+//! the timer must be pushed back periodically for as long as the process is healthy, so that if the
+//! process hangs or loses its connection, its orders are canceled soon after.
+//!
+//! A [DeadMansSwitch] owns a background thread which does this at a configured interval. The switch is
+//! disarmed (with a timeout of 0) when it is dropped.
+//!
+//! All calls to Kraken are made from the background thread, so a switch may be created and dropped within
+//! an async runtime. Creating and dropping it still blocks until the switch is armed or disarmed.
+
+use crate::{BuilderError, CancelAllOrdersAfterResponse, Error, KrakenRestAPI, Result};
+use std::{
+    io,
+    sync::{
+        Arc, Mutex,
+        mpsc::{self, RecvTimeoutError},
+    },
+    thread,
+    time::Duration,
+};
+
+#[cfg(feature = "ws")]
+use crate::ws::KrakenWsAPI;
+#[cfg(feature = "ws")]
+use tokio::runtime::{self, Runtime};
+
+/// Configuration for a dead man's switch
+#[derive(Clone, Debug)]
+pub struct DeadMansSwitchConfig {
+    /// The countdown after which all orders are canceled, unless the switch is triggered again
+    pub(crate) timeout: Duration,
+    /// How often to trigger the switch
+    pub(crate) interval: Duration,
+}
+
+impl DeadMansSwitchConfig {
+    /// Create a builder for the DeadMansSwitchConfig object
+    pub fn builder() -> DeadMansSwitchConfigBuilder {
+        Default::default()
+    }
+}
+
+impl Default for DeadMansSwitchConfig {
+    fn default() -> Self {
+        Self {
+            timeout: Duration::from_secs(60),
+            interval: Duration::from_secs(15),
+        }
+    }
+}
+
+/// Builder for the DeadMansSwitchConfig object
+#[derive(Default)]
+pub struct DeadMansSwitchConfigBuilder {
+    config: DeadMansSwitchConfig,
+}
+
+impl DeadMansSwitchConfigBuilder {
+    /// Create a new builder
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set the countdown after which all orders are canceled, unless the switch is triggered again.
+    /// This is rounded down to whole seconds. Kraken recommends (and the default is) 60 seconds.
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.config.timeout = timeout;
+        self
+    }
+
+    /// Set how often to trigger the switch. This must be shorter than the timeout, and Kraken recommends
+    /// 15 to 30 seconds for a 60 second timeout. The default is 15 seconds.
+    pub fn interval(mut self, interval: Duration) -> Self {
+        self.config.interval = interval;
+        self
+    }
+
+    /// Create a DeadMansSwitchConfig
+    pub fn build(self) -> core::result::Result<DeadMansSwitchConfig, BuilderError> {
+        let timeout = Duration::from_secs(self.config.timeout.as_secs());
+        if timeout.is_zero() || self.config.interval >= timeout {
+            return Err(BuilderError::InvalidInterval(self.config.interval, self.config.timeout));
+        }
+        Ok(DeadMansSwitchConfig {
+            timeout,
+            interval: self.config.interval,
+        })
+    }
+}
+
+/// Keeps Kraken's dead man's switch armed, from a background thread, until it is dropped.
+///
+/// Triggering the switch uses the REST API, or a private websockets connection if one is provided.
+/// If the websockets connection is closed, or there is no response within half the interval, the REST
+/// API is used instead. Failures are reported through a callback, and the thread keeps trying at the
+/// configured interval.
+pub struct DeadMansSwitch {
+    // The worker thread that triggers the switch, and returns the result of disarming it
+    worker_thread: Option<thread::JoinHandle<Result<()>>>,
+    // Sender to stop the worker thread
+    stop_sender: Option<mpsc::Sender<()>>,
+    // The latest trigger time reported by kraken
+    trigger_time: Arc<Mutex<Option<String>>>,
+}
+
+impl DeadMansSwitch {
+    /// Arm the switch using the REST API, and start a thread which keeps it armed.
+    ///
+    /// This fails fast: the switch is armed once before returning, and an error is returned if that fails.
+    ///
+    /// Arguments:
+    /// * config: The timeout and interval of the switch
+    /// * rest_api: The REST API, which must have credentials
+    /// * on_error: Called from the background thread whenever triggering the switch fails
+    pub fn new(
+        config: DeadMansSwitchConfig,
+        rest_api: Arc<KrakenRestAPI>,
+        on_error: impl FnMut(Error) + Send + 'static,
+    ) -> Result<Self> {
+        Self::start(
+            Trigger {
+                rest_api,
+                #[cfg(feature = "ws")]
+                ws_api: None,
+                #[cfg(feature = "ws")]
+                ws_timeout: config.interval / 2,
+                #[cfg(feature = "ws")]
+                runtime: None,
+            },
+            config,
+            on_error,
+        )
+    }
+
+    /// Arm the switch using a private websockets connection, and start a thread which keeps it armed.
+    /// The REST API is used instead if the connection is closed, or if there is no response within half the
+    /// interval, in which case `on_error` is called with [Error::WsTimeout].
+    ///
+    /// This fails fast: the switch is armed once before returning, and an error is returned if that fails.
+    ///
+    /// Arguments:
+    /// * config: The timeout and interval of the switch
+    /// * rest_api: The REST API, which must have credentials
+    /// * ws_api: A websockets connection, which must be configured with the auth token
+    /// * on_error: Called from the background thread whenever triggering the switch fails
+    #[cfg(feature = "ws")]
+    pub fn with_ws(
+        config: DeadMansSwitchConfig,
+        rest_api: Arc<KrakenRestAPI>,
+        ws_api: Arc<KrakenWsAPI>,
+        on_error: impl FnMut(Error) + Send + 'static,
+    ) -> Result<Self> {
+        Self::start(
+            Trigger {
+                rest_api,
+                ws_api: Some(ws_api),
+                ws_timeout: config.interval / 2,
+                runtime: None,
+            },
+            config,
+            on_error,
+        )
+    }
+
+    /// The latest trigger time reported by kraken (RFC 3339), after which all orders will be canceled
+    /// unless the switch is triggered again
+    pub fn trigger_time(&self) -> Option<String> {
+        self.trigger_time.lock().expect("mutex poisoned").clone()
+    }
+
+    /// Stop the background thread and disarm the switch, so that orders are no longer canceled.
+    /// This is done on drop as well, but then errors can only be logged.
+    pub fn disarm(mut self) -> Result<()> {
+        self.stop()
+    }
+
+    fn start(
+        trigger: Trigger,
+        config: DeadMansSwitchConfig,
+        mut on_error: impl FnMut(Error) + Send + 'static,
+    ) -> Result<Self> {
+        let timeout = config.timeout.as_secs();
+        let trigger_time = Arc::new(Mutex::new(None));
+
+        let (armed_sender, armed_receiver) = mpsc::channel();
+        let (stop_sender, stop_receiver) = mpsc::channel();
+        let worker_thread = {
+            let trigger_time = trigger_time.clone();
+            thread::Builder::new()
+                .name("kraken-dead-mans-switch".into())
+                .spawn(move || {
+                    let mut trigger = trigger;
+                    match trigger.init().and_then(|()| trigger.trigger(timeout, &mut on_error)) {
+                        Ok(response) => {
+                            *trigger_time.lock().expect("mutex poisoned") = Some(response.trigger_time);
+                            let _ = armed_sender.send(Ok(()));
+                        }
+                        Err(err) => {
+                            let _ = armed_sender.send(Err(err));
+                            return Ok(());
+                        }
+                    }
+                    // Wait for the interval, unless we are stopped (or the handle is dropped) first
+                    while let Err(RecvTimeoutError::Timeout) = stop_receiver.recv_timeout(config.interval) {
+                        match trigger.trigger(timeout, &mut on_error) {
+                            Ok(response) => {
+                                *trigger_time.lock().expect("mutex poisoned") = Some(response.trigger_time);
+                            }
+                            Err(err) => on_error(err),
+                        }
+                    }
+                    trigger.trigger(0, &mut on_error)?;
+                    *trigger_time.lock().expect("mutex poisoned") = None;
+                    Ok(())
+                })
+                .map_err(Error::Thread)?
+        };
+
+        // Fail fast if the switch could not be armed, or the thread panicked before reporting it
+        match armed_receiver.recv() {
+            Ok(Ok(())) => {}
+            Ok(Err(err)) => {
+                join(worker_thread)?;
+                return Err(err);
+            }
+            Err(_) => {
+                join(worker_thread)?;
+                return Err(thread_panicked());
+            }
+        }
+
+        Ok(Self {
+            worker_thread: Some(worker_thread),
+            stop_sender: Some(stop_sender),
+            trigger_time,
+        })
+    }
+
+    fn stop(&mut self) -> Result<()> {
+        let Some(worker_thread) = self.worker_thread.take() else {
+            return Ok(());
+        };
+        drop(self.stop_sender.take());
+        join(worker_thread)
+    }
+}
+
+// Join the worker thread, turning a panic (e.g. in the on_error callback) into an error
+fn join(worker_thread: thread::JoinHandle<Result<()>>) -> Result<()> {
+    worker_thread.join().map_err(|_| thread_panicked())?
+}
+
+fn thread_panicked() -> Error {
+    Error::Thread(io::Error::other("dead man's switch thread panicked"))
+}
+
+impl Drop for DeadMansSwitch {
+    fn drop(&mut self) {
+        if let Err(err) = self.stop() {
+            log::error!("failed to disarm dead man's switch: {}", err);
+        }
+    }
+}
+
+/// The APIs used to trigger the switch
+struct Trigger {
+    rest_api: Arc<KrakenRestAPI>,
+    #[cfg(feature = "ws")]
+    ws_api: Option<Arc<KrakenWsAPI>>,
+    // How long to wait for a response over websockets, before falling back to REST
+    #[cfg(feature = "ws")]
+    ws_timeout: Duration,
+    // A runtime to wait for websockets responses on, which is created (and dropped) on the worker thread
+    #[cfg(feature = "ws")]
+    runtime: Option<Runtime>,
+}
+
+impl Trigger {
+    // Create the runtime, if the switch is triggered over websockets
+    fn init(&mut self) -> Result<()> {
+        #[cfg(feature = "ws")]
+        if self.ws_api.is_some() {
+            let runtime = runtime::Builder::new_current_thread()
+                .enable_time()
+                .build()
+                .map_err(Error::Thread)?;
+            self.runtime = Some(runtime);
+        }
+        Ok(())
+    }
+
+    // Trigger the switch with a timeout in seconds, preferring websockets if available.
+    // A websockets timeout is reported through on_error before falling back to REST.
+    #[cfg_attr(not(feature = "ws"), allow(unused_variables))]
+    fn trigger(&self, timeout: u64, on_error: &mut impl FnMut(Error)) -> Result<CancelAllOrdersAfterResponse> {
+        #[cfg(feature = "ws")]
+        if let Some(runtime) = self.runtime.as_ref()
+            && let Some(receiver) = self
+                .ws_api
+                .as_ref()
+                .and_then(|ws_api| ws_api.cancel_all_orders_after(timeout))
+        {
+            match runtime.block_on(async { tokio::time::timeout(self.ws_timeout, receiver).await }) {
+                Ok(Ok(result)) => return result.map_err(|err| Error::KrakenErrors(vec![err])),
+                Ok(Err(_)) => {
+                    log::warn!("no response to cancelAllOrdersAfter over websockets, falling back to REST");
+                }
+                Err(_) => on_error(Error::WsTimeout(self.ws_timeout)),
+            }
+        }
+        self.rest_api.cancel_all_orders_after(timeout)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_config_builder() {
+        let config = DeadMansSwitchConfig::builder().build().unwrap();
+        assert_eq!(config.timeout, Duration::from_secs(60));
+        let config = DeadMansSwitchConfig::builder()
+            .timeout(Duration::from_millis(30500))
+            .build()
+            .unwrap();
+        assert_eq!(config.timeout, Duration::from_secs(30));
+        assert!(
+            DeadMansSwitchConfig::builder()
+                .timeout(Duration::from_secs(10))
+                .interval(Duration::from_secs(10))
+                .build()
+                .is_err()
+        );
+        assert!(
+            DeadMansSwitchConfig::builder()
+                .timeout(Duration::from_millis(500))
+                .interval(Duration::from_millis(100))
+                .build()
+                .is_err()
+        );
+    }
+}
@@ -0,0 +1,331 @@
+//! Parsed representation of error strings returned by Kraken, in REST responses
+//! and websockets order statuses.
+//!
+//! These are encoded as:
+//! `<char-severity code><string-error category>:<string-error type>[:<string-extra info>]`
+//! <https://support.kraken.com/hc/en-us/articles/360001491786-API-error-messages>
+
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::{
+    convert::Infallible,
+    fmt::{self, Display},
+    str::FromStr,
+};
+
+/// An error (or warning) reported by Kraken
+#[derive(Clone, Debug, Eq, PartialEq, Hash)]
+pub struct KrakenError {
+    /// Whether this is an error or a warning
+    pub severity: KrakenErrorSeverity,
+    /// The category of the error, e.g. "API" for "EAPI:Invalid nonce"
+    pub category: KrakenErrorCategory,
+    /// The well-known error type, if we recognize it
+    pub kind: KrakenErrorKind,
+    /// The error type as a string, e.g. "Invalid nonce" for "EAPI:Invalid nonce"
+    pub message: String,
+    /// Extra info, e.g. "volume" for "EGeneral:Invalid arguments:volume"
+    pub extra: Option<String>,
+}
+
+/// The severity of a Kraken error
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash)]
+pub enum KrakenErrorSeverity {
+    /// E
+    Error,
+    /// W
+    Warning,
+}
+
+/// The category of a Kraken error
+#[derive(Clone, Debug, Eq, PartialEq, Hash)]
+#[non_exhaustive]
+pub enum KrakenErrorCategory {
+    /// General
+    General,
+    /// API
+    Api,
+    /// Query
+    Query,
+    /// Order
+    Order,
+    /// Trade
+    Trade,
+    /// Funding
+    Funding,
+    /// Service
+    Service,
+    /// Session
+    Session,
+    /// Some other category
+    Other(String),
+}
+
+impl KrakenErrorCategory {
+    fn as_str(&self) -> &str {
+        match self {
+            Self::General => "General",
+            Self::Api => "API",
+            Self::Query => "Query",
+            Self::Order => "Order",
+            Self::Trade => "Trade",
+            Self::Funding => "Funding",
+            Self::Service => "Service",
+            Self::Session => "Session",
+            Self::Other(other) => other,
+        }
+    }
+}
+
+impl From<&str> for KrakenErrorCategory {
+    fn from(src: &str) -> Self {
+        match src {
+            "General" => Self::General,
+            "API" => Self::Api,
+            "Query" => Self::Query,
+            "Order" => Self::Order,
+            "Trade" => Self::Trade,
+            "Funding" => Self::Funding,
+            "Service" => Self::Service,
+            "Session" => Self::Session,
+            other => Self::Other(other.to_owned()),
+        }
+    }
+}
+
+/// Well-known types of Kraken errors
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash)]
+#[non_exhaustive]
+pub enum KrakenErrorKind {
+    /// Invalid arguments
+    InvalidArguments,
+    /// Permission denied
+    PermissionDenied,
+    /// Unknown method
+    UnknownMethod,
+    /// Temporary lockout
+    TemporaryLockout,
+    /// Invalid key
+    InvalidKey,
+    /// Invalid signature
+    InvalidSignature,
+    /// Invalid nonce
+    InvalidNonce,
+    /// Invalid session
+    InvalidSession,
+    /// Bad request
+    BadRequest,
+    /// Feature disabled
+    FeatureDisabled,
+    /// Rate limit exceeded (API or Order category)
+    RateLimitExceeded,
+    /// Insufficient funds
+    InsufficientFunds,
+    /// Insufficient margin
+    InsufficientMargin,
+    /// Margin allowance exceeded
+    MarginAllowanceExceeded,
+    /// Cannot open position
+    CannotOpenPosition,
+    /// Cannot open opposing position
+    CannotOpenOpposingPosition,
+    /// Order minimum not met
+    OrderMinimumNotMet,
+    /// Orders limit exceeded
+    OrdersLimitExceeded,
+    /// Positions limit exceeded
+    PositionsLimitExceeded,
+    /// Unknown order
+    UnknownOrder,
+    /// Unknown position
+    UnknownPosition,
+    /// Post only order (a post-only order would have taken liquidity)
+    PostOnlyOrder,
+    /// Invalid price
+    InvalidPrice,
+    /// Invalid request
+    InvalidRequest,
+    /// Unknown asset pair
+    UnknownAssetPair,
+    /// Unknown asset
+    UnknownAsset,
+    /// Service unavailable
+    Unavailable,
+    /// Service busy
+    Busy,
+    /// Market in cancel_only mode
+    MarketInCancelOnlyMode,
+    /// Market in post_only mode
+    MarketInPostOnlyMode,
+    /// Deadline elapsed
+    DeadlineElapsed,
+    /// Some other error
+    Other,
+}
+
+impl From<&str> for KrakenErrorKind {
+    fn from(src: &str) -> Self {
+        match src {
+            "Invalid arguments" => Self::InvalidArguments,
+            "Permission denied" => Self::PermissionDenied,
+            "Unknown method" => Self::UnknownMethod,
+            "Temporary lockout" => Self::TemporaryLockout,
+            "Invalid key" => Self::InvalidKey,
+            "Invalid signature" => Self::InvalidSignature,
+            "Invalid nonce" => Self::InvalidNonce,
+            "Invalid session" => Self::InvalidSession,
+            "Bad request" => Self::BadRequest,
+            "Feature disabled" => Self::FeatureDisabled,
+            "Rate limit exceeded" => Self::RateLimitExceeded,
+            "Insufficient funds" => Self::InsufficientFunds,
+            "Insufficient margin" => Self::InsufficientMargin,
+            "Margin allowance exceeded" => Self::MarginAllowanceExceeded,
+            "Cannot open position" => Self::CannotOpenPosition,
+            "Cannot open opposing position" => Self::CannotOpenOpposingPosition,
+            "Order minimum not met" => Self::OrderMinimumNotMet,
+            "Orders limit exceeded" => Self::OrdersLimitExceeded,
+            "Positions limit exceeded" => Self::PositionsLimitExceeded,
+            "Unknown order" => Self::UnknownOrder,
+            "Unknown position" => Self::UnknownPosition,
+            "Post only order" => Self::PostOnlyOrder,
+            "Invalid price" => Self::InvalidPrice,
+            "Invalid request" => Self::InvalidRequest,
+            "Unknown asset pair" => Self::UnknownAssetPair,
+            "Unknown asset" => Self::UnknownAsset,
+            "Unavailable" => Self::Unavailable,
+            "Busy" => Self::Busy,
+            "Market in cancel_only mode" => Self::MarketInCancelOnlyMode,
+            "Market in post_only mode" => Self::MarketInPostOnlyMode,
+            "Deadline elapsed" => Self::DeadlineElapsed,
+            _ => Self::Other,
+        }
+    }
+}
+
+impl KrakenError {
+    /// Parse a Kraken error string. This never fails: strings which don't match
+    /// Kraken's format become errors with category `Other("")` and kind `Other`.
+    pub fn parse(src: &str) -> Self {
+        let Some((prefix, rest)) = src.split_once(':') else {
+            return Self::unrecognized(src);
+        };
+        let severity = match prefix.chars().next() {
+            Some('E') => KrakenErrorSeverity::Error,
+            Some('W') => KrakenErrorSeverity::Warning,
+            _ => return Self::unrecognized(src),
+        };
+        let category = KrakenErrorCategory::from(&prefix[1..]);
+        let (message, extra) = match rest.split_once(':') {
+            Some((message, extra)) => (message, Some(extra.to_owned())),
+            None => (rest, None),
+        };
+        Self {
+            severity,
+            category,
+            kind: KrakenErrorKind::from(message),
+            message: message.to_owned(),
+            extra,
+        }
+    }
+
+    /// True if this is an error rather than a warning
+    pub fn is_error(&self) -> bool {
+        self.severity == KrakenErrorSeverity::Error
+    }
+
+    fn unrecognized(src: &str) -> Self {
+        Self {
+            severity: KrakenErrorSeverity::Error,
+            category: KrakenErrorCategory::Other(String::new()),
+            kind: KrakenErrorKind::Other,
+            message: src.to_owned(),
+            extra: None,
+        }
+    }
+}
+
+impl FromStr for KrakenError {
+    type Err = Infallible;
+    fn from_str(src: &str) -> Result<Self, Self::Err> {
+        Ok(Self::parse(src))
+    }
+}
+
+impl Display for KrakenError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.category == KrakenErrorCategory::Other(String::new()) {
+            return write!(f, "{}", self.message);
+        }
+        let severity = match self.severity {
+            KrakenErrorSeverity::Error => 'E',
+            KrakenErrorSeverity::Warning => 'W',
+        };
+        write!(f, "{}{}:{}", severity, self.category.as_str(), self.message)?;
+        if let Some(extra) = self.extra.as_ref() {
+            write!(f, ":{}", extra)?;
+        }
+        Ok(())
+    }
+}
+
+impl Serialize for KrakenError {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for KrakenError {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let src = String::deserialize(deserializer)?;
+        Ok(Self::parse(&src))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_kraken_errors() {
+        let err = KrakenError::parse("EAPI:Invalid nonce");
+        assert_eq!(err.severity, KrakenErrorSeverity::Error);
+        assert_eq!(err.category, KrakenErrorCategory::Api);
+        assert_eq!(err.kind, KrakenErrorKind::InvalidNonce);
+        assert_eq!(err.extra, None);
+
+        let err = KrakenError::parse("EOrder:Rate limit exceeded");
+        assert_eq!(err.category, KrakenErrorCategory::Order);
+        assert_eq!(err.kind, KrakenErrorKind::RateLimitExceeded);
+
+        let err = KrakenError::parse("EGeneral:Invalid arguments:volume");
+        assert_eq!(err.category, KrakenErrorCategory::General);
+        assert_eq!(err.kind, KrakenErrorKind::InvalidArguments);
+        assert_eq!(err.extra.as_deref(), Some("volume"));
+
+        let err = KrakenError::parse("WFunding:Something new");
+        assert_eq!(err.severity, KrakenErrorSeverity::Warning);
+        assert_eq!(err.category, KrakenErrorCategory::Funding);
+        assert_eq!(err.kind, KrakenErrorKind::Other);
+        assert_eq!(err.message, "Something new");
+
+        let err = KrakenError::parse("Currency pair not supported XBT/USDD");
+        assert_eq!(err.category, KrakenErrorCategory::Other(String::new()));
+        assert_eq!(err.kind, KrakenErrorKind::Other);
+    }
+
+    #[test]
+    fn test_kraken_error_round_trip() {
+        for src in [
+            "EAPI:Rate limit exceeded",
+            "EService:Market in post_only mode",
+            "EGeneral:Invalid arguments:Index unavailable",
+            "EOrder:Insufficient funds",
+            "Currency pair not supported XBT/USDD",
+        ] {
+            assert_eq!(KrakenError::parse(src).to_string(), src);
+        }
+
+        let errors: Vec<KrakenError> = serde_json::from_str(r#"["EOrder:Post only order"]"#).unwrap();
+        assert_eq!(errors[0].kind, KrakenErrorKind::PostOnlyOrder);
+        assert_eq!(serde_json::to_string(&errors).unwrap(), r#"["EOrder:Post only order"]"#);
+    }
+}
@@ -2,22 +2,21 @@
 //! and serialization. It is similar to krakenex python code, but less messy.
 //! <https://github.com/veox/python3-krakenex/blob/master/krakenex/api.py>
 
+use crate::{
+    KrakenError, MonotonicNonce, NonceProvider, OrderValidationError, OrderValidator, RetryPolicy,
+    rate_limit::{RateLimitConfig, RateLimiter},
+};
 use base64ct::{Base64, Encoding};
 use displaydoc::Display;
 use hmac::{Hmac, Mac};
 use reqwest::{
+    StatusCode,
     blocking::Response,
     header::{HeaderMap, HeaderValue, InvalidHeaderValue},
 };
 use serde::{Deserialize, Serialize, de::DeserializeOwned};
 use sha2::{Digest, Sha256, Sha512};
-use std::{
-    convert::TryFrom,
-    io::Error as IoError,
-    path::Path,
-    str::FromStr,
-    time::{Duration, SystemTime},
-};
+use std::{convert::TryFrom, io::Error as IoError, path::Path, str::FromStr, sync::Arc, time::Duration};
 use url::{ParseError as UrlParseError, Url};
 
 /// Configuration needed to initialize a Kraken client.
@@ -27,9 +26,21 @@ use url::{ParseError as UrlParseError, Url};
 pub struct KrakenRestConfig {
     /// The timeout to use for http connections
     /// Recommended is to use 30s.
-    timeout: Duration,
+    pub(crate) timeout: Duration,
     /// The credentials (if using private APIs)
-    creds: KrakenCredentials,
+    pub(crate) creds: KrakenCredentials,
+    /// Base url to contact kraken at
+    pub(crate) base_url: String,
+    /// Kraken Api version to connect to
+    pub(crate) api_version: u16,
+    /// The client-side rate limiter configuration (if rate limiting is enabled)
+    pub(crate) rate_limit: Option<RateLimitConfig>,
+    /// The policy for retrying failed calls (if retries are enabled)
+    pub(crate) retry_policy: Option<RetryPolicy>,
+    /// The source of nonces for private calls
+    pub(crate) nonce_provider: Arc<dyn NonceProvider>,
+    /// The validator to check orders against before placing them (if validation is enabled)
+    pub(crate) order_validator: Option<Arc<OrderValidator>>,
 }
 
 impl KrakenRestConfig {
@@ -37,6 +48,17 @@ impl KrakenRestConfig {
     pub fn builder() -> KrakenRestConfigBuilder {
         Default::default()
     }
+
+    /// Parse the base url, making sure that request paths are joined onto the full base url,
+    /// including any path prefix
+    pub(crate) fn parsed_base_url(&self) -> Result<Url> {
+        let mut base_url = Url::from_str(&self.base_url)?;
+        if !base_url.path().ends_with('/') {
+            let path = format!("{}/", base_url.path());
+            base_url.set_path(&path);
+        }
+        Ok(base_url)
+    }
 }
 
 impl Default for KrakenRestConfig {
@@ -44,6 +66,12 @@ impl Default for KrakenRestConfig {
         Self {
             timeout: Duration::new(30, 0),
             creds: Default::default(),
+            base_url: DEFAULT_BASE_URL.to_owned(),
+            api_version: 0,
+            rate_limit: None,
+            retry_policy: None,
+            nonce_provider: Arc::new(MonotonicNonce::new()),
+            order_validator: None,
         }
     }
 }
@@ -72,8 +100,53 @@ impl KrakenRestConfigBuilder {
         self
     }
 
+    /// Set the base url to contact kraken at. The default is `https://api.kraken.com/`.
+    /// This can be used to target a proxy, a sandbox environment, or a local mock server.
+    pub fn base_url(mut self, base_url: impl Into<String>) -> Self {
+        self.config.base_url = base_url.into();
+        self
+    }
+
+    /// Set the Kraken API version to use in request paths. The default is 0.
+    pub fn api_version(mut self, api_version: u16) -> Self {
+        self.config.api_version = api_version;
+        self
+    }
+
+    /// Enable client-side rate limiting of private APIs, modelling Kraken's rate limit counters.
+    /// This is disabled by default.
+    pub fn rate_limit(mut self, rate_limit: RateLimitConfig) -> Self {
+        self.config.rate_limit = Some(rate_limit);
+        self
+    }
+
+    /// Enable automatic retries of calls which fail for transient reasons, according to a policy.
+    /// Private calls are signed again with a fresh nonce for each attempt. This is disabled by default.
+    pub fn retry_policy(mut self, retry_policy: RetryPolicy) -> Self {
+        self.config.retry_policy = Some(retry_policy);
+        self
+    }
+
+    /// Set the source of nonces for private calls. The default is a [MonotonicNonce] in milliseconds.
+    /// Clients created from clones of this config share the nonce provider.
+    /// If several processes use the same API key, use a [crate::FileNonce] in each of them.
+    pub fn nonce_provider(mut self, nonce_provider: impl NonceProvider + 'static) -> Self {
+        self.config.nonce_provider = Arc::new(nonce_provider);
+        self
+    }
+
+    /// Validate orders against their asset pairs before placing them, failing with [Error::InvalidOrder]
+    /// rather than sending orders which Kraken would reject. This is disabled by default.
+    pub fn order_validator(mut self, order_validator: OrderValidator) -> Self {
+        self.config.order_validator = Some(Arc::new(order_validator));
+        self
+    }
+
     /// Create a KrakenRestConfig
     pub fn build(self) -> core::result::Result<KrakenRestConfig, BuilderError> {
+        if Url::from_str(&self.config.base_url).is_err() {
+            return Err(BuilderError::InvalidUrl(self.config.base_url));
+        }
         Ok(self.config)
     }
 }
@@ -84,6 +157,10 @@ impl KrakenRestConfigBuilder {
 pub enum BuilderError {
     /// A websockets token is required for these subscriptions
     MissingWsToken,
+    /// Invalid url: {0}
+    InvalidUrl(String),
+    /// Interval {0:?} must be shorter than the timeout {1:?}, which must be at least a second
+    InvalidInterval(Duration, Duration),
 }
 
 /// Credentials needed to use private Kraken APIs.
@@ -120,25 +197,32 @@ pub struct KrakenRestClient {
     base_url: Url,
     /// Kraken Api version to connect to
     version: u16,
+    /// Client-side rate limiter (if enabled)
+    rate_limiter: Option<RateLimiter>,
 }
 
+// The default base url for the Kraken REST API
+const DEFAULT_BASE_URL: &str = "https://api.kraken.com/";
+
 // KrakenRS version
 const KRAKEN_RS_VERSION: Option<&'static str> = option_env!("CARGO_PKG_VERSION");
 
 impl TryFrom<KrakenRestConfig> for KrakenRestClient {
     type Error = Error;
     fn try_from(config: KrakenRestConfig) -> Result<Self> {
-        let base_url = Url::from_str("https://api.kraken.com/")?;
-        let version = 0;
+        let base_url = config.parsed_base_url()?;
+        let version = config.api_version;
         let client = reqwest::blocking::ClientBuilder::new()
-            .user_agent(format!("krakenrs/{}", KRAKEN_RS_VERSION.unwrap_or("unknown")))
+            .user_agent(user_agent())
             .timeout(Some(config.timeout))
             .build()?;
+        let rate_limiter = config.rate_limit.map(RateLimiter::new);
         Ok(Self {
             base_url,
             version,
             client,
             config,
+            rate_limiter,
         })
     }
 }
@@ -156,6 +240,16 @@ impl KrakenRestClient {
         self.config.creds = creds;
     }
 
+    /// Get the client-side rate limiter, if rate limiting is enabled
+    pub fn rate_limiter(&self) -> Option<&RateLimiter> {
+        self.rate_limiter.as_ref()
+    }
+
+    /// Get the order validator, if order validation is enabled
+    pub fn order_validator(&self) -> Option<&OrderValidator> {
+        self.config.order_validator.as_deref()
+    }
+
     /// Execute a public API, given method, and object matching the expected schema, and returning expected schema or an error.
     pub fn query_public<D: Serialize, R: DeserializeOwned>(&self, method: &str, query_data: D) -> Result<R> {
         let url_path = format!("/{}/public/{}", self.version, method);
@@ -163,7 +257,10 @@ impl KrakenRestClient {
         let post_data = serde_qs::to_string(&query_data)?;
         //log::debug!("post_data = {}", post_data);
 
-        self.query(&url_path, HeaderMap::new(), post_data)
+        // Public APIs are all idempotent
+        self.with_retries(method, true, || {
+            self.query(&url_path, HeaderMap::new(), post_data.clone())
+        })
     }
 
     /// Execute a private API, given method, and object matching the expected schema, and returning expected schema or an error.
@@ -174,80 +271,161 @@ impl KrakenRestClient {
 
         let url_path = format!("/{}/private/{}", self.version, method);
 
-        // Sign the query data and url path, resulting in encoded post_data with nonce, and a signature.
-        let (post_data, sig) = self.sign(query_data, &url_path)?;
+        // Convert the data to a query string
+        let qs = serde_qs::to_string(&query_data)?;
+
+        let retryable_call = self
+            .config
+            .retry_policy
+            .as_ref()
+            .is_some_and(|policy| policy.is_retryable_call(method, &qs));
+
+        self.with_retries(method, retryable_call, || {
+            if let Some(rate_limiter) = self.rate_limiter.as_ref() {
+                rate_limiter.acquire(method)?;
+            }
+
+            // Sign the query data and url path, resulting in encoded post_data with nonce, and a signature.
+            // This happens on every attempt, so that each attempt has a fresh nonce.
+            let (post_data, sig) = self.sign(&qs, &url_path)?;
 
-        let mut headers = HeaderMap::new();
-        headers.insert("API-Key", HeaderValue::from_str(&self.config.creds.key)?);
-        headers.insert("API-Sign", HeaderValue::from_str(&sig)?);
+            let mut headers = HeaderMap::new();
+            headers.insert("API-Key", HeaderValue::from_str(&self.config.creds.key)?);
+            headers.insert("API-Sign", HeaderValue::from_str(&sig)?);
 
-        self.query(&url_path, headers, post_data)
+            self.query(&url_path, headers, post_data)
+        })
+    }
+
+    /// Make attempts at a call until it succeeds, or the retry policy says to stop
+    fn with_retries<R>(&self, method: &str, retryable_call: bool, mut attempt: impl FnMut() -> Result<R>) -> Result<R> {
+        let Some(policy) = self.config.retry_policy.as_ref().filter(|_| retryable_call) else {
+            return attempt();
+        };
+        let mut attempts = 0;
+        loop {
+            attempts += 1;
+            match attempt() {
+                Err(err) if attempts < policy.max_attempts && policy.is_retryable(&err) => {
+                    let backoff = policy.backoff(attempts);
+                    log::warn!(
+                        "{} failed (attempt {}/{}), retrying in {:?}: {}",
+                        method,
+                        attempts,
+                        policy.max_attempts,
+                        backoff,
+                        err
+                    );
+                    std::thread::sleep(backoff);
+                }
+                result => return result,
+            }
+        }
     }
 
     /// Send a query (public or private) to kraken API, and interpret response as JSON
     fn query<R: DeserializeOwned>(&self, url_path: &str, headers: HeaderMap, post_data: String) -> Result<R> {
-        let url = self.base_url.join(url_path)?;
+        let url = self.base_url.join(url_path.trim_start_matches('/'))?;
 
         //log::trace!("POST {}\n{}", url_path, post_data);
 
         let response = self.client.post(url).headers(headers).body(post_data).send()?;
-        if !(response.status() == 200 || response.status() == 201 || response.status() == 202) {
+        if !is_success_status(response.status()) {
             return Err(Error::BadStatus(response));
         }
 
         let text = response.text()?;
-
-        let result: R = serde_json::from_str(&text).map_err(|err| Error::Json(err, text.clone()))?;
-        Ok(result)
+        parse_response(self.config.retry_policy.as_ref(), text)
     }
 
-    /// Serialize a json payload, adding a nonce, and producing a signature using Kraken's scheme
-    ///
-    /// Arguments:
-    /// * query_data for the request, with "nonce" value not yet assigned
-    /// * url path for the request
-    ///
-    /// Returns:
-    /// * post_data for the request (encoded query data, with nonce added)
-    /// * signature over that post data string
-    fn sign<D: Serialize>(&self, query_data: D, url_path: &str) -> Result<(String, String)> {
-        // Generate a nonce to become part of the postdata
-        let nonce = Self::nonce()?;
-        // Convert the data to a query string
-        let qs = serde_qs::to_string(&query_data)?;
-        // Append nonce to query string
-        let post_data = if qs.is_empty() {
-            format!("nonce={}", nonce)
-        } else {
-            format!("nonce={}&{}", nonce, qs)
-        };
+    /// Add a nonce to an encoded query string, and produce a signature using Kraken's scheme
+    fn sign(&self, qs: &str, url_path: &str) -> Result<(String, String)> {
+        sign_query(
+            &self.config.creds.secret,
+            qs,
+            url_path,
+            self.config.nonce_provider.next_nonce()?,
+        )
+    }
+}
 
-        let sha2_result = {
-            let mut hasher = Sha256::default();
-            hasher.update(nonce.to_string());
-            hasher.update(&post_data);
-            hasher.finalize()
-        };
+/// Add a nonce to an encoded query string, and produce a signature using Kraken's scheme
+///
+/// Arguments:
+/// * secret of the credentials
+/// * query string for the request, with "nonce" value not yet assigned
+/// * url path for the request
+/// * nonce to assign
+///
+/// Returns:
+/// * post_data for the request (encoded query data, with nonce added)
+/// * signature over that post data string
+pub(crate) fn sign_query(secret: &str, qs: &str, url_path: &str, nonce: u64) -> Result<(String, String)> {
+    // Append nonce to query string
+    let post_data = if qs.is_empty() {
+        format!("nonce={}", nonce)
+    } else {
+        format!("nonce={}&{}", nonce, qs)
+    };
+
+    let sig = compute_signature(secret, url_path, nonce, &post_data)?;
+    Ok((post_data, sig))
+}
 
-        let hmac_sha_key = Base64::decode_vec(&self.config.creds.secret).map_err(Error::SigningB64)?;
+/// The user agent to send with requests
+pub(crate) fn user_agent() -> String {
+    format!("krakenrs/{}", KRAKEN_RS_VERSION.unwrap_or("unknown"))
+}
 
-        type HmacSha = Hmac<Sha512>;
-        let mut mac = HmacSha::new_from_slice(&hmac_sha_key).expect("Hmac should work with any key length");
-        mac.update(url_path.as_bytes());
-        mac.update(&sha2_result);
-        let mac = mac.finalize().into_bytes();
+/// Check if an http status is one which kraken returns results with
+pub(crate) fn is_success_status(status: StatusCode) -> bool {
+    status == 200 || status == 201 || status == 202
+}
 
-        let sig = Base64::encode_string(&mac);
-        Ok((post_data, sig))
+/// Interpret the body of a response as JSON.
+///
+/// If there is a retry policy, kraken errors are surfaced here, so that they can be retried.
+pub(crate) fn parse_response<R: DeserializeOwned>(retry_policy: Option<&RetryPolicy>, text: String) -> Result<R> {
+    if let Some(policy) = retry_policy
+        && let Ok(ErrorsOnly { error }) = serde_json::from_str::<ErrorsOnly>(&text)
+        && !error.is_empty()
+    {
+        let err = Error::KrakenErrors(error);
+        if policy.is_retryable(&err) {
+            return Err(err);
+        }
     }
 
-    /// Get a nonce as suggsted by Kraken
-    fn nonce() -> Result<u64> {
-        Ok(SystemTime::now()
-            .duration_since(SystemTime::UNIX_EPOCH)
-            .map_err(|_| Error::TimeError)?
-            .as_millis() as u64)
-    }
+    let result: R = serde_json::from_str(&text).map_err(|err| Error::Json(err, text.clone()))?;
+    Ok(result)
+}
+
+// Just the errors of a kraken response
+#[derive(Deserialize)]
+struct ErrorsOnly {
+    #[serde(default)]
+    error: Vec<KrakenError>,
+}
+
+/// Compute the signature of a private request using Kraken's scheme:
+/// HMAC-SHA512 of (url path + SHA256(nonce + post data)), keyed with the base64-decoded secret.
+pub(crate) fn compute_signature(secret: &str, url_path: &str, nonce: u64, post_data: &str) -> Result<String> {
+    let sha2_result = {
+        let mut hasher = Sha256::default();
+        hasher.update(nonce.to_string());
+        hasher.update(post_data);
+        hasher.finalize()
+    };
+
+    let hmac_sha_key = Base64::decode_vec(secret).map_err(Error::SigningB64)?;
+
+    type HmacSha = Hmac<Sha512>;
+    let mut mac = HmacSha::new_from_slice(&hmac_sha_key).expect("Hmac should work with any key length");
+    mac.update(url_path.as_bytes());
+    mac.update(&sha2_result);
+    let mac = mac.finalize().into_bytes();
+
+    Ok(Base64::encode_string(&mac))
 }
 
 /// Alias for Result that contains the error type for this crate
@@ -255,6 +433,7 @@ pub type Result<T> = core::result::Result<T, Error>;
 
 /// An error that can be generated from the low-level kraken client
 #[derive(Display, Debug)]
+#[non_exhaustive]
 pub enum Error {
     /// Failed forming URI: {0}
     Url(UrlParseError),
@@ -262,22 +441,48 @@ pub enum Error {
     Reqwest(reqwest::Error),
     /// kraken returned bad status: {0:?}
     BadStatus(Response),
+    /// kraken returned bad status: {0:?}
+    #[cfg(feature = "async")]
+    AsyncBadStatus(reqwest::Response),
     /// json deserialization failed: {0}, body was: {1}
     Json(serde_json::Error, String),
     /// Kraken errors present: {0:?}
-    KrakenErrors(Vec<String>),
+    KrakenErrors(Vec<KrakenError>),
     /// Missing result json
     MissingResultJson,
     /// Missing credentials required for private APIs
     MissingCredentials,
     /// Time error (preventing nonce computation)
     TimeError,
+    /// Error accessing nonce file: {0}
+    NonceFile(IoError),
     /// Error serializing query string: {0}
     SerializingQs(serde_qs::Error),
     /// base64 error during signing: {0}
     SigningB64(base64ct::Error),
     /// Invalid header value: {0}
     InvalidHeader(InvalidHeaderValue),
+    /// Background thread failed: {0}
+    Thread(IoError),
+    /// No response over websockets within {0:?}
+    WsTimeout(Duration),
+    /// Invalid order batch: {0}
+    InvalidBatch(String),
+    /// Order failed validation: {0}
+    InvalidOrder(OrderValidationError),
+    /// Not supported by Kraken's websockets API: {0}
+    WsUnsupported(String),
+    /// Rate limited by client-side rate limiter, retry after {retry_after:?}
+    RateLimited {
+        /// How long until the call would be allowed
+        retry_after: Duration,
+    },
+}
+
+impl From<OrderValidationError> for Error {
+    fn from(src: OrderValidationError) -> Self {
+        Self::InvalidOrder(src)
+    }
 }
 
 impl From<UrlParseError> for Error {
@@ -303,3 +508,19 @@ impl From<serde_qs::Error> for Error {
         Self::SerializingQs(src)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // The example from kraken's REST API authentication docs
+    #[test]
+    fn test_compute_signature() {
+        let secret = "kQH5HW/8p1uGOVjbgWA7FunAmGO8lsSUXNsu3eow76sz84Q18fWxnyRzBHCd3pd5nE9qa99HAZtuZuj6F1huXg==";
+        let post_data = "nonce=1616492376594&ordertype=limit&pair=XBTUSD&price=37500&type=buy&volume=1.25";
+        assert_eq!(
+            compute_signature(secret, "/0/private/AddOrder", 1616492376594, post_data).unwrap(),
+            "4/dpxb3iT4tp/ZCVEwSnEsLxx0bqyhLpdfOpc6fn7OR8+UClSV5n9E6aSS8MPtnRfp32bAb0nmbRn6H8ndwLUQ=="
+        );
+    }
+}
@@ -9,6 +9,7 @@ use serde::{
 ///
 /// * OHLC candle (see issue #3)
 /// * Get Recent Trades
+/// * Get Recent Spreads
 ///
 /// These endpoints return a json object with two members:
 /// * one page of data is returned at a dynamically-changing (but uninteresting) key,
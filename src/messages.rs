@@ -1,12 +1,13 @@
 //! Structures representing json schema sent to and from Kraken REST API
 //! <https://docs.kraken.com/rest/>
 
-use crate::serde_helpers::{comma_separated, default_on_error};
-use crate::{Error, LastAndData, Result};
+use crate::serde_helpers::{comma_separated, default_on_error, display_fromstr};
+use crate::{ConditionalClose, Error, KrakenError, LastAndData, OrderValidationError, Result};
 use displaydoc::Display;
-use rust_decimal::Decimal;
+use rust_decimal::{Decimal, RoundingStrategy};
 use serde::{Deserialize, Serialize};
 use std::collections::{BTreeSet, HashMap};
+use std::fmt;
 use std::str::FromStr;
 
 /// Kraken responds to APIs with a json body consisting of "error:" and "result:" fields.
@@ -16,7 +17,7 @@ use std::str::FromStr;
 #[derive(Debug, Deserialize)]
 pub struct KrakenResult<ResultJson> {
     /// Kraken API returns error strings in an array marked "error"
-    pub error: Vec<String>,
+    pub error: Vec<KrakenError>,
     /// Kraken API returns results here, separated from error
     /// Sometimes result is omitted if errors occured.
     pub result: Option<ResultJson>,
@@ -88,33 +89,152 @@ pub struct AssetPairsRequest {
     /// A comma-separated list of kraken asset pair strings
     #[serde(skip_serializing_if = "String::is_empty")]
     pub pair: String,
+    /// Which info to retrieve (default all info)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub info: Option<AssetPairInfo>,
+}
+
+/// Which info to retrieve about asset pairs
+/// These are lowercase strings in the query string
+#[derive(Debug, Display, Default, Copy, Clone, Serialize, Deserialize, Eq, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum AssetPairInfo {
+    /// info
+    #[default]
+    Info,
+    /// leverage
+    Leverage,
+    /// fees
+    Fees,
+    /// margin
+    Margin,
+}
+
+/// The trading status of an asset pair
+/// These are snake_case strings in json
+#[derive(Debug, Display, Copy, Clone, Serialize, Deserialize, Eq, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum AssetPairStatus {
+    /// online
+    Online,
+    /// cancel_only
+    CancelOnly,
+    /// post_only
+    PostOnly,
+    /// limit_only
+    LimitOnly,
+    /// reduce_only
+    ReduceOnly,
 }
 
 /// (Substructure within) Result of kraken public "Asset Pairs" API call
-#[derive(Clone, Debug, Serialize, Deserialize)]
+///
+/// When only some of the info is requested (see [AssetPairInfo]), the other fields are defaulted.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
 pub struct AssetPair {
     /// Alternate pair name
+    #[serde(alias = "altname")]
     pub alt_name: Option<String>,
     /// Web-sockets pair name (if available)
     pub wsname: Option<String>,
     /// Asset class of base component
+    #[serde(default)]
     pub aclass_base: String,
     /// Asset id of base component
+    #[serde(default)]
     pub base: String,
     /// Asset class of quote component
+    #[serde(default)]
     pub aclass_quote: String,
     /// Asset id of quote component
+    #[serde(default)]
     pub quote: String,
     /// Scaling decimal places for pair
+    #[serde(default)]
     pub pair_decimals: u64,
+    /// Scaling decimal places for cost
+    #[serde(default)]
+    pub cost_decimals: u64,
     /// Scaling decimal places for volume
+    #[serde(default)]
     pub lot_decimals: u64,
     /// Amount to multiply lot volume by to get currency volume
+    #[serde(default)]
     pub lot_multiplier: u64,
-    /// Fee schedule array in [volume, percent] tuples
-    pub fees: Vec<Vec<Decimal>>,
+    /// Leverage amounts available when buying
+    #[serde(default)]
+    pub leverage_buy: Vec<u32>,
+    /// Leverage amounts available when selling
+    #[serde(default)]
+    pub leverage_sell: Vec<u32>,
+    /// Taker fee schedule
+    #[serde(default)]
+    pub fees: Vec<FeeTier>,
+    /// Maker fee schedule
+    #[serde(default)]
+    pub fees_maker: Vec<FeeTier>,
+    /// Volume discount currency
+    #[serde(default)]
+    pub fee_volume_currency: Option<String>,
+    /// Margin call level (%)
+    #[serde(default)]
+    pub margin_call: Option<u32>,
+    /// Stop-out/liquidation margin level (%)
+    #[serde(default)]
+    pub margin_stop: Option<u32>,
     /// Minimum order size (in terms of base currency)
+    #[serde(default)]
     pub ordermin: Option<Decimal>,
+    /// Minimum order cost (in terms of quote currency)
+    #[serde(default)]
+    pub costmin: Option<Decimal>,
+    /// Minimum increment between valid price levels
+    #[serde(default)]
+    pub tick_size: Option<Decimal>,
+    /// Trading status of the pair (None if not recognized)
+    #[serde(deserialize_with = "default_on_error::deserialize", default)]
+    pub status: Option<AssetPairStatus>,
+    /// Maximum long margin position size (in terms of base currency)
+    #[serde(default)]
+    pub long_position_limit: Option<u64>,
+    /// Maximum short margin position size (in terms of base currency)
+    #[serde(default)]
+    pub short_position_limit: Option<u64>,
+}
+
+impl AssetPair {
+    /// The minimum increment between prices: the tick size if known, or else the last of `pair_decimals` places
+    pub fn price_increment(&self) -> Decimal {
+        self.tick_size
+            .filter(|tick_size| !tick_size.is_zero())
+            .unwrap_or_else(|| Decimal::new(1, self.pair_decimals as u32))
+    }
+
+    /// Round a volume down to `lot_decimals` places
+    pub fn round_volume(&self, volume: Decimal) -> Decimal {
+        volume.round_dp_with_strategy(self.lot_decimals as u32, RoundingStrategy::ToZero)
+    }
+
+    /// Round a price to a multiple of the price increment, down for buy orders and up for sell orders,
+    /// so that a limit price never becomes more aggressive
+    pub fn round_price(&self, bs_type: &BsType, price: Decimal) -> Decimal {
+        let strategy = match bs_type {
+            BsType::Buy => RoundingStrategy::ToNegativeInfinity,
+            BsType::Sell => RoundingStrategy::ToPositiveInfinity,
+        };
+        let increment = self.price_increment();
+        (price / increment).round_dp_with_strategy(0, strategy) * increment
+    }
+}
+
+/// A tier of a fee schedule
+#[derive(Clone, Debug, Serialize, Deserialize, Eq, PartialEq)]
+#[serde(expecting = "expecting [<volume>, <percent>] array")]
+pub struct FeeTier {
+    /// The 30-day volume from which this tier applies (in terms of the fee volume currency)
+    pub volume: Decimal,
+    /// The fee, expressed as a %
+    pub percent: Decimal,
 }
 
 /// Type alias for response of AssetPairs API call
@@ -130,12 +250,65 @@ pub struct TickerRequest {
 /// (Substructure within) Result of kraken public "Ticker" API call
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct AssetTickerInfo {
-    /// Ask `[price, whole lot volume, lot volume]`
-    pub a: Vec<String>,
-    /// Bid `[price, whole lot volume, lot volume]`
-    pub b: Vec<String>,
-    /// Closed `[price, lot volume]`
-    pub c: Vec<String>,
+    /// Best ask
+    #[serde(rename = "a")]
+    pub ask: TickerLevel,
+    /// Best bid
+    #[serde(rename = "b")]
+    pub bid: TickerLevel,
+    /// Last trade
+    #[serde(rename = "c")]
+    pub last_trade: TickerTrade,
+    /// Volume
+    #[serde(rename = "v")]
+    pub volume: TickerWindow,
+    /// Volume weighted average price
+    #[serde(rename = "p")]
+    pub vwap: TickerWindow,
+    /// Number of trades
+    #[serde(rename = "t")]
+    pub trades: TickerWindow<u64>,
+    /// Lowest price
+    #[serde(rename = "l")]
+    pub low: TickerWindow,
+    /// Highest price
+    #[serde(rename = "h")]
+    pub high: TickerWindow,
+    /// Opening price today
+    #[serde(rename = "o")]
+    pub open: Decimal,
+}
+
+/// A best ask or bid in the ticker
+#[derive(Clone, Debug, Serialize, Deserialize, Eq, PartialEq)]
+#[serde(expecting = "expecting [<price>, <whole lot volume>, <lot volume>] array")]
+pub struct TickerLevel {
+    /// The price
+    pub price: Decimal,
+    /// The volume at this price, rounded down to a whole number of lots
+    pub whole_lot_volume: Decimal,
+    /// The volume at this price
+    pub lot_volume: Decimal,
+}
+
+/// The last trade in the ticker
+#[derive(Clone, Debug, Serialize, Deserialize, Eq, PartialEq)]
+#[serde(expecting = "expecting [<price>, <lot volume>] array")]
+pub struct TickerTrade {
+    /// The price of the trade
+    pub price: Decimal,
+    /// The volume of the trade
+    pub lot_volume: Decimal,
+}
+
+/// A statistic in the ticker, over today (since midnight UTC) and over the last 24 hours
+#[derive(Clone, Debug, Serialize, Deserialize, Eq, PartialEq)]
+#[serde(expecting = "expecting [<today>, <last 24 hours>] array")]
+pub struct TickerWindow<T = Decimal> {
+    /// Since midnight UTC
+    pub today: T,
+    /// Over the last 24 hours
+    pub last_24h: T,
 }
 
 /// Type alias for response of Ticker API call
@@ -179,6 +352,66 @@ pub struct PublicTrade {
     pub trade_id: u64,
 }
 
+/// A query object to kraken public "Get Order Book" API call
+#[derive(Default, Debug, Serialize, Deserialize)]
+pub struct GetOrderBookRequest {
+    /// An asset pair
+    pub pair: String,
+    /// Maximum number of asks and bids, from 1 to 500. Defaults to 100.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub count: Option<u16>,
+}
+
+/// Response object of Get Order Book API call, keyed by asset pair
+pub type GetOrderBookResponse = HashMap<String, OrderBookDepth>;
+
+/// A sub-object of the order book response
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct OrderBookDepth {
+    /// The asks, from low to high
+    pub asks: Vec<DepthEntry>,
+    /// The bids, from high to low
+    pub bids: Vec<DepthEntry>,
+}
+
+/// A price level in the order book response
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(expecting = "expecting [<price>, <volume>, <timestamp>] array")]
+pub struct DepthEntry {
+    /// The price level
+    pub price: Decimal,
+    /// The total volume at this price level
+    pub volume: Decimal,
+    /// The timestamp of the last update to this price level (seconds since the unix epoch)
+    #[serde(deserialize_with = "rust_decimal::serde::arbitrary_precision::deserialize")]
+    pub timestamp: Decimal,
+}
+
+/// A query object to kraken public "Get Recent Spreads" API call
+#[derive(Default, Debug, Serialize, Deserialize)]
+pub struct GetRecentSpreadsRequest {
+    /// An asset pair
+    pub pair: String,
+    /// Return spread data since given timestamp
+    pub since: Option<String>,
+}
+
+/// Response object of Get Recent Spreads API call
+pub type GetRecentSpreadsResponse = LastAndData<Vec<Spread>>;
+
+/// A sub-object of the recent spreads response
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(expecting = "expecting [<timestamp>, <bid>, <ask>] array")]
+pub struct Spread {
+    /// The timestamp of the spread (seconds since the unix epoch)
+    #[serde(deserialize_with = "rust_decimal::serde::arbitrary_precision::deserialize")]
+    pub timestamp: Decimal,
+    /// The best bid price
+    pub bid: Decimal,
+    /// The best ask price
+    pub ask: Decimal,
+}
+
 /// A query object to kraken public "Get OHLC Data" API call
 #[derive(Clone, Default, Debug, Serialize, Deserialize)]
 pub struct GetOHLCDataRequest {
@@ -260,6 +493,12 @@ pub enum OrderType {
     StopLossLimit,
     /// Take-Profit-Limit
     TakeProfitLimit,
+    /// Trailing-Stop
+    TrailingStop,
+    /// Trailing-Stop-Limit
+    TrailingStopLimit,
+    /// Iceberg
+    Iceberg,
     /// Settle-Position
     SettlePosition,
 }
@@ -286,6 +525,9 @@ pub enum OrderStatus {
 pub struct OrderInfo {
     /// User reference id for the order
     pub userref: UserRefId,
+    /// Client order id for the order, if it has one
+    #[serde(default)]
+    pub cl_ord_id: Option<String>,
     /// Status of the order
     pub status: OrderStatus,
     /// unix timestamp of when the order was placed
@@ -312,6 +554,15 @@ pub struct OrderInfo {
     /// misc info (comma separated list)
     #[serde(with = "comma_separated")]
     pub misc: BTreeSet<MiscInfo>,
+    /// unix timestamp of when the order was closed (closed orders only)
+    #[serde(default)]
+    pub closetm: Option<Decimal>,
+    /// additional info on status, e.g. why the order was canceled (closed orders only)
+    #[serde(default)]
+    pub reason: Option<String>,
+    /// trade ids related to the order (only if trades were requested)
+    #[serde(default)]
+    pub trades: Option<Vec<TxId>>,
 }
 
 /// Possible order flags in Kraken.
@@ -349,6 +600,237 @@ impl FromStr for OrderFlag {
     }
 }
 
+/// The price signal which triggers a stop-loss or take-profit order
+/// These are lowercase strings in json
+///
+/// * last: The last traded price on Kraken (the default)
+/// * index: The index price for the broader market
+#[derive(Debug, Display, Copy, Clone, Serialize, Deserialize, Eq, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum OrderTrigger {
+    /// last
+    Last,
+    /// index
+    Index,
+}
+
+/// Time-in-force of an order
+/// These are uppercase strings in json
+///
+/// * GTC: Good-til-cancelled (the default)
+/// * IOC: Immediate-or-cancel. Any portion of the order which doesn't fill immediately is cancelled.
+/// * GTD: Good-til-date. The order expires at `expiretm`.
+#[derive(Debug, Display, Copy, Clone, Serialize, Deserialize, Eq, PartialEq)]
+#[serde(rename_all = "UPPERCASE")]
+pub enum TimeInForce {
+    /// GTC
+    Gtc,
+    /// IOC
+    Ioc,
+    /// GTD
+    Gtd,
+}
+
+/// Self-trade prevention, which determines what happens when two of our own orders would match
+/// These are kebab-case strings in json
+///
+/// * cancel-newest: The arriving order is canceled (the default)
+/// * cancel-oldest: The resting order is canceled
+/// * cancel-both: Both orders are canceled
+#[derive(Debug, Display, Copy, Clone, Serialize, Deserialize, Eq, PartialEq)]
+#[serde(rename_all = "kebab-case")]
+pub enum SelfTradePrevention {
+    /// cancel-newest
+    CancelNewest,
+    /// cancel-oldest
+    CancelOldest,
+    /// cancel-both
+    CancelBoth,
+}
+
+/// A scheduled start time or expiration time of an order
+///
+/// In Kraken's encoding, "0" means now, "+n" means n seconds from now,
+/// and "n" means the unix timestamp n.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum OrderTime {
+    /// Now
+    Now,
+    /// A number of seconds from now
+    FromNow(u64),
+    /// A unix timestamp
+    At(u64),
+}
+
+impl fmt::Display for OrderTime {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Now => write!(f, "0"),
+            Self::FromNow(secs) => write!(f, "+{}", secs),
+            Self::At(timestamp) => write!(f, "{}", timestamp),
+        }
+    }
+}
+
+impl FromStr for OrderTime {
+    type Err = &'static str;
+    fn from_str(src: &str) -> core::result::Result<OrderTime, Self::Err> {
+        let parse = |src: &str| src.parse::<u64>().map_err(|_| "invalid OrderTime");
+        match src {
+            "0" => Ok(OrderTime::Now),
+            _ => match src.strip_prefix('+') {
+                Some(secs) => Ok(OrderTime::FromNow(parse(secs)?)),
+                None => Ok(OrderTime::At(parse(src)?)),
+            },
+        }
+    }
+}
+
+impl Serialize for OrderTime {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> core::result::Result<S::Ok, S::Error> {
+        display_fromstr::serialize(self, serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for OrderTime {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> core::result::Result<Self, D::Error> {
+        display_fromstr::deserialize(deserializer)
+    }
+}
+
+/// A price in an order, which may be relative to a reference price.
+///
+/// Kraken encodes these as strings: "30000" is a fixed price, "+50" and "-50" are offsets
+/// from the reference price, and "+2%" and "-2%" are percentage offsets.
+///
+/// * For trailing-stop and trailing-stop-limit orders, the primary price is the trailing offset
+///   from the best price, and must be `Plus` or `PlusPercent` (see [TrailingOffset]).
+/// * For trailing-stop-limit orders, the secondary price is the offset of the limit price
+///   from the trigger price.
+/// * For other orders, relative prices are offsets from the last traded price.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum OrderPrice {
+    /// A fixed price
+    Fixed(Decimal),
+    /// An amount above the reference price
+    Plus(Decimal),
+    /// An amount below the reference price
+    Minus(Decimal),
+    /// A percentage above the reference price
+    PlusPercent(Decimal),
+    /// A percentage below the reference price
+    MinusPercent(Decimal),
+}
+
+impl OrderPrice {
+    /// Get the price, if this is a fixed price
+    pub fn fixed(&self) -> Option<Decimal> {
+        match self {
+            Self::Fixed(price) => Some(*price),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for OrderPrice {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Fixed(price) => write!(f, "{}", price),
+            Self::Plus(offset) => write!(f, "+{}", offset),
+            Self::Minus(offset) => write!(f, "-{}", offset),
+            Self::PlusPercent(percent) => write!(f, "+{}%", percent),
+            Self::MinusPercent(percent) => write!(f, "-{}%", percent),
+        }
+    }
+}
+
+impl FromStr for OrderPrice {
+    type Err = &'static str;
+    fn from_str(src: &str) -> core::result::Result<OrderPrice, Self::Err> {
+        let parse = |src: &str| Decimal::from_str(src).map_err(|_| "invalid OrderPrice");
+        let (sign, rest) = match src.split_at_checked(1) {
+            Some((sign @ ("+" | "-"), rest)) => (Some(sign), rest),
+            _ => (None, src),
+        };
+        match (sign, rest.strip_suffix('%')) {
+            (None, None) => Ok(OrderPrice::Fixed(parse(rest)?)),
+            (Some("+"), None) => Ok(OrderPrice::Plus(parse(rest)?)),
+            (Some(_), None) => Ok(OrderPrice::Minus(parse(rest)?)),
+            (Some("+"), Some(percent)) => Ok(OrderPrice::PlusPercent(parse(percent)?)),
+            (Some(_), Some(percent)) => Ok(OrderPrice::MinusPercent(parse(percent)?)),
+            (None, Some(_)) => Err("percentage OrderPrice must have a sign"),
+        }
+    }
+}
+
+impl From<Decimal> for OrderPrice {
+    fn from(src: Decimal) -> Self {
+        Self::Fixed(src)
+    }
+}
+
+impl From<OrderPrice> for String {
+    fn from(src: OrderPrice) -> Self {
+        src.to_string()
+    }
+}
+
+impl Serialize for OrderPrice {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> core::result::Result<S::Ok, S::Error> {
+        display_fromstr::serialize(self, serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for OrderPrice {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> core::result::Result<Self, D::Error> {
+        display_fromstr::deserialize(deserializer)
+    }
+}
+
+/// The trailing offset of a trailing-stop or trailing-stop-limit order, from the best price since the order
+/// was placed. Kraken requires a positive offset, which is sent as e.g. "+50" or "+2%".
+///
+/// An [OrderPrice] can be converted with `TryFrom`, which rejects fixed prices and negative offsets.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum TrailingOffset {
+    /// An amount in the quote currency
+    Amount(Decimal),
+    /// A percentage of the price
+    Percent(Decimal),
+}
+
+impl fmt::Display for TrailingOffset {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        OrderPrice::from(*self).fmt(f)
+    }
+}
+
+impl From<TrailingOffset> for OrderPrice {
+    fn from(src: TrailingOffset) -> Self {
+        match src {
+            TrailingOffset::Amount(offset) => Self::Plus(offset),
+            TrailingOffset::Percent(percent) => Self::PlusPercent(percent),
+        }
+    }
+}
+
+impl From<TrailingOffset> for String {
+    fn from(src: TrailingOffset) -> Self {
+        src.to_string()
+    }
+}
+
+impl TryFrom<OrderPrice> for TrailingOffset {
+    type Error = OrderValidationError;
+    fn try_from(src: OrderPrice) -> core::result::Result<Self, Self::Error> {
+        match src {
+            OrderPrice::Plus(offset) if offset.is_sign_positive() => Ok(Self::Amount(offset)),
+            OrderPrice::PlusPercent(percent) if percent.is_sign_positive() => Ok(Self::Percent(percent)),
+            _ => Err(OrderValidationError::InvalidPrice(src.to_string())),
+        }
+    }
+}
+
 /// Possible miscellaneous info flags in Kraken.
 /// These are options in a comma-separated list
 #[derive(Debug, Display, Clone, Ord, PartialOrd, Eq, PartialEq)]
@@ -361,6 +843,8 @@ pub enum MiscInfo {
     Liquidated,
     /// partial
     PartialFill,
+    /// closing
+    Closing,
 }
 
 impl FromStr for MiscInfo {
@@ -371,6 +855,7 @@ impl FromStr for MiscInfo {
             "touched" => Ok(MiscInfo::Touched),
             "liquidated" => Ok(MiscInfo::Liquidated),
             "partial" => Ok(MiscInfo::PartialFill),
+            "closing" => Ok(MiscInfo::Closing),
             _ => Err("unknown MiscInfo"),
         }
     }
@@ -387,9 +872,9 @@ pub struct OrderDescriptionInfo {
     /// order type
     pub ordertype: OrderType,
     /// primary price
-    pub price: Decimal,
+    pub price: OrderPrice,
     /// secondary price
-    pub price2: Decimal,
+    pub price2: OrderPrice,
     /// leverage
     #[serde(deserialize_with = "default_on_error::deserialize", default)]
     pub leverage: Option<Decimal>,
@@ -415,17 +900,176 @@ pub struct GetOpenOrdersResponse {
 /// Query orders request schema
 #[derive(Default, Debug, Serialize, Deserialize)]
 pub struct QueryOrdersRequest {
+    /// Comma separated list of txids to query
+    #[serde(skip_serializing_if = "String::is_empty")]
     pub txid: String,
+    /// Client order id of an order to query (instead of txids)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cl_ord_id: Option<String>,
 }
 
 /// Query orders response schema, keyed by tx id
 pub type QueryOrdersResponse = HashMap<String, OrderInfo>;
 
+/// Which time to use when filtering closed orders by start and end
+/// These are lowercase strings in the query string
+#[derive(Debug, Display, Clone, Serialize, Deserialize, Ord, PartialOrd, Eq, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum CloseTime {
+    /// open
+    Open,
+    /// close
+    Close,
+    /// both
+    Both,
+}
+
+/// Get closed orders request
+#[derive(Clone, Default, Debug, Serialize, Deserialize)]
+pub struct GetClosedOrdersRequest {
+    /// Whether or not to include trades related to position in output
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub trades: Option<bool>,
+    /// restrict results to given user reference id (optional)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub userref: Option<UserRefId>,
+    /// Starting unix timestamp or order tx id of results (exclusive)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub start: Option<String>,
+    /// Ending unix timestamp or order tx id of results (inclusive)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub end: Option<String>,
+    /// Result offset for pagination
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ofs: Option<u64>,
+    /// Which time to use to search (default: both)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub closetime: Option<CloseTime>,
+}
+
+/// Get closed orders response
+#[derive(Default, Debug, Serialize, Deserialize)]
+pub struct GetClosedOrdersResponse {
+    /// The set of closed orders (at most 50 per page), keyed by TxId
+    pub closed: HashMap<TxId, OrderInfo>,
+    /// Total number of closed orders matching the criteria
+    pub count: u64,
+}
+
+/// Type of trade to filter by in the trades history
+/// These are strings with spaces in the query string
+#[derive(Debug, Display, Clone, Serialize, Deserialize, Ord, PartialOrd, Eq, PartialEq)]
+pub enum TradeHistoryType {
+    /// all
+    #[serde(rename = "all")]
+    All,
+    /// any position
+    #[serde(rename = "any position")]
+    AnyPosition,
+    /// closed position
+    #[serde(rename = "closed position")]
+    ClosedPosition,
+    /// closing position
+    #[serde(rename = "closing position")]
+    ClosingPosition,
+    /// no position
+    #[serde(rename = "no position")]
+    NoPosition,
+}
+
+/// Get trades history request
+#[derive(Clone, Default, Debug, Serialize, Deserialize)]
+pub struct TradesHistoryRequest {
+    /// Type of trade (default: all)
+    #[serde(rename = "type", skip_serializing_if = "Option::is_none")]
+    pub trade_type: Option<TradeHistoryType>,
+    /// Whether or not to include trades related to position in output
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub trades: Option<bool>,
+    /// Starting unix timestamp or trade tx id of results (exclusive)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub start: Option<String>,
+    /// Ending unix timestamp or trade tx id of results (inclusive)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub end: Option<String>,
+    /// Result offset for pagination
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ofs: Option<u64>,
+}
+
+/// Get trades history response
+#[derive(Default, Debug, Serialize, Deserialize)]
+pub struct TradesHistoryResponse {
+    /// The trades (at most 50 per page), keyed by trade TxId
+    pub trades: HashMap<TxId, TradeInfo>,
+    /// Total number of trades matching the criteria
+    pub count: u64,
+}
+
+/// Query trades request schema
+#[derive(Default, Debug, Serialize, Deserialize)]
+pub struct QueryTradesRequest {
+    /// Comma-separated list of trade tx ids (up to 20)
+    pub txid: String,
+    /// Whether or not to include trades related to position in output
+    #[serde(skip_serializing_if = "core::ops::Not::not")]
+    pub trades: bool,
+}
+
+/// Query trades response schema, keyed by trade tx id
+pub type QueryTradesResponse = HashMap<TxId, TradeInfo>;
+
+/// Trade-info (one of our fills) used in TradesHistory and QueryTrades APIs
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
+pub struct TradeInfo {
+    /// Order responsible for execution of trade
+    pub ordertxid: TxId,
+    /// Position responsible for execution of trade
+    #[serde(default)]
+    pub postxid: Option<TxId>,
+    /// Asset pair
+    pub pair: String,
+    /// unix timestamp of trade
+    pub time: Decimal,
+    /// type of order (buy/sell)
+    #[serde(rename = "type")]
+    pub bs_type: BsType,
+    /// order type
+    pub ordertype: OrderType,
+    /// average price order was executed at (quote currency)
+    pub price: Decimal,
+    /// total cost of order (quote currency)
+    pub cost: Decimal,
+    /// total fee (quote currency)
+    pub fee: Decimal,
+    /// volume (base currency)
+    pub vol: Decimal,
+    /// initial margin (quote currency)
+    #[serde(default)]
+    pub margin: Decimal,
+    /// misc info (comma separated list, e.g. "closing")
+    #[serde(default, with = "comma_separated")]
+    pub misc: BTreeSet<MiscInfo>,
+    /// True if trade was executed with user as the maker
+    #[serde(default)]
+    pub maker: Option<bool>,
+    /// Position status (open/closed), only for trades opening a position
+    #[serde(default)]
+    pub posstatus: Option<String>,
+    /// List of closing trades for the position, only if trades were requested
+    #[serde(default)]
+    pub trades: Option<Vec<TxId>>,
+}
+
 /// Cancel order request
 #[derive(Default, Debug, Serialize, Deserialize)]
 pub struct CancelOrderRequest {
     /// The txid of the order to cancel. OR a userref id of orders to cancel
+    #[serde(skip_serializing_if = "String::is_empty")]
     pub txid: String,
+    /// The client order id of the order to cancel (instead of txid)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cl_ord_id: Option<String>,
 }
 
 /// Cancel order response
@@ -463,12 +1107,169 @@ pub struct CancelAllOrdersAfterResponse {
     pub trigger_time: String,
 }
 
+/// Possible ledger entry types in Kraken.
+/// These are lowercase strings in json
+///
+/// Kraken adds new ledger types from time to time, any type which is not known
+/// to this crate deserializes as `Other`.
+#[derive(Debug, Display, Clone, Serialize, Deserialize, Ord, PartialOrd, Eq, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum LedgerType {
+    /// trade
+    Trade,
+    /// deposit
+    Deposit,
+    /// withdrawal
+    Withdrawal,
+    /// transfer
+    Transfer,
+    /// margin
+    Margin,
+    /// adjustment
+    Adjustment,
+    /// rollover
+    Rollover,
+    /// spend
+    Spend,
+    /// receive
+    Receive,
+    /// settled
+    Settled,
+    /// credit
+    Credit,
+    /// staking
+    Staking,
+    /// reward
+    Reward,
+    /// dividend
+    Dividend,
+    /// sale
+    Sale,
+    /// conversion
+    Conversion,
+    /// other (a ledger type unknown to this crate)
+    #[serde(other)]
+    Other,
+}
+
+/// The types of ledger entries which can be requested from the Ledgers API.
+/// These are snake_case strings in requests
+#[derive(Debug, Display, Copy, Clone, Serialize, Deserialize, Eq, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum LedgerTypeFilter {
+    /// all
+    All,
+    /// trade
+    Trade,
+    /// deposit
+    Deposit,
+    /// withdrawal
+    Withdrawal,
+    /// transfer
+    Transfer,
+    /// margin
+    Margin,
+    /// adjustment
+    Adjustment,
+    /// rollover
+    Rollover,
+    /// credit
+    Credit,
+    /// settled
+    Settled,
+    /// staking
+    Staking,
+    /// dividend
+    Dividend,
+    /// sale
+    Sale,
+    /// nft_rebate
+    NftRebate,
+}
+
+/// Get ledgers request
+#[derive(Clone, Default, Debug, Serialize, Deserialize)]
+pub struct LedgersRequest {
+    /// Comma-separated list of assets to restrict output to (default: all)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub asset: Option<String>,
+    /// Asset class to restrict output to (default: currency)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub aclass: Option<String>,
+    /// Type of ledger entries to retrieve (default: all)
+    #[serde(rename = "type", skip_serializing_if = "Option::is_none")]
+    pub ledger_type: Option<LedgerTypeFilter>,
+    /// Starting unix timestamp or ledger id of results (exclusive)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub start: Option<String>,
+    /// Ending unix timestamp or ledger id of results (inclusive)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub end: Option<String>,
+    /// Result offset for pagination
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ofs: Option<u64>,
+    /// If true, do not retrieve the count of ledger entries (faster for large ledgers)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub without_count: Option<bool>,
+}
+
+/// Get ledgers response
+#[derive(Default, Debug, Serialize, Deserialize)]
+pub struct LedgersResponse {
+    /// The ledger entries (at most 50 per page), keyed by ledger id
+    pub ledger: HashMap<String, LedgerEntry>,
+    /// Total number of ledger entries matching the criteria (omitted if without_count was set)
+    #[serde(default)]
+    pub count: Option<u64>,
+}
+
+/// Query ledgers request schema
+#[derive(Default, Debug, Serialize, Deserialize)]
+pub struct QueryLedgersRequest {
+    /// Comma-separated list of ledger ids (up to 20)
+    pub id: String,
+    /// Whether or not to include trades related to position in output
+    #[serde(skip_serializing_if = "core::ops::Not::not")]
+    pub trades: bool,
+}
+
+/// Query ledgers response schema, keyed by ledger id
+pub type QueryLedgersResponse = HashMap<String, LedgerEntry>;
+
+/// A ledger entry, used in Ledgers and QueryLedgers APIs
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
+pub struct LedgerEntry {
+    /// Reference id of the trade, deposit, withdrawal etc. which created this entry
+    pub refid: String,
+    /// unix timestamp of ledger entry
+    pub time: Decimal,
+    /// type of ledger entry
+    #[serde(rename = "type")]
+    pub ledger_type: LedgerType,
+    /// additional info relating to the ledger entry type, where applicable
+    #[serde(default)]
+    pub subtype: String,
+    /// asset class
+    pub aclass: String,
+    /// asset
+    pub asset: String,
+    /// transaction amount (negative for debits)
+    pub amount: Decimal,
+    /// transaction fee
+    pub fee: Decimal,
+    /// resulting balance
+    pub balance: Decimal,
+}
+
 /// Add order request
 #[derive(Debug, Serialize, Deserialize)]
 pub struct AddOrderRequest {
     /// A user ref id for this order
     #[serde(skip_serializing_if = "Option::is_none")]
     pub userref: Option<UserRefId>,
+    /// A client order id for this order
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cl_ord_id: Option<String>,
     /// order type
     pub ordertype: OrderType,
     /// type of order (buy/sell)
@@ -482,10 +1283,43 @@ pub struct AddOrderRequest {
     /// price
     #[serde(skip_serializing_if = "String::is_empty")]
     pub price: String,
+    /// secondary price (the limit price of stop-loss-limit and take-profit-limit orders)
+    #[serde(skip_serializing_if = "String::is_empty")]
+    pub price2: String,
+    /// price signal which triggers stop-loss and take-profit orders
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub trigger: Option<OrderTrigger>,
+    /// visible volume of iceberg orders
+    #[serde(skip_serializing_if = "String::is_empty")]
+    pub displayvol: String,
     /// order flags (comma separated list)
     #[serde(with = "comma_separated")]
     #[serde(skip_serializing_if = "BTreeSet::is_empty")]
     pub oflags: BTreeSet<OrderFlag>,
+    /// amount of leverage (margin orders only)
+    #[serde(skip_serializing_if = "String::is_empty")]
+    pub leverage: String,
+    /// If true, the order can only reduce an open margin position
+    #[serde(skip_serializing_if = "core::ops::Not::not")]
+    pub reduce_only: bool,
+    /// self-trade prevention
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub stptype: Option<SelfTradePrevention>,
+    /// time-in-force
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub timeinforce: Option<TimeInForce>,
+    /// scheduled start time
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub starttm: Option<OrderTime>,
+    /// expiration time
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub expiretm: Option<OrderTime>,
+    /// RFC3339 timestamp after which the matching engine should reject the order
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub deadline: Option<String>,
+    /// conditional close order (encoded as close[ordertype], close[price], close[price2])
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub close: Option<ConditionalClose>,
     /// validate: If true, do not submit order
     #[serde(skip_serializing_if = "core::ops::Not::not")]
     pub validate: bool,
@@ -512,6 +1346,224 @@ pub struct OrderAdded {
     pub close: String,
 }
 
+/// Add order batch request
+#[derive(Debug, Serialize, Deserialize)]
+pub struct AddOrderBatchRequest {
+    /// The orders to place (encoded as orders[0][ordertype], etc.)
+    pub orders: Vec<BatchOrder>,
+    /// pair (AssetPair id or altname) of all of the orders
+    pub pair: String,
+    /// RFC3339 timestamp after which the matching engine should reject the batch
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub deadline: Option<String>,
+    /// validate: If true, do not submit the orders
+    #[serde(skip_serializing_if = "core::ops::Not::not")]
+    pub validate: bool,
+}
+
+/// One order within an add order batch request
+#[derive(Debug, Serialize, Deserialize)]
+pub struct BatchOrder {
+    /// A user ref id for this order
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub userref: Option<UserRefId>,
+    /// A client order id for this order
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cl_ord_id: Option<String>,
+    /// order type
+    pub ordertype: OrderType,
+    /// type of order (buy/sell)
+    #[serde(rename = "type")]
+    pub bs_type: BsType,
+    /// volume (in lots)
+    #[serde(skip_serializing_if = "String::is_empty")]
+    pub volume: String,
+    /// price
+    #[serde(skip_serializing_if = "String::is_empty")]
+    pub price: String,
+    /// secondary price
+    #[serde(skip_serializing_if = "String::is_empty")]
+    pub price2: String,
+    /// price signal which triggers stop-loss and take-profit orders
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub trigger: Option<OrderTrigger>,
+    /// visible volume of iceberg orders
+    #[serde(skip_serializing_if = "String::is_empty")]
+    pub displayvol: String,
+    /// order flags (comma separated list)
+    #[serde(with = "comma_separated")]
+    #[serde(skip_serializing_if = "BTreeSet::is_empty")]
+    pub oflags: BTreeSet<OrderFlag>,
+    /// amount of leverage (margin orders only)
+    #[serde(skip_serializing_if = "String::is_empty")]
+    pub leverage: String,
+    /// If true, the order can only reduce an open margin position
+    #[serde(skip_serializing_if = "core::ops::Not::not")]
+    pub reduce_only: bool,
+    /// self-trade prevention
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub stptype: Option<SelfTradePrevention>,
+    /// time-in-force
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub timeinforce: Option<TimeInForce>,
+    /// scheduled start time
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub starttm: Option<OrderTime>,
+    /// expiration time
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub expiretm: Option<OrderTime>,
+    /// conditional close order
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub close: Option<ConditionalClose>,
+}
+
+/// Add order batch response
+#[derive(Debug, Serialize, Deserialize)]
+pub struct AddOrderBatchResponse {
+    /// The result of each order, in the order they were submitted
+    pub orders: Vec<BatchOrderResult>,
+}
+
+/// The result of one order within an add order batch
+#[derive(Debug, Serialize, Deserialize)]
+pub struct BatchOrderResult {
+    /// Description of resulting order
+    #[serde(default)]
+    pub descr: Option<OrderAdded>,
+    /// Txid of the order, if it was placed
+    #[serde(default)]
+    pub txid: Option<TxId>,
+    /// The error, if the order was rejected
+    #[serde(default)]
+    pub error: Option<KrakenError>,
+}
+
+/// Cancel order batch request
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CancelOrderBatchRequest {
+    /// The txids or userref ids of orders to cancel (encoded as orders[0], etc.)
+    pub orders: Vec<String>,
+}
+
+/// Edit order request
+///
+/// Editing an order cancels it and places a new order with a new txid, keeping the
+/// original order's parameters except those which are set here.
+#[derive(Default, Debug, Clone, Serialize, Deserialize)]
+pub struct EditOrderRequest {
+    /// TxId (or user ref id) of the order to edit
+    pub txid: String,
+    /// asset pair of the order
+    pub pair: String,
+    /// new volume (in lots)
+    #[serde(skip_serializing_if = "String::is_empty")]
+    pub volume: String,
+    /// new visible volume of an iceberg order
+    #[serde(skip_serializing_if = "String::is_empty")]
+    pub displayvol: String,
+    /// new price
+    #[serde(skip_serializing_if = "String::is_empty")]
+    pub price: String,
+    /// new secondary price
+    #[serde(skip_serializing_if = "String::is_empty")]
+    pub price2: String,
+    /// new order flags (comma separated list)
+    #[serde(with = "comma_separated")]
+    #[serde(skip_serializing_if = "BTreeSet::is_empty")]
+    pub oflags: BTreeSet<OrderFlag>,
+    /// new user ref id
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub newuserref: Option<UserRefId>,
+    /// RFC3339 timestamp after which the matching engine should reject the edit
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub deadline: Option<String>,
+    /// If true, the original order is canceled even if the new order can't be placed
+    #[serde(skip_serializing_if = "core::ops::Not::not")]
+    pub cancel_response: bool,
+    /// validate: If true, do not submit the edit
+    #[serde(skip_serializing_if = "core::ops::Not::not")]
+    pub validate: bool,
+}
+
+/// Edit order response
+#[derive(Debug, Serialize, Deserialize)]
+pub struct EditOrderResponse {
+    /// Description of the new order
+    #[serde(default)]
+    pub descr: Option<OrderAdded>,
+    /// TxId of the new order (omitted if validating)
+    #[serde(default)]
+    pub txid: Option<TxId>,
+    /// TxId of the original order
+    pub originaltxid: TxId,
+    /// Status of the edit ("ok" or "err")
+    #[serde(default)]
+    pub status: String,
+    /// Error message, if the edit failed
+    #[serde(default)]
+    pub error_message: Option<String>,
+    /// Volume of the new order
+    #[serde(deserialize_with = "default_on_error::deserialize", default)]
+    pub volume: Option<Decimal>,
+    /// Price of the new order
+    #[serde(deserialize_with = "default_on_error::deserialize", default)]
+    pub price: Option<Decimal>,
+    /// Secondary price of the new order
+    #[serde(deserialize_with = "default_on_error::deserialize", default)]
+    pub price2: Option<Decimal>,
+    /// Number of orders canceled (0 or 1)
+    #[serde(default)]
+    pub orders_cancelled: u32,
+    /// User ref id of the new order
+    #[serde(default)]
+    pub newuserref: Option<UserRefId>,
+    /// User ref id of the original order
+    #[serde(default)]
+    pub olduserref: Option<UserRefId>,
+}
+
+/// Amend order request
+///
+/// Amending an order changes it in place, keeping its txid and (where possible) its queue priority.
+/// Only the fields which are set are changed.
+#[derive(Default, Debug, Clone, Serialize, Deserialize)]
+pub struct AmendOrderRequest {
+    /// TxId of the order to amend
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub txid: Option<TxId>,
+    /// Client order id of the order to amend (instead of txid)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cl_ord_id: Option<String>,
+    /// Asset pair of the order. This is not sent to kraken, but is needed to check the amend with an order validator.
+    #[serde(skip)]
+    pub pair: Option<String>,
+    /// new order quantity (in base asset)
+    #[serde(skip_serializing_if = "String::is_empty")]
+    pub order_qty: String,
+    /// new visible quantity of an iceberg order
+    #[serde(skip_serializing_if = "String::is_empty")]
+    pub display_qty: String,
+    /// new limit price
+    #[serde(skip_serializing_if = "String::is_empty")]
+    pub limit_price: String,
+    /// new trigger price
+    #[serde(skip_serializing_if = "String::is_empty")]
+    pub trigger_price: String,
+    /// If true, the amend is rejected if the new limit price would take liquidity
+    #[serde(skip_serializing_if = "core::ops::Not::not")]
+    pub post_only: bool,
+    /// RFC3339 timestamp after which the matching engine should reject the amend
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub deadline: Option<String>,
+}
+
+/// Amend order response
+#[derive(Debug, Serialize, Deserialize)]
+pub struct AmendOrderResponse {
+    /// A unique id for the amend transaction
+    pub amend_id: String,
+}
+
 /// GetTradeVolume request
 #[derive(Debug, Serialize, Deserialize)]
 pub struct GetTradeVolumeRequest {
@@ -537,6 +1589,99 @@ pub struct FeeTierInfo {
     pub fee: Decimal,
 }
 
+/// TradeBalance request
+#[derive(Default, Debug, Serialize, Deserialize)]
+pub struct TradeBalanceRequest {
+    /// Base asset used to determine balance (default ZUSD)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub asset: Option<String>,
+}
+
+/// TradeBalance response, with all values in the base asset
+#[derive(Debug, Serialize, Deserialize)]
+pub struct TradeBalanceResponse {
+    /// Equivalent balance (combined balance of all currencies)
+    pub eb: Decimal,
+    /// Trade balance (combined balance of all equity currencies)
+    pub tb: Decimal,
+    /// Margin amount of open positions
+    pub m: Decimal,
+    /// Unrealized net profit/loss of open positions
+    pub n: Decimal,
+    /// Cost basis of open positions
+    pub c: Decimal,
+    /// Current floating valuation of open positions
+    pub v: Decimal,
+    /// Equity (trade balance + unrealized net profit/loss)
+    pub e: Decimal,
+    /// Free margin (equity - initial margin)
+    pub mf: Decimal,
+    /// Margin level ((equity / initial margin) * 100), if there are open positions
+    #[serde(default)]
+    pub ml: Option<Decimal>,
+    /// Value of unfilled and partially filled orders
+    #[serde(default)]
+    pub uv: Option<Decimal>,
+}
+
+/// OpenPositions request
+#[derive(Default, Debug, Serialize, Deserialize)]
+pub struct OpenPositionsRequest {
+    /// Comma separated list of txids to restrict output to
+    #[serde(skip_serializing_if = "String::is_empty")]
+    pub txid: String,
+    /// Whether to include profit/loss calculations
+    #[serde(skip_serializing_if = "core::ops::Not::not")]
+    pub docalcs: bool,
+}
+
+/// OpenPositions response, keyed by position txid
+pub type OpenPositionsResponse = HashMap<TxId, PositionInfo>;
+
+/// An open margin position
+#[derive(Debug, Serialize, Deserialize)]
+pub struct PositionInfo {
+    /// Txid of the order which opened the position
+    pub ordertxid: TxId,
+    /// Status of the position (open)
+    pub posstatus: String,
+    /// Asset pair
+    pub pair: String,
+    /// Unix timestamp of the trade
+    pub time: Decimal,
+    /// Direction of the position (buy/sell)
+    #[serde(rename = "type")]
+    pub bs_type: BsType,
+    /// Order type used to open the position
+    pub ordertype: OrderType,
+    /// Opening cost of the position (quote currency)
+    pub cost: Decimal,
+    /// Opening fee of the position (quote currency)
+    pub fee: Decimal,
+    /// Position volume (base currency)
+    pub vol: Decimal,
+    /// Quantity closed (base currency)
+    pub vol_closed: Decimal,
+    /// Initial margin (quote currency)
+    pub margin: Decimal,
+    /// Current value of the remaining position (only if docalcs is requested)
+    #[serde(default)]
+    pub value: Option<Decimal>,
+    /// Unrealized profit/loss of the remaining position (only if docalcs is requested)
+    #[serde(default)]
+    pub net: Option<Decimal>,
+    /// Funding cost and term of the position
+    pub terms: String,
+    /// Unix timestamp of the next margin rollover fee
+    pub rollovertm: Decimal,
+    /// Misc info (comma separated list)
+    #[serde(default, with = "comma_separated")]
+    pub misc: BTreeSet<MiscInfo>,
+    /// Order flags of the order which opened the position (comma separated list)
+    #[serde(with = "comma_separated")]
+    pub oflags: BTreeSet<OrderFlag>,
+}
+
 /// WebSockets authenitcation token response, including token and expiry
 #[derive(Debug, Serialize, Deserialize)]
 pub struct GetWebSocketsTokenResponse {
@@ -549,6 +1694,87 @@ pub struct GetWebSocketsTokenResponse {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::KrakenErrorKind;
+
+    #[test]
+    fn test_unpack_kraken_result() {
+        let text = r#"{"error":["EAPI:Rate limit exceeded"]}"#;
+        let result: KrakenResult<TimeResponse> = serde_json::from_str(text).unwrap();
+        match unpack_kraken_result(result) {
+            Err(Error::KrakenErrors(errors)) => assert_eq!(errors[0].kind, KrakenErrorKind::RateLimitExceeded),
+            _ => panic!("expected kraken errors"),
+        }
+
+        // Warnings are returned as errors too, but can be told apart by their severity
+        let text = r#"{"error":["WGeneral:Something"],"result":{"unixtime":1688669448}}"#;
+        let result: KrakenResult<TimeResponse> = serde_json::from_str(text).unwrap();
+        match unpack_kraken_result(result) {
+            Err(Error::KrakenErrors(errors)) => assert!(!errors[0].is_error()),
+            _ => panic!("expected kraken errors"),
+        }
+    }
+
+    #[test]
+    fn test_asset_pairs_response() {
+        // Example from kraken docs
+        let text = r#"{"XETHXXBT":{"altname":"ETHXBT","wsname":"ETH/XBT","aclass_base":"currency","base":"XETH","aclass_quote":"currency","quote":"XXBT","lot":"unit","cost_decimals":6,"pair_decimals":5,"lot_decimals":8,"lot_multiplier":1,"leverage_buy":[2,3,4,5],"leverage_sell":[2,3,4,5],"fees":[[0,0.26],[50000,0.24],[100000,0.22]],"fees_maker":[[0,0.16],[50000,0.14],[100000,0.12]],"fee_volume_currency":"ZUSD","margin_call":80,"margin_stop":40,"ordermin":"0.01","costmin":"0.00002","tick_size":"0.00001","status":"online","long_position_limit":1100,"short_position_limit":400}}"#;
+
+        let obj: AssetPairsResponse = serde_json::from_str(text).unwrap();
+        let pair = &obj["XETHXXBT"];
+
+        assert_eq!(pair.alt_name.as_deref(), Some("ETHXBT"));
+        assert_eq!(pair.cost_decimals, 6);
+        assert_eq!(pair.leverage_sell, vec![2, 3, 4, 5]);
+        assert_eq!(
+            pair.fees[1],
+            FeeTier {
+                volume: Decimal::from(50000),
+                percent: Decimal::from_str("0.24").unwrap()
+            }
+        );
+        assert_eq!(pair.fees_maker[2].percent, Decimal::from_str("0.12").unwrap());
+        assert_eq!(pair.margin_stop, Some(40));
+        assert_eq!(pair.costmin, Some(Decimal::from_str("0.00002").unwrap()));
+        assert_eq!(pair.tick_size, Some(Decimal::from_str("0.00001").unwrap()));
+        assert_eq!(pair.status, Some(AssetPairStatus::Online));
+        assert_eq!(pair.short_position_limit, Some(400));
+
+        // Only the requested info is present, e.g. with info=leverage
+        let text = r#"{"XETHXXBT":{"leverage_buy":[2,3],"leverage_sell":[2]}}"#;
+        let obj: AssetPairsResponse = serde_json::from_str(text).unwrap();
+        assert_eq!(obj["XETHXXBT"].leverage_buy, vec![2, 3]);
+        assert!(obj["XETHXXBT"].fees.is_empty());
+
+        let text = r#"{"XETHXXBT":{"status":"delisted"}}"#;
+        let obj: AssetPairsResponse = serde_json::from_str(text).unwrap();
+        assert_eq!(obj["XETHXXBT"].status, None);
+    }
+
+    #[test]
+    fn test_ticker_response() {
+        // Example from kraken docs
+        let text = r#"{"XXBTZUSD":{"a":["30300.10000","1","1.000"],"b":["30300.00000","1","1.000"],"c":["30303.20000","0.00067643"],"v":["4083.67001100","4412.73601799"],"p":["30706.77771","30689.13205"],"t":[34619,38907],"l":["29868.30000","29868.30000"],"h":["31631.00000","31631.00000"],"o":"30502.80000"}}"#;
+
+        let obj: TickerResponse = serde_json::from_str(text).unwrap();
+        let ticker = &obj["XXBTZUSD"];
+
+        assert_eq!(ticker.ask.price, Decimal::from_str("30300.1").unwrap());
+        assert_eq!(ticker.ask.whole_lot_volume, Decimal::from(1));
+        assert_eq!(ticker.bid.lot_volume, Decimal::from(1));
+        assert_eq!(ticker.last_trade.lot_volume, Decimal::from_str("0.00067643").unwrap());
+        assert_eq!(ticker.volume.last_24h, Decimal::from_str("4412.73601799").unwrap());
+        assert_eq!(ticker.vwap.today, Decimal::from_str("30706.77771").unwrap());
+        assert_eq!(
+            ticker.trades,
+            TickerWindow {
+                today: 34619,
+                last_24h: 38907
+            }
+        );
+        assert_eq!(ticker.low.today, Decimal::from_str("29868.3").unwrap());
+        assert_eq!(ticker.high.last_24h, Decimal::from_str("31631").unwrap());
+        assert_eq!(ticker.open, Decimal::from_str("30502.8").unwrap());
+    }
 
     #[test]
     fn test_public_trade() {
@@ -593,6 +1819,39 @@ mod tests {
         assert_eq!(obj.last, "1756443816201051892");
     }
 
+    #[test]
+    fn test_get_order_book_response() {
+        // Example from kraken docs
+        let text = r#"{"XXBTZUSD":{"asks":[["30384.10000","2.059",1688671659],["30387.90000","1.500",1688671380],["30393.70000","9.871",1688671261]],"bids":[["30297.00000","1.115",1688671636],["30296.70000","2.002",1688671674],["30289.80000","5.001",1688671673]]}}"#;
+
+        let obj: GetOrderBookResponse = serde_json::from_str(text).unwrap();
+        let book = crate::BookData::from(obj["XXBTZUSD"].clone());
+
+        let (best_ask, ask) = book.ask.iter().next().unwrap();
+        assert_eq!(*best_ask, Decimal::from_str("30384.1").unwrap());
+        assert_eq!(ask.price_str, "30384.10000");
+        assert_eq!(ask.volume_str, "2.059");
+        assert_eq!(ask.timestamp, Decimal::new(1688671659, 0));
+        let (best_bid, bid) = book.bid.iter().next_back().unwrap();
+        assert_eq!(*best_bid, Decimal::from_str("30297").unwrap());
+        assert_eq!(bid.volume, Decimal::from_str("1.115").unwrap());
+        assert_eq!(book.bid.len(), 3);
+    }
+
+    #[test]
+    fn test_get_recent_spreads_response() {
+        // Example from kraken docs
+        let text = r#"{"XXBTZUSD":[[1688671834,"30292.10000","30297.50000"],[1688671834,"30292.10000","30296.70000"],[1688671834,"30292.70000","30296.70000"]],"last":1688672106}"#;
+
+        let obj: GetRecentSpreadsResponse = serde_json::from_str(text).unwrap();
+
+        assert_eq!(obj.data.len(), 3);
+        assert_eq!(obj.last, "1688672106");
+        assert_eq!(obj.data[1].timestamp, Decimal::new(1688671834, 0));
+        assert_eq!(obj.data[1].bid, Decimal::from_str("30292.1").unwrap());
+        assert_eq!(obj.data[1].ask, Decimal::from_str("30296.7").unwrap());
+    }
+
     #[test]
     fn test_get_ohlc_data_response() {
         // This text from kraken docs api console
@@ -604,6 +1863,165 @@ mod tests {
         assert_eq!(obj.last, "1756923300");
     }
 
+    #[test]
+    fn test_closed_orders_response() {
+        // Example from kraken docs
+        let text = r#"{"closed":{"O37652-RJWRT-IMO74O":{"refid":"None","userref":1,"status":"canceled","reason":"User requested","opentm":1688148493.7708,"closetm":1688148610.0482,"starttm":0,"expiretm":0,"descr":{"pair":"XBTGBP","type":"buy","ordertype":"stop-loss-limit","price":"23667.0","price2":"0","leverage":"none","order":"buy 0.00100000 XBTGBP @ limit 23667.0","close":""},"vol":"0.00100000","vol_exec":"0.00000000","cost":"0.00000","fee":"0.00000","price":"0.00000","stopprice":"0.00000","limitprice":"0.00000","misc":"","oflags":"fciq","trigger":"index"}},"count":1}"#;
+
+        let obj: GetClosedOrdersResponse = serde_json::from_str(text).unwrap();
+
+        assert_eq!(obj.count, 1);
+        let order = &obj.closed["O37652-RJWRT-IMO74O"];
+        assert_eq!(order.status, OrderStatus::Canceled);
+        assert_eq!(order.reason.as_deref(), Some("User requested"));
+        assert_eq!(order.closetm, Some(Decimal::from_str("1688148610.0482").unwrap()));
+        assert_eq!(order.descr.ordertype, OrderType::StopLossLimit);
+    }
+
+    #[test]
+    fn test_open_orders_response_trailing_stop() {
+        let text = r#"{"open":{"OB5VMB-B4U2U-DK2WRW":{"refid":null,"userref":0,"cl_ord_id":"6d1b345e-2821-40e2-ad83-4ecb18a06876","status":"open","opentm":1688666559.8974,"starttm":0,"expiretm":0,"descr":{"pair":"XBTUSD","type":"sell","ordertype":"trailing-stop-limit","price":"+50.0","price2":"-0.5%","leverage":"none","order":"sell 1.25000000 XBTUSD @ trailing stop +50.0 -> limit -0.5%","close":""},"vol":"1.25000000","vol_exec":"0.00000000","cost":"0.00000","fee":"0.00000","price":"0.00000","stopprice":"0.00000","limitprice":"0.00000","misc":"","oflags":"fciq"},"OCWXJU-TQSSG-3UBNXA":{"refid":null,"userref":0,"status":"open","opentm":1688666559.8974,"starttm":0,"expiretm":0,"descr":{"pair":"XBTUSD","type":"buy","ordertype":"iceberg","price":"27500.0","price2":"0","leverage":"none","order":"buy 5.00000000 XBTUSD @ iceberg 27500.0","close":""},"vol":"5.00000000","vol_exec":"0.00000000","cost":"0.00000","fee":"0.00000","price":"0.00000","stopprice":"0.00000","limitprice":"0.00000","misc":"","oflags":"fciq"}}}"#;
+
+        let obj: GetOpenOrdersResponse = serde_json::from_str(text).unwrap();
+
+        let order = &obj.open["OB5VMB-B4U2U-DK2WRW"];
+        assert_eq!(order.descr.ordertype, OrderType::TrailingStopLimit);
+        assert_eq!(order.cl_ord_id.as_deref(), Some("6d1b345e-2821-40e2-ad83-4ecb18a06876"));
+        assert_eq!(order.descr.price, OrderPrice::Plus(Decimal::from_str("50.0").unwrap()));
+        assert_eq!(
+            order.descr.price2,
+            OrderPrice::MinusPercent(Decimal::from_str("0.5").unwrap())
+        );
+        let order = &obj.open["OCWXJU-TQSSG-3UBNXA"];
+        assert_eq!(order.descr.ordertype, OrderType::Iceberg);
+        assert_eq!(order.cl_ord_id, None);
+        assert_eq!(order.descr.price.fixed(), Some(Decimal::from_str("27500.0").unwrap()));
+    }
+
+    #[test]
+    fn test_trade_balance_and_open_positions() {
+        // Examples from kraken docs
+        let text = r#"{"eb":"1101.3425","tb":"392.2264","m":"7.0354","n":"-10.0232","c":"21.1063","v":"31.1297","e":"382.2032","mf":"375.1678","ml":"5432.57","uv":"0"}"#;
+        let obj: TradeBalanceResponse = serde_json::from_str(text).unwrap();
+        assert_eq!(obj.n, Decimal::from_str("-10.0232").unwrap());
+        assert_eq!(obj.ml, Some(Decimal::from_str("5432.57").unwrap()));
+
+        let text = r#"{"TF5GVO-T7ZZ2-6NBKBI":{"ordertxid":"OLWNFG-LLH4R-D6SFFP","posstatus":"open","pair":"XXBTZUSD","time":1605280097.8294,"type":"buy","ordertype":"limit","cost":"104610.52842","fee":"289.06565","vol":"8.82412861","vol_closed":"0.20200000","margin":"20922.10568","value":"258797.5","net":"+154186.9728","terms":"0.0100% per 4 hours","rollovertm":"1616672637","misc":"","oflags":""},"T24DOR-TAFLM-ID3NYP":{"ordertxid":"OIVYGZ-M5EHU-ZRUQXX","posstatus":"open","pair":"XXBTZUSD","time":1607943827.3172,"type":"sell","ordertype":"limit","cost":"145756.76856","fee":"335.24057","vol":"8.00000000","vol_closed":"0.00000000","margin":"29151.35371","terms":"0.0100% per 4 hours","rollovertm":"1616672637","misc":"","oflags":"fciq"}}"#;
+        let obj: OpenPositionsResponse = serde_json::from_str(text).unwrap();
+        let position = &obj["TF5GVO-T7ZZ2-6NBKBI"];
+        assert_eq!(position.bs_type, BsType::Buy);
+        assert_eq!(position.net, Some(Decimal::from_str("154186.9728").unwrap()));
+        assert!(position.misc.is_empty());
+        let position = &obj["T24DOR-TAFLM-ID3NYP"];
+        assert_eq!(position.vol, Decimal::from_str("8").unwrap());
+        assert_eq!(position.net, None);
+        assert!(position.oflags.contains(&OrderFlag::Fciq));
+    }
+
+    #[test]
+    fn test_order_price() {
+        for src in ["30000.5", "+50", "-50", "+2.5%", "-0.1%"] {
+            assert_eq!(OrderPrice::from_str(src).unwrap().to_string(), src);
+        }
+        assert_eq!(
+            OrderPrice::from_str("+2%"),
+            Ok(OrderPrice::PlusPercent(Decimal::from_str("2").unwrap()))
+        );
+        assert!(OrderPrice::from_str("2%").is_err());
+        assert!(OrderPrice::from_str("+").is_err());
+        assert!(OrderPrice::from_str("").is_err());
+    }
+
+    #[test]
+    fn test_trailing_offset() {
+        let offset = TrailingOffset::try_from(OrderPrice::from_str("+2.5%").unwrap()).unwrap();
+        assert_eq!(offset, TrailingOffset::Percent(Decimal::from_str("2.5").unwrap()));
+        assert_eq!(offset.to_string(), "+2.5%");
+        assert_eq!(TrailingOffset::Amount(50.into()).to_string(), "+50");
+
+        // Only positive offsets are accepted
+        for src in ["30000", "-50", "-2%", "+-50"] {
+            let price = OrderPrice::from_str(src).unwrap();
+            assert_eq!(
+                TrailingOffset::try_from(price),
+                Err(OrderValidationError::InvalidPrice(price.to_string()))
+            );
+        }
+    }
+
+    #[test]
+    fn test_trades_history_response() {
+        // Example from kraken docs
+        let text = r#"{"trades":{"THVRQM-33VKH-UCI7BS":{"ordertxid":"OQCLML-BW3P3-BUCMWZ","postxid":"TKH2SE-M7IF5-CFI7LT","pair":"XXBTZUSD","time":1688667796.8802,"type":"buy","ordertype":"limit","price":"30010.00000","cost":"600.20000","fee":"0.00000","vol":"0.02000000","margin":"0.00000","misc":"closing","trade_id":40274859,"maker":true}},"count":1}"#;
+
+        let obj: TradesHistoryResponse = serde_json::from_str(text).unwrap();
+
+        assert_eq!(obj.count, 1);
+        let trade = &obj.trades["THVRQM-33VKH-UCI7BS"];
+        assert_eq!(trade.ordertxid, "OQCLML-BW3P3-BUCMWZ");
+        assert_eq!(trade.postxid.as_deref(), Some("TKH2SE-M7IF5-CFI7LT"));
+        assert_eq!(trade.bs_type, BsType::Buy);
+        assert_eq!(trade.price, Decimal::from_str("30010.00000").unwrap());
+        assert_eq!(trade.cost, Decimal::from_str("600.20000").unwrap());
+        assert_eq!(trade.vol, Decimal::from_str("0.02").unwrap());
+        assert_eq!(trade.maker, Some(true));
+        assert_eq!(trade.misc, BTreeSet::from([MiscInfo::Closing]));
+    }
+
+    #[test]
+    fn test_ledgers_response() {
+        // Example from kraken docs, with an extra unknown ledger type
+        let text = r#"{"ledger":{"L4UESK-KG3EQ-UFO4T5":{"refid":"TJKLXX-PGMUI-4NTLXU","time":1688464484.1787,"type":"trade","subtype":"","aclass":"currency","asset":"ZGBP","amount":"-24.5000","fee":"0.0490","balance":"459567.9171"},"LMKZCZ-Z3GVL-CXKK4H":{"refid":"TBZIP2-F6QOU-TMB6FY","time":1688444262.8888,"type":"nfttrade","subtype":"","aclass":"currency","asset":"XXBT","amount":"0.0001000000","fee":"0.0000000000","balance":"0.0001000000"}},"count":2}"#;
+
+        let obj: LedgersResponse = serde_json::from_str(text).unwrap();
+
+        assert_eq!(obj.count, Some(2));
+        let entry = &obj.ledger["L4UESK-KG3EQ-UFO4T5"];
+        assert_eq!(entry.refid, "TJKLXX-PGMUI-4NTLXU");
+        assert_eq!(entry.ledger_type, LedgerType::Trade);
+        assert_eq!(entry.asset, "ZGBP");
+        assert_eq!(entry.amount, Decimal::from_str("-24.5").unwrap());
+        assert_eq!(entry.fee, Decimal::from_str("0.049").unwrap());
+        assert_eq!(entry.balance, Decimal::from_str("459567.9171").unwrap());
+
+        let entry = &obj.ledger["LMKZCZ-Z3GVL-CXKK4H"];
+        assert_eq!(entry.ledger_type, LedgerType::Other);
+    }
+
+    #[test]
+    fn test_ledgers_request() {
+        let request = LedgersRequest {
+            ledger_type: Some(LedgerTypeFilter::All),
+            ..Default::default()
+        };
+        assert_eq!(serde_qs::to_string(&request).unwrap(), "type=all");
+        let request = LedgersRequest {
+            ledger_type: Some(LedgerTypeFilter::NftRebate),
+            ..Default::default()
+        };
+        assert_eq!(serde_qs::to_string(&request).unwrap(), "type=nft_rebate");
+    }
+
+    #[test]
+    fn test_closed_orders_request_qs() {
+        let req = GetClosedOrdersRequest {
+            start: Some("1688148493".into()),
+            ofs: Some(50),
+            closetime: Some(CloseTime::Close),
+            ..Default::default()
+        };
+        assert_eq!(
+            serde_qs::to_string(&req).unwrap(),
+            "start=1688148493&ofs=50&closetime=close"
+        );
+
+        let req = TradesHistoryRequest {
+            trade_type: Some(TradeHistoryType::NoPosition),
+            ..Default::default()
+        };
+        assert_eq!(serde_qs::to_string(&req).unwrap(), "type=no+position");
+    }
+
     #[test]
     fn test_deposit_method_deserialize_limit_false() {
         // Test that limit: false deserializes to None
@@ -826,7 +2244,7 @@ pub struct WithdrawAddress {
 pub type WithdrawAddressesResponse = Vec<WithdrawAddress>;
 
 /// Request for WithdrawStatus private API call
-#[derive(Debug, Serialize, Default)]
+#[derive(Clone, Debug, Serialize, Default)]
 pub struct WithdrawStatusRequest {
     /// Optional asset to filter by
     #[serde(skip_serializing_if = "Option::is_none")]
@@ -886,7 +2304,7 @@ pub struct WithdrawalStatus {
 pub type WithdrawStatusResponse = Vec<WithdrawalStatus>;
 
 /// Request for DepositStatus private API call
-#[derive(Debug, Serialize, Default)]
+#[derive(Clone, Debug, Serialize, Default)]
 pub struct DepositStatusRequest {
     /// Optional asset to filter by
     #[serde(skip_serializing_if = "Option::is_none")]
@@ -0,0 +1,239 @@
+//! Strategies for generating the nonces of private REST API calls.
+//!
+//! Kraken requires the nonce of each private call to be larger than that of the previous call
+//! made with the same API key. Otherwise the call fails with `EAPI:Invalid nonce`.
+//! Using the wall-clock time as a nonce can fail this if two calls are made within the same
+//! millisecond, if the clock steps backwards, or if several processes share an API key.
+//!
+//! * [MonotonicNonce] is strictly increasing within a process, even across threads. This is the default.
+//! * [FileNonce] is strictly increasing across all processes which share a nonce file.
+//!
+//! Both are seeded from the wall-clock time, at a configurable [NonceResolution].
+//! Note that once an API key has been used with a particular resolution, it cannot be used
+//! with a coarser resolution, because the nonces would be smaller.
+
+use crate::{Error, Result};
+use std::{
+    ffi::OsString,
+    fs::{self, File, OpenOptions},
+    io::{self, ErrorKind, Write},
+    path::PathBuf,
+    sync::atomic::{AtomicU64, Ordering},
+    time::SystemTime,
+};
+
+/// A source of nonces for private REST API calls
+pub trait NonceProvider: Send + Sync {
+    /// Get the next nonce. This should be larger than any nonce previously returned.
+    fn next_nonce(&self) -> Result<u64>;
+}
+
+/// The resolution of the timestamps which nonces are seeded from
+#[derive(Debug, Default, Copy, Clone, Eq, PartialEq)]
+pub enum NonceResolution {
+    /// Milliseconds since the unix epoch
+    #[default]
+    Millis,
+    /// Microseconds since the unix epoch
+    Micros,
+    /// Nanoseconds since the unix epoch
+    Nanos,
+}
+
+impl NonceResolution {
+    /// Get the current time since the unix epoch, at this resolution
+    pub fn now(&self) -> Result<u64> {
+        let since_epoch = SystemTime::now()
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .map_err(|_| Error::TimeError)?;
+        Ok(match self {
+            Self::Millis => since_epoch.as_millis() as u64,
+            Self::Micros => since_epoch.as_micros() as u64,
+            Self::Nanos => since_epoch.as_nanos() as u64,
+        })
+    }
+}
+
+/// A nonce which is the current time, or one more than the previous nonce if that is larger.
+/// This is strictly increasing within a process, even if it is shared between threads.
+#[derive(Debug, Default)]
+pub struct MonotonicNonce {
+    resolution: NonceResolution,
+    last: AtomicU64,
+}
+
+impl MonotonicNonce {
+    /// Create a new monotonic nonce, seeded from time in milliseconds
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Create a new monotonic nonce, seeded from time at a particular resolution
+    pub fn with_resolution(resolution: NonceResolution) -> Self {
+        Self {
+            resolution,
+            last: AtomicU64::new(0),
+        }
+    }
+}
+
+impl NonceProvider for MonotonicNonce {
+    fn next_nonce(&self) -> Result<u64> {
+        let now = self.resolution.now()?;
+        let prev = self
+            .last
+            .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |last| Some(now.max(last + 1)))
+            .expect("closure always returns Some");
+        Ok(now.max(prev + 1))
+    }
+}
+
+/// A nonce which is stored in a file, which is locked while the next nonce is chosen.
+/// This is strictly increasing across all threads and processes which use the same file.
+///
+/// The file is created if it does not exist. It is locked through a separate `<file>.lock` file,
+/// and each new nonce is written to `<file>.tmp` and then renamed over it, so that a crash never leaves
+/// a partly written nonce behind. If the file holds anything but a nonce, an error is returned rather
+/// than falling back to the wall-clock time.
+#[derive(Debug, Clone)]
+pub struct FileNonce {
+    path: PathBuf,
+    resolution: NonceResolution,
+}
+
+impl FileNonce {
+    /// Create a new file nonce, seeded from time in milliseconds
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self::with_resolution(path, Default::default())
+    }
+
+    /// Create a new file nonce, seeded from time at a particular resolution
+    pub fn with_resolution(path: impl Into<PathBuf>, resolution: NonceResolution) -> Self {
+        Self {
+            path: path.into(),
+            resolution,
+        }
+    }
+
+    // The path of the file, with a suffix appended
+    fn sibling(&self, suffix: &str) -> PathBuf {
+        let mut path = OsString::from(self.path.as_os_str());
+        path.push(suffix);
+        path.into()
+    }
+
+    // Read the last nonce, which is 0 if there is none yet
+    fn read_last(&self) -> io::Result<u64> {
+        let contents = match fs::read_to_string(&self.path) {
+            Ok(contents) => contents,
+            Err(err) if err.kind() == ErrorKind::NotFound => return Ok(0),
+            Err(err) => return Err(err),
+        };
+        let contents = contents.trim();
+        if contents.is_empty() {
+            return Ok(0);
+        }
+        contents.parse().map_err(|_| {
+            io::Error::new(
+                ErrorKind::InvalidData,
+                format!("nonce file {} does not contain a nonce", self.path.display()),
+            )
+        })
+    }
+
+    // Replace the nonce in the file, so that it holds either the old or the new nonce even after a crash
+    fn write(&self, nonce: u64) -> io::Result<()> {
+        let tmp_path = self.sibling(".tmp");
+        let mut tmp = File::create(&tmp_path)?;
+        write!(tmp, "{nonce}")?;
+        tmp.sync_data()?;
+        fs::rename(&tmp_path, &self.path)
+    }
+}
+
+impl NonceProvider for FileNonce {
+    fn next_nonce(&self) -> Result<u64> {
+        // Each call opens the lock file anew, so that the lock also excludes other threads of this process.
+        // The nonce file itself is replaced on each write, so it can't be the one which is locked.
+        let lock = OpenOptions::new()
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(self.sibling(".lock"))
+            .map_err(Error::NonceFile)?;
+        lock.lock().map_err(Error::NonceFile)?;
+
+        let last = self.read_last().map_err(Error::NonceFile)?;
+        let nonce = self.resolution.now()?.max(last + 1);
+        self.write(nonce).map_err(Error::NonceFile)?;
+        lock.unlock().map_err(Error::NonceFile)?;
+        Ok(nonce)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{collections::BTreeSet, thread};
+
+    #[test]
+    fn test_monotonic_nonce_across_threads() {
+        let provider = MonotonicNonce::new();
+        let nonces: Vec<Vec<u64>> = thread::scope(|scope| {
+            let handles: Vec<_> = (0..4)
+                .map(|_| scope.spawn(|| (0..1000).map(|_| provider.next_nonce().unwrap()).collect()))
+                .collect();
+            handles.into_iter().map(|handle| handle.join().unwrap()).collect()
+        });
+        // Each thread sees increasing nonces, and no nonce is handed out twice
+        for thread_nonces in &nonces {
+            assert!(thread_nonces.windows(2).all(|pair| pair[0] < pair[1]));
+        }
+        let unique: BTreeSet<u64> = nonces.iter().flatten().copied().collect();
+        assert_eq!(unique.len(), 4000);
+    }
+
+    #[test]
+    fn test_nonce_resolution() {
+        let millis = MonotonicNonce::new().next_nonce().unwrap();
+        let micros = MonotonicNonce::with_resolution(NonceResolution::Micros)
+            .next_nonce()
+            .unwrap();
+        let nanos = MonotonicNonce::with_resolution(NonceResolution::Nanos)
+            .next_nonce()
+            .unwrap();
+        assert!(micros / 1000 >= millis);
+        assert!(nanos / 1000 >= micros);
+    }
+
+    #[test]
+    fn test_file_nonce_shared() {
+        let path = std::env::temp_dir().join(format!("krakenrs-nonce-test-{}", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        // Two providers using the same file interleave strictly increasing nonces
+        let first = FileNonce::new(&path);
+        let second = FileNonce::new(&path);
+        let mut last = 0;
+        for _ in 0..100 {
+            for provider in [&first, &second] {
+                let nonce = provider.next_nonce().unwrap();
+                assert!(nonce > last);
+                last = nonce;
+            }
+        }
+
+        // A nonce file from the future (e.g. written before the clock stepped back) is respected
+        let future = MonotonicNonce::new().next_nonce().unwrap() + 1_000_000;
+        std::fs::write(&path, future.to_string()).unwrap();
+        assert_eq!(first.next_nonce().unwrap(), future + 1);
+
+        // A corrupt nonce file is an error, rather than falling back to the time
+        std::fs::write(&path, "12ab").unwrap();
+        match first.next_nonce() {
+            Err(Error::NonceFile(err)) => assert_eq!(err.kind(), ErrorKind::InvalidData),
+            other => panic!("unexpected result: {other:?}"),
+        }
+        std::fs::remove_file(&path).unwrap();
+        std::fs::remove_file(first.sibling(".lock")).unwrap();
+    }
+}
@@ -0,0 +1,738 @@
+//! A general description of an order to place, covering all of the order types
+//! and options that Kraken's AddOrder supports.
+//!
+//! [MarketOrder] and [LimitOrder] cover the most common cases, and can be converted into an [Order].
+//! They can be created from `Decimal` volumes and prices, optionally rounded to the precision of the pair.
+//!
+//! Any of these can carry a [ConditionalClose], an order which Kraken places when the order fills,
+//! so that e.g. an entry and its take-profit are placed atomically.
+//!
+//! Up to [MAX_ORDER_BATCH] orders in the same pair can be placed in one call, as a batch.
+
+use crate::{
+    AssetPair, BsType, Error, LimitOrder, MarketOrder, OrderFlag, OrderPrice, OrderTime, OrderTrigger, OrderType,
+    Result, SelfTradePrevention, TimeInForce, TrailingOffset, UserRefId,
+    messages::{AddOrderBatchRequest, AddOrderRequest, BatchOrder, CancelOrderBatchRequest},
+};
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeSet;
+
+/// The maximum number of orders in a batch passed to `add_order_batch`
+pub const MAX_ORDER_BATCH: usize = 15;
+
+/// The maximum number of orders in a batch passed to `cancel_order_batch`
+pub const MAX_CANCEL_BATCH: usize = 50;
+
+/// A description of an order to place.
+///
+/// Use the constructors for each order type, and then the builder-style methods to set options, e.g.
+/// `Order::stop_loss_limit(BsType::Sell, "0.01", "XBTUSD", "25000", "24900").trigger(OrderTrigger::Index)`
+///
+/// The meaning of `price` and `price2` depends on the order type:
+/// * limit: `price` is the limit price
+/// * stop-loss, take-profit: `price` is the trigger price
+/// * stop-loss-limit, take-profit-limit: `price` is the trigger price, and `price2` is the limit price
+/// * trailing-stop: `price` is the trailing offset
+/// * trailing-stop-limit: `price` is the trailing offset, and `price2` is the offset of the limit price
+///   from the trigger price
+/// * iceberg: `price` is the limit price, and `displayvol` is the visible volume
+///
+/// Prices can be relative, see [OrderPrice]. An [OrderPrice] can be passed wherever a price is expected.
+#[derive(Debug, Clone)]
+pub struct Order {
+    /// Whether to buy or sell
+    pub bs_type: BsType,
+    /// The order type
+    pub ordertype: OrderType,
+    /// Volume (in lots)
+    pub volume: String,
+    /// Asset pair
+    pub pair: String,
+    /// Primary price
+    pub price: String,
+    /// Secondary price
+    pub price2: String,
+    /// Visible volume of iceberg orders
+    pub displayvol: String,
+    /// Price signal which triggers stop-loss and take-profit orders (default last)
+    pub trigger: Option<OrderTrigger>,
+    /// Time-in-force (default GTC)
+    pub timeinforce: Option<TimeInForce>,
+    /// Scheduled start time (default now)
+    pub starttm: Option<OrderTime>,
+    /// Expiration time (required for GTD orders)
+    pub expiretm: Option<OrderTime>,
+    /// RFC3339 timestamp, after which the matching engine should reject the order rather than place it
+    pub deadline: Option<String>,
+    /// Order flags (post-only etc.)
+    pub oflags: BTreeSet<OrderFlag>,
+    /// Amount of leverage, for margin orders
+    pub leverage: Option<Decimal>,
+    /// If true, the order can only reduce an open margin position, and never increase or flip it
+    pub reduce_only: bool,
+    /// Self-trade prevention, when this order would match another of our orders (default cancel-newest)
+    pub stptype: Option<SelfTradePrevention>,
+    /// User ref id to attach to the order
+    pub userref: Option<UserRefId>,
+    /// Client order id to attach to the order. Unlike a user ref id, this must be unique among open orders,
+    /// so it identifies a single order. Kraken does not allow both a user ref id and a client order id.
+    pub cl_ord_id: Option<String>,
+    /// Conditional close order, placed when this order fills
+    pub close: Option<ConditionalClose>,
+}
+
+impl Order {
+    /// Create an order of any type, with no prices set
+    pub fn new(bs_type: BsType, ordertype: OrderType, volume: impl Into<String>, pair: impl Into<String>) -> Self {
+        Self {
+            bs_type,
+            ordertype,
+            volume: volume.into(),
+            pair: pair.into(),
+            price: Default::default(),
+            price2: Default::default(),
+            displayvol: Default::default(),
+            trigger: None,
+            timeinforce: None,
+            starttm: None,
+            expiretm: None,
+            deadline: None,
+            oflags: Default::default(),
+            leverage: None,
+            reduce_only: false,
+            stptype: None,
+            userref: None,
+            cl_ord_id: None,
+            close: None,
+        }
+    }
+
+    /// A market order
+    pub fn market(bs_type: BsType, volume: impl Into<String>, pair: impl Into<String>) -> Self {
+        Self::new(bs_type, OrderType::Market, volume, pair)
+    }
+
+    /// A limit order
+    pub fn limit(
+        bs_type: BsType,
+        volume: impl Into<String>,
+        pair: impl Into<String>,
+        price: impl Into<String>,
+    ) -> Self {
+        Self::new(bs_type, OrderType::Limit, volume, pair).price(price)
+    }
+
+    /// A stop-loss order, which becomes a market order when the trigger price is reached
+    pub fn stop_loss(
+        bs_type: BsType,
+        volume: impl Into<String>,
+        pair: impl Into<String>,
+        trigger_price: impl Into<String>,
+    ) -> Self {
+        Self::new(bs_type, OrderType::StopLoss, volume, pair).price(trigger_price)
+    }
+
+    /// A take-profit order, which becomes a market order when the trigger price is reached
+    pub fn take_profit(
+        bs_type: BsType,
+        volume: impl Into<String>,
+        pair: impl Into<String>,
+        trigger_price: impl Into<String>,
+    ) -> Self {
+        Self::new(bs_type, OrderType::TakeProfit, volume, pair).price(trigger_price)
+    }
+
+    /// A stop-loss-limit order, which becomes a limit order when the trigger price is reached
+    pub fn stop_loss_limit(
+        bs_type: BsType,
+        volume: impl Into<String>,
+        pair: impl Into<String>,
+        trigger_price: impl Into<String>,
+        limit_price: impl Into<String>,
+    ) -> Self {
+        Self::new(bs_type, OrderType::StopLossLimit, volume, pair)
+            .price(trigger_price)
+            .price2(limit_price)
+    }
+
+    /// A take-profit-limit order, which becomes a limit order when the trigger price is reached
+    pub fn take_profit_limit(
+        bs_type: BsType,
+        volume: impl Into<String>,
+        pair: impl Into<String>,
+        trigger_price: impl Into<String>,
+        limit_price: impl Into<String>,
+    ) -> Self {
+        Self::new(bs_type, OrderType::TakeProfitLimit, volume, pair)
+            .price(trigger_price)
+            .price2(limit_price)
+    }
+
+    /// A trailing-stop order, which becomes a market order when the price moves against us
+    /// by the trailing offset from the best price since the order was placed.
+    pub fn trailing_stop(
+        bs_type: BsType,
+        volume: impl Into<String>,
+        pair: impl Into<String>,
+        trailing_offset: TrailingOffset,
+    ) -> Self {
+        Self::new(bs_type, OrderType::TrailingStop, volume, pair).price(trailing_offset)
+    }
+
+    /// A trailing-stop-limit order, which becomes a limit order when the price moves against us
+    /// by the trailing offset from the best price since the order was placed.
+    /// The limit price is given as an offset from the trigger price.
+    pub fn trailing_stop_limit(
+        bs_type: BsType,
+        volume: impl Into<String>,
+        pair: impl Into<String>,
+        trailing_offset: TrailingOffset,
+        limit_offset: OrderPrice,
+    ) -> Self {
+        Self::new(bs_type, OrderType::TrailingStopLimit, volume, pair)
+            .price(trailing_offset)
+            .price2(limit_offset)
+    }
+
+    /// A settle-position order, which settles an open margin position by paying for it with the quote currency
+    /// (for a long position) or delivering the base currency (for a short position).
+    ///
+    /// `bs_type` is the opposite of the position's direction, and `leverage` must be set, as for the position.
+    /// A volume of "0" settles the entire position.
+    pub fn settle_position(
+        bs_type: BsType,
+        volume: impl Into<String>,
+        pair: impl Into<String>,
+        leverage: Decimal,
+    ) -> Self {
+        Self::new(bs_type, OrderType::SettlePosition, volume, pair).leverage(leverage)
+    }
+
+    /// An iceberg order, which is a limit order showing only part of its volume in the book at a time
+    pub fn iceberg(
+        bs_type: BsType,
+        volume: impl Into<String>,
+        pair: impl Into<String>,
+        price: impl Into<String>,
+        display_volume: impl Into<String>,
+    ) -> Self {
+        Self::new(bs_type, OrderType::Iceberg, volume, pair)
+            .price(price)
+            .display_volume(display_volume)
+    }
+
+    /// Set the primary price
+    pub fn price(mut self, price: impl Into<String>) -> Self {
+        self.price = price.into();
+        self
+    }
+
+    /// Set the secondary price
+    pub fn price2(mut self, price2: impl Into<String>) -> Self {
+        self.price2 = price2.into();
+        self
+    }
+
+    /// Set the visible volume of an iceberg order
+    pub fn display_volume(mut self, displayvol: impl Into<String>) -> Self {
+        self.displayvol = displayvol.into();
+        self
+    }
+
+    /// Set the price signal which triggers a stop-loss or take-profit order
+    pub fn trigger(mut self, trigger: OrderTrigger) -> Self {
+        self.trigger = Some(trigger);
+        self
+    }
+
+    /// Set the time-in-force
+    pub fn time_in_force(mut self, timeinforce: TimeInForce) -> Self {
+        self.timeinforce = Some(timeinforce);
+        self
+    }
+
+    /// Set the amount of leverage, making this a margin order
+    pub fn leverage(mut self, leverage: Decimal) -> Self {
+        self.leverage = Some(leverage);
+        self
+    }
+
+    /// Only allow the order to reduce an open margin position
+    pub fn reduce_only(mut self) -> Self {
+        self.reduce_only = true;
+        self
+    }
+
+    /// Set the self-trade prevention
+    pub fn self_trade_prevention(mut self, stptype: SelfTradePrevention) -> Self {
+        self.stptype = Some(stptype);
+        self
+    }
+
+    /// Set the scheduled start time
+    pub fn start_time(mut self, starttm: OrderTime) -> Self {
+        self.starttm = Some(starttm);
+        self
+    }
+
+    /// Set the expiration time. This is required for GTD orders.
+    pub fn expire_time(mut self, expiretm: OrderTime) -> Self {
+        self.expiretm = Some(expiretm);
+        self
+    }
+
+    /// Set an RFC3339 timestamp, after which the matching engine should reject the order.
+    /// Kraken requires this to be between 2 and 60 seconds in the future.
+    pub fn deadline(mut self, deadline: impl Into<String>) -> Self {
+        self.deadline = Some(deadline.into());
+        self
+    }
+
+    /// Add an order flag
+    pub fn oflag(mut self, oflag: OrderFlag) -> Self {
+        self.oflags.insert(oflag);
+        self
+    }
+
+    /// Set the user ref id
+    pub fn userref(mut self, userref: UserRefId) -> Self {
+        self.userref = Some(userref);
+        self
+    }
+
+    /// Set the client order id (e.g. a UUID)
+    pub fn cl_ord_id(mut self, cl_ord_id: impl Into<String>) -> Self {
+        self.cl_ord_id = Some(cl_ord_id.into());
+        self
+    }
+
+    /// Attach a conditional close order, which is placed when this order fills
+    pub fn close(mut self, close: ConditionalClose) -> Self {
+        self.close = Some(close);
+        self
+    }
+
+    /// Make the REST API request to place this order
+    pub(crate) fn into_request(self, validate: bool) -> AddOrderRequest {
+        AddOrderRequest {
+            userref: self.userref,
+            cl_ord_id: self.cl_ord_id,
+            ordertype: self.ordertype,
+            bs_type: self.bs_type,
+            volume: self.volume,
+            pair: self.pair,
+            price: self.price,
+            price2: self.price2,
+            displayvol: self.displayvol,
+            trigger: self.trigger,
+            oflags: self.oflags,
+            leverage: self.leverage.map(|leverage| leverage.to_string()).unwrap_or_default(),
+            reduce_only: self.reduce_only,
+            stptype: self.stptype,
+            timeinforce: self.timeinforce,
+            starttm: self.starttm,
+            expiretm: self.expiretm,
+            deadline: self.deadline,
+            close: self.close,
+            validate,
+        }
+    }
+
+    /// Make the description of this order within a batch.
+    /// The pair and deadline are set on the batch rather than on its orders.
+    fn into_batch_order(self) -> BatchOrder {
+        BatchOrder {
+            userref: self.userref,
+            cl_ord_id: self.cl_ord_id,
+            ordertype: self.ordertype,
+            bs_type: self.bs_type,
+            volume: self.volume,
+            price: self.price,
+            price2: self.price2,
+            trigger: self.trigger,
+            displayvol: self.displayvol,
+            oflags: self.oflags,
+            leverage: self.leverage.map(|leverage| leverage.to_string()).unwrap_or_default(),
+            reduce_only: self.reduce_only,
+            stptype: self.stptype,
+            timeinforce: self.timeinforce,
+            starttm: self.starttm,
+            expiretm: self.expiretm,
+            close: self.close,
+        }
+    }
+}
+
+/// Check that a batch of orders is allowed: at least one and at most MAX_ORDER_BATCH orders, all in the same pair
+pub(crate) fn check_order_batch(orders: &[Order]) -> Result<()> {
+    let Some(first) = orders.first() else {
+        return Err(Error::InvalidBatch("no orders".into()));
+    };
+    if orders.len() > MAX_ORDER_BATCH {
+        return Err(Error::InvalidBatch(format!(
+            "{} orders, at most {} are allowed",
+            orders.len(),
+            MAX_ORDER_BATCH
+        )));
+    }
+    if let Some(order) = orders.iter().find(|order| order.pair != first.pair) {
+        return Err(Error::InvalidBatch(format!(
+            "orders must all be in the same pair, found {} and {}",
+            first.pair, order.pair
+        )));
+    }
+    Ok(())
+}
+
+/// Check that a batch of cancels is allowed: at least one and at most MAX_CANCEL_BATCH orders
+pub(crate) fn check_cancel_batch(ids: &[String]) -> Result<()> {
+    if ids.is_empty() {
+        return Err(Error::InvalidBatch("no orders".into()));
+    }
+    if ids.len() > MAX_CANCEL_BATCH {
+        return Err(Error::InvalidBatch(format!(
+            "{} orders, at most {} are allowed",
+            ids.len(),
+            MAX_CANCEL_BATCH
+        )));
+    }
+    Ok(())
+}
+
+/// Make the REST API request to place a batch of orders, checking that the batch is allowed
+pub(crate) fn add_order_batch_request(
+    orders: Vec<Order>,
+    deadline: Option<String>,
+    validate: bool,
+) -> Result<AddOrderBatchRequest> {
+    check_order_batch(&orders)?;
+    Ok(AddOrderBatchRequest {
+        pair: orders[0].pair.clone(),
+        orders: orders.into_iter().map(Order::into_batch_order).collect(),
+        deadline,
+        validate,
+    })
+}
+
+/// Make the REST API request to cancel a batch of orders, checking that the batch is allowed
+pub(crate) fn cancel_order_batch_request(ids: Vec<String>) -> Result<CancelOrderBatchRequest> {
+    check_cancel_batch(&ids)?;
+    Ok(CancelOrderBatchRequest { orders: ids })
+}
+
+/// A conditional close order, which Kraken places when the order it is attached to fills.
+/// The close order is on the opposite side, for the filled volume.
+///
+/// `price` and `price2` have the same meaning as for an [Order] of the same type.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ConditionalClose {
+    /// The order type of the close order
+    pub ordertype: OrderType,
+    /// Primary price
+    #[serde(skip_serializing_if = "String::is_empty")]
+    pub price: String,
+    /// Secondary price
+    #[serde(skip_serializing_if = "String::is_empty")]
+    pub price2: String,
+}
+
+impl ConditionalClose {
+    /// Close with a limit order
+    pub fn limit(price: impl Into<String>) -> Self {
+        Self::new(OrderType::Limit, price, "")
+    }
+
+    /// Close with a take-profit order
+    pub fn take_profit(trigger_price: impl Into<String>) -> Self {
+        Self::new(OrderType::TakeProfit, trigger_price, "")
+    }
+
+    /// Close with a stop-loss order
+    pub fn stop_loss(trigger_price: impl Into<String>) -> Self {
+        Self::new(OrderType::StopLoss, trigger_price, "")
+    }
+
+    /// Close with a take-profit-limit order
+    pub fn take_profit_limit(trigger_price: impl Into<String>, limit_price: impl Into<String>) -> Self {
+        Self::new(OrderType::TakeProfitLimit, trigger_price, limit_price)
+    }
+
+    /// Close with a stop-loss-limit order
+    pub fn stop_loss_limit(trigger_price: impl Into<String>, limit_price: impl Into<String>) -> Self {
+        Self::new(OrderType::StopLossLimit, trigger_price, limit_price)
+    }
+
+    /// Close with a trailing-stop order
+    pub fn trailing_stop(trailing_offset: TrailingOffset) -> Self {
+        Self::new(OrderType::TrailingStop, trailing_offset, "")
+    }
+
+    fn new(ordertype: OrderType, price: impl Into<String>, price2: impl Into<String>) -> Self {
+        Self {
+            ordertype,
+            price: price.into(),
+            price2: price2.into(),
+        }
+    }
+}
+
+impl MarketOrder {
+    /// Create a market order from a decimal volume
+    pub fn new(bs_type: BsType, volume: Decimal, pair: impl Into<String>) -> Self {
+        Self {
+            bs_type,
+            volume: volume.to_string(),
+            pair: pair.into(),
+            oflags: Default::default(),
+        }
+    }
+
+    /// Create a market order from a decimal volume, rounded down to the precision of the asset pair
+    ///
+    /// Arguments:
+    /// * pair: The name of the asset pair, as it should appear in the order
+    /// * pair_info: The info for the asset pair, from the AssetPairs API
+    pub fn for_pair(bs_type: BsType, volume: Decimal, pair: impl Into<String>, pair_info: &AssetPair) -> Self {
+        Self::new(bs_type, pair_info.round_volume(volume), pair)
+    }
+
+    /// Attach a conditional close order, which is placed when this order fills.
+    /// The result is an [Order], which can be placed with `add_order`.
+    pub fn close(self, close: ConditionalClose) -> Order {
+        Order::from(self).close(close)
+    }
+}
+
+impl LimitOrder {
+    /// Create a limit order from a decimal volume and price
+    pub fn new(bs_type: BsType, volume: Decimal, pair: impl Into<String>, price: Decimal) -> Self {
+        Self {
+            bs_type,
+            volume: volume.to_string(),
+            pair: pair.into(),
+            price: price.to_string(),
+            oflags: Default::default(),
+        }
+    }
+
+    /// Create a limit order from a decimal volume and price, rounded to the precision of the asset pair.
+    /// The volume is rounded down, and the price is rounded so that it never becomes more aggressive.
+    ///
+    /// Arguments:
+    /// * pair: The name of the asset pair, as it should appear in the order
+    /// * pair_info: The info for the asset pair, from the AssetPairs API
+    pub fn for_pair(
+        bs_type: BsType,
+        volume: Decimal,
+        pair: impl Into<String>,
+        price: Decimal,
+        pair_info: &AssetPair,
+    ) -> Self {
+        let price = pair_info.round_price(&bs_type, price);
+        Self::new(bs_type, pair_info.round_volume(volume), pair, price)
+    }
+
+    /// Attach a conditional close order, which is placed when this order fills.
+    /// The result is an [Order], which can be placed with `add_order`.
+    pub fn close(self, close: ConditionalClose) -> Order {
+        Order::from(self).close(close)
+    }
+}
+
+impl From<MarketOrder> for Order {
+    fn from(src: MarketOrder) -> Self {
+        Self {
+            oflags: src.oflags,
+            ..Self::market(src.bs_type, src.volume, src.pair)
+        }
+    }
+}
+
+impl From<LimitOrder> for Order {
+    fn from(src: LimitOrder) -> Self {
+        Self {
+            oflags: src.oflags,
+            ..Self::limit(src.bs_type, src.volume, src.pair, src.price)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_order_request_encoding() {
+        let order = Order::stop_loss_limit(BsType::Sell, "0.5", "XBTUSD", "25000", "24900")
+            .trigger(OrderTrigger::Index)
+            .time_in_force(TimeInForce::Gtd)
+            .start_time(OrderTime::Now)
+            .expire_time(OrderTime::FromNow(3600))
+            .deadline("2023-07-06T18:50:48Z")
+            .userref(7);
+        let qs = serde_qs::to_string(&order.into_request(false)).unwrap();
+        assert_eq!(
+            qs,
+            "userref=7&ordertype=stop-loss-limit&type=sell&volume=0.5&pair=XBTUSD&price=25000&price2=24900\
+             &trigger=index&timeinforce=GTD&starttm=0&expiretm=%2B3600&deadline=2023-07-06T18%3A50%3A48Z"
+        );
+
+        let order = Order::from(MarketOrder {
+            bs_type: BsType::Buy,
+            volume: "1".into(),
+            pair: "XBTUSD".into(),
+            oflags: [OrderFlag::Fciq].into(),
+        });
+        let qs = serde_qs::to_string(&order.into_request(true)).unwrap();
+        assert_eq!(
+            qs,
+            "ordertype=market&type=buy&volume=1&pair=XBTUSD&oflags=fciq&validate=true"
+        );
+    }
+
+    #[test]
+    fn test_margin_and_self_trade_options() {
+        let order = Order::limit(BsType::Sell, "0.5", "XBTUSD", "31000")
+            .leverage(Decimal::from(2))
+            .reduce_only()
+            .self_trade_prevention(SelfTradePrevention::CancelOldest);
+        let qs = serde_qs::to_string(&order.into_request(false)).unwrap();
+        assert_eq!(
+            qs,
+            "ordertype=limit&type=sell&volume=0.5&pair=XBTUSD&price=31000\
+             &leverage=2&reduce_only=true&stptype=cancel-oldest"
+        );
+
+        let order = Order::settle_position(BsType::Sell, "0", "XBTUSD", Decimal::from(2));
+        let qs = serde_qs::to_string(&order.into_request(false)).unwrap();
+        assert_eq!(
+            qs,
+            "ordertype=settle-position&type=sell&volume=0&pair=XBTUSD&leverage=2"
+        );
+    }
+
+    #[test]
+    fn test_trailing_and_iceberg_orders() {
+        let order = Order::trailing_stop_limit(
+            BsType::Sell,
+            "1",
+            "XBTUSD",
+            TrailingOffset::Percent(2.into()),
+            OrderPrice::Minus(50.into()),
+        );
+        let qs = serde_qs::to_string(&order.into_request(false)).unwrap();
+        assert_eq!(
+            qs,
+            "ordertype=trailing-stop-limit&type=sell&volume=1&pair=XBTUSD&price=%2B2%25&price2=-50"
+        );
+
+        let order = Order::iceberg(BsType::Buy, "5", "XBTUSD", "27500", "0.5");
+        let qs = serde_qs::to_string(&order.into_request(false)).unwrap();
+        assert_eq!(
+            qs,
+            "ordertype=iceberg&type=buy&volume=5&pair=XBTUSD&price=27500&displayvol=0.5"
+        );
+    }
+
+    #[test]
+    fn test_conditional_close() {
+        let order = LimitOrder {
+            bs_type: BsType::Buy,
+            volume: "1".into(),
+            pair: "XBTUSD".into(),
+            price: "30000".into(),
+            oflags: Default::default(),
+        }
+        .close(ConditionalClose::stop_loss_limit("29000", "28900"));
+        let qs = serde_qs::to_string(&order.into_request(false)).unwrap();
+        assert_eq!(
+            qs,
+            "ordertype=limit&type=buy&volume=1&pair=XBTUSD&price=30000\
+             &close[ordertype]=stop-loss-limit&close[price]=29000&close[price2]=28900"
+        );
+    }
+
+    #[test]
+    fn test_order_time() {
+        for (time, src) in [
+            (OrderTime::Now, "0"),
+            (OrderTime::FromNow(60), "+60"),
+            (OrderTime::At(1688669448), "1688669448"),
+        ] {
+            assert_eq!(time.to_string(), src);
+            assert_eq!(src.parse::<OrderTime>(), Ok(time));
+        }
+        assert!("+".parse::<OrderTime>().is_err());
+        assert!("soon".parse::<OrderTime>().is_err());
+    }
+
+    #[test]
+    fn test_order_batch_request() {
+        let orders = vec![
+            Order::limit(BsType::Buy, "0.01", "XBTUSD", "29000").userref(1),
+            Order::limit(BsType::Buy, "0.02", "XBTUSD", "28900").deadline("ignored"),
+        ];
+        let req = add_order_batch_request(orders, Some("2023-07-06T18:50:48Z".into()), true).unwrap();
+        let qs = serde_qs::to_string(&req).unwrap();
+        assert_eq!(
+            qs,
+            "orders[0][userref]=1&orders[0][ordertype]=limit&orders[0][type]=buy&orders[0][volume]=0.01\
+             &orders[0][price]=29000&orders[1][ordertype]=limit&orders[1][type]=buy&orders[1][volume]=0.02\
+             &orders[1][price]=28900&pair=XBTUSD&deadline=2023-07-06T18%3A50%3A48Z&validate=true"
+        );
+
+        let too_many = vec![Order::limit(BsType::Buy, "0.01", "XBTUSD", "29000"); MAX_ORDER_BATCH + 1];
+        assert!(matches!(
+            add_order_batch_request(too_many, None, false),
+            Err(Error::InvalidBatch(_))
+        ));
+        let mixed_pairs = vec![
+            Order::limit(BsType::Buy, "0.01", "XBTUSD", "29000"),
+            Order::limit(BsType::Buy, "0.01", "ETHUSD", "1900"),
+        ];
+        assert!(matches!(
+            add_order_batch_request(mixed_pairs, None, false),
+            Err(Error::InvalidBatch(_))
+        ));
+        assert!(matches!(
+            add_order_batch_request(vec![], None, false),
+            Err(Error::InvalidBatch(_))
+        ));
+
+        let req = cancel_order_batch_request(vec!["OABC".into(), "7".into()]).unwrap();
+        assert_eq!(serde_qs::to_string(&req).unwrap(), "orders[0]=OABC&orders[1]=7");
+        assert!(cancel_order_batch_request(vec!["OABC".into(); MAX_CANCEL_BATCH + 1]).is_err());
+    }
+
+    #[test]
+    fn test_decimal_orders() {
+        let order = LimitOrder::new(BsType::Buy, Decimal::new(1, 2), "XBTUSD", Decimal::new(2900050, 2));
+        assert_eq!(order.volume, "0.01");
+        assert_eq!(order.price, "29000.50");
+
+        let pair_info = AssetPair {
+            pair_decimals: 1,
+            lot_decimals: 4,
+            ..Default::default()
+        };
+        // e.g. the mid price of a book, and a third of a balance
+        let price = (Decimal::new(290001, 1) + Decimal::new(290004, 1)) / Decimal::TWO;
+        let volume = Decimal::ONE / Decimal::from(3);
+        let order = LimitOrder::for_pair(BsType::Buy, volume, "XBTUSD", price, &pair_info);
+        assert_eq!(order.volume, "0.3333");
+        assert_eq!(order.price, "29000.2");
+        let order = LimitOrder::for_pair(BsType::Sell, volume, "XBTUSD", price, &pair_info);
+        assert_eq!(order.price, "29000.3");
+
+        let pair_info = AssetPair {
+            tick_size: Some(Decimal::new(5, 1)),
+            ..pair_info
+        };
+        let order = LimitOrder::for_pair(BsType::Sell, volume, "XBTUSD", price, &pair_info);
+        assert_eq!(order.price, "29000.5");
+
+        let order: Order = MarketOrder::for_pair(BsType::Sell, Decimal::new(123456, 5), "XBTUSD", &pair_info).into();
+        assert_eq!(order.volume, "1.2345");
+    }
+}
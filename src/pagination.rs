@@ -0,0 +1,441 @@
+//! Iterators which follow the `last`, `ofs` and `cursor` pagination schemes
+//! of the Kraken REST API, so that callers don't have to write the paging loop
+//! by hand every time.
+//!
+//! * Get Recent Trades and OHLC return a `last` value which is passed as `since` for the next page.
+//! * ClosedOrders, TradesHistory and Ledgers return at most 50 results, and a total `count`.
+//!   The next page is found by passing an `ofs` (offset).
+//! * DepositStatus and WithdrawStatus return a `next_cursor` when a `cursor` is passed.
+
+use crate::{
+    Candle, DepositStatus, DepositStatusRequest, GetClosedOrdersRequest, KrakenRestAPI, LedgerEntry, LedgersRequest,
+    OrderInfo, PublicTrade, Result, TradeInfo, TradesHistoryRequest, TxId, WithdrawStatusRequest, WithdrawalStatus,
+    messages::{KrakenResult, unpack_kraken_result},
+};
+use core::{cmp::Reverse, marker::PhantomData};
+use rust_decimal::Decimal;
+use serde::{
+    Deserialize, Deserializer, Serialize,
+    de::{Error, MapAccess, SeqAccess, Visitor},
+};
+use std::{
+    collections::VecDeque,
+    thread,
+    time::{Duration, Instant},
+};
+
+/// The default pause between fetching consecutive pages
+pub const DEFAULT_PAGE_PAUSE: Duration = Duration::from_secs(1);
+
+// A function which fetches the next page of results, or returns None if there are no more pages.
+// It is passed the `until` timestamp of the iterator, so that it can be sent to kraken where supported.
+type FetchPage<'a, T> = Box<dyn FnMut(&KrakenRestAPI, Option<Decimal>) -> Result<Option<Vec<T>>> + 'a>;
+
+/// An iterator over the items of a paginated Kraken REST API.
+///
+/// Pages are fetched lazily as the iterator is advanced. If fetching a page fails,
+/// the error is yielded and the iteration ends.
+///
+/// The iterator can be configured with:
+/// * `pause`: A minimum time to wait between requests for consecutive pages, to stay under rate limits.
+///   Defaults to [DEFAULT_PAGE_PAUSE].
+/// * `until`: A unix timestamp. Items later than this are not yielded. For APIs where results are
+///   returned in chronological order (recent trades, OHLC), the iteration stops at the first such item.
+///   For private APIs, this is also passed to kraken as the `end` of the request, unless it already has one.
+pub struct PageIter<'a, T> {
+    api: &'a KrakenRestAPI,
+    fetch_page: FetchPage<'a, T>,
+    timestamp: fn(&T) -> Decimal,
+    chronological: bool,
+    buffer: VecDeque<T>,
+    pause: Duration,
+    until: Option<Decimal>,
+    last_fetch: Option<Instant>,
+    done: bool,
+}
+
+impl<'a, T> PageIter<'a, T> {
+    fn new(
+        api: &'a KrakenRestAPI,
+        fetch_page: FetchPage<'a, T>,
+        timestamp: fn(&T) -> Decimal,
+        chronological: bool,
+    ) -> Self {
+        Self {
+            api,
+            fetch_page,
+            timestamp,
+            chronological,
+            buffer: Default::default(),
+            pause: DEFAULT_PAGE_PAUSE,
+            until: None,
+            last_fetch: None,
+            done: false,
+        }
+    }
+
+    /// Set the minimum time to wait between requests for consecutive pages
+    pub fn pause(mut self, pause: Duration) -> Self {
+        self.pause = pause;
+        self
+    }
+
+    /// Set a unix timestamp, such that later items are not yielded
+    pub fn until(mut self, until: Decimal) -> Self {
+        self.until = Some(until);
+        self
+    }
+
+    // Wait until the pause has elapsed since the last fetch, then fetch the next page
+    fn fetch(&mut self) -> Result<Option<Vec<T>>> {
+        if let Some(last_fetch) = self.last_fetch {
+            let elapsed = last_fetch.elapsed();
+            if elapsed < self.pause {
+                thread::sleep(self.pause - elapsed);
+            }
+        }
+        let result = (self.fetch_page)(self.api, self.until);
+        self.last_fetch = Some(Instant::now());
+        result
+    }
+}
+
+impl<T> Iterator for PageIter<'_, T> {
+    type Item = Result<T>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(item) = self.buffer.pop_front() {
+                if let Some(until) = self.until
+                    && (self.timestamp)(&item) > until
+                {
+                    if self.chronological {
+                        self.done = true;
+                        self.buffer.clear();
+                        return None;
+                    }
+                    continue;
+                }
+                return Some(Ok(item));
+            }
+            if self.done {
+                return None;
+            }
+            match self.fetch() {
+                Ok(Some(page)) => {
+                    if page.is_empty() {
+                        self.done = true;
+                    }
+                    self.buffer.extend(page);
+                }
+                Ok(None) => {
+                    self.done = true;
+                }
+                Err(err) => {
+                    self.done = true;
+                    return Some(Err(err));
+                }
+            }
+        }
+    }
+}
+
+impl KrakenRestAPI {
+    /// (Public) Iterate over all recent trades in an asset pair since a particular timestamp,
+    /// following the `last` value of each page of Get Recent Trades.
+    ///
+    /// The iteration ends when kraken has no newer trades, or when `until` is reached.
+    ///
+    /// Arguments:
+    /// * pair: Which asset pair to get trades for
+    /// * since: A timestamp to get trades since
+    pub fn recent_trades_since(&self, pair: String, since: Option<String>) -> PageIter<'_, PublicTrade> {
+        let mut since = since;
+        let fetch_page = move |api: &KrakenRestAPI, _until| {
+            let page = api.get_recent_trades(pair.clone(), since.clone())?;
+            if since.as_ref() == Some(&page.last) {
+                return Ok(None);
+            }
+            since = Some(page.last);
+            Ok(Some(page.data))
+        };
+        PageIter::new(self, Box::new(fetch_page), |trade| trade.timestamp, true)
+    }
+
+    /// (Public) Iterate over OHLC data for an asset pair since a particular timestamp,
+    /// following the `last` value of each page.
+    ///
+    /// Note that kraken only serves the 720 most recent candles, regardless of `since`.
+    /// The last candle of the most recent page is the current, not yet committed candle.
+    ///
+    /// Arguments:
+    /// * pair: Which asset pair to get data for
+    /// * interval: The width of each candle in minutes
+    /// * since: A timestamp to get data since
+    pub fn ohlc_since(&self, pair: String, interval: u16, since: Option<String>) -> PageIter<'_, Candle> {
+        let mut since = since;
+        let fetch_page = move |api: &KrakenRestAPI, _until| {
+            let page = api.ohlc_at_interval(pair.clone(), interval, since.clone())?;
+            if since.as_ref() == Some(&page.last) {
+                return Ok(None);
+            }
+            since = Some(page.last);
+            Ok(Some(page.data))
+        };
+        PageIter::new(self, Box::new(fetch_page), |candle| candle.timestamp, true)
+    }
+
+    /// (Private) Iterate over all closed orders matching a request, following `ofs`.
+    ///
+    /// Orders are yielded most recently closed first. The `ofs` of the request is used as the starting offset.
+    /// Unless the request has an `end` or the iterator has an `until`, the end is pinned to the first page,
+    /// so that orders closed in the meantime don't shift the offsets of later pages.
+    pub fn closed_orders_iter(&self, request: GetClosedOrdersRequest) -> PageIter<'_, (TxId, OrderInfo)> {
+        let mut request = request;
+        let fetch_page = move |api: &KrakenRestAPI, until| {
+            let ofs = request.ofs.unwrap_or(0);
+            let page = api.get_closed_orders(GetClosedOrdersRequest {
+                ofs: Some(ofs),
+                end: end_or_until(&request.end, until),
+                ..request.clone()
+            })?;
+            if page.closed.is_empty() || ofs >= page.count {
+                return Ok(None);
+            }
+            request.ofs = Some(ofs + page.closed.len() as u64);
+            let mut items: Vec<_> = page.closed.into_iter().collect();
+            items.sort_by_key(|item| Reverse(closed_order_time(item)));
+            // Pin the end to the newest item of the first page, so that later pages have stable offsets
+            if request.end.is_none() && until.is_none() {
+                request.end = items.first().map(|item| closed_order_time(item).to_string());
+            }
+            Ok(Some(items))
+        };
+        PageIter::new(self, Box::new(fetch_page), closed_order_time, false)
+    }
+
+    /// (Private) Iterate over all of our trades (fills) matching a request, following `ofs`.
+    ///
+    /// Trades are yielded most recent first. The `ofs` of the request is used as the starting offset.
+    /// Unless the request has an `end` or the iterator has an `until`, the end is pinned to the first page,
+    /// so that new trades don't shift the offsets of later pages.
+    pub fn trades_history_iter(&self, request: TradesHistoryRequest) -> PageIter<'_, (TxId, TradeInfo)> {
+        let mut request = request;
+        let fetch_page = move |api: &KrakenRestAPI, until| {
+            let ofs = request.ofs.unwrap_or(0);
+            let page = api.get_trades_history(TradesHistoryRequest {
+                ofs: Some(ofs),
+                end: end_or_until(&request.end, until),
+                ..request.clone()
+            })?;
+            if page.trades.is_empty() || ofs >= page.count {
+                return Ok(None);
+            }
+            request.ofs = Some(ofs + page.trades.len() as u64);
+            let mut items: Vec<_> = page.trades.into_iter().collect();
+            items.sort_by_key(|item| Reverse(item.1.time));
+            if request.end.is_none() && until.is_none() {
+                request.end = items.first().map(|item| item.1.time.to_string());
+            }
+            Ok(Some(items))
+        };
+        PageIter::new(self, Box::new(fetch_page), |(_, trade)| trade.time, false)
+    }
+
+    /// (Private) Iterate over all ledger entries matching a request, following `ofs`.
+    ///
+    /// Entries are yielded most recent first. The `ofs` of the request is used as the starting offset.
+    /// Unless the request has an `end` or the iterator has an `until`, the end is pinned to the first page,
+    /// so that new entries don't shift the offsets of later pages.
+    pub fn ledgers_iter(&self, request: LedgersRequest) -> PageIter<'_, (String, LedgerEntry)> {
+        let mut request = request;
+        let fetch_page = move |api: &KrakenRestAPI, until| {
+            let ofs = request.ofs.unwrap_or(0);
+            let page = api.get_ledgers(LedgersRequest {
+                ofs: Some(ofs),
+                end: end_or_until(&request.end, until),
+                ..request.clone()
+            })?;
+            if page.ledger.is_empty() || page.count.is_some_and(|count| ofs >= count) {
+                return Ok(None);
+            }
+            request.ofs = Some(ofs + page.ledger.len() as u64);
+            let mut items: Vec<_> = page.ledger.into_iter().collect();
+            items.sort_by_key(|item| Reverse(item.1.time));
+            if request.end.is_none() && until.is_none() {
+                request.end = items.first().map(|item| item.1.time.to_string());
+            }
+            Ok(Some(items))
+        };
+        PageIter::new(self, Box::new(fetch_page), |(_, entry)| entry.time, false)
+    }
+
+    /// (Private) Iterate over the status of all withdrawals matching a request, following `next_cursor`.
+    ///
+    /// If the request has no cursor, pagination is enabled starting from the first page.
+    pub fn withdraw_status_iter(&self, request: WithdrawStatusRequest) -> PageIter<'_, WithdrawalStatus> {
+        let mut request = request;
+        let mut cursor = Some(request.cursor.take().unwrap_or_else(|| "true".to_owned()));
+        let fetch_page = move |api: &KrakenRestAPI, until| {
+            let Some(current) = cursor.take() else {
+                return Ok(None);
+            };
+            let result: Result<KrakenResult<CursorPage<WithdrawalStatus>>> = api.client.query_private(
+                "WithdrawStatus",
+                WithdrawStatusRequest {
+                    cursor: Some(current),
+                    end: end_or_until(&request.end, until),
+                    ..request.clone()
+                },
+            );
+            let page = result.and_then(unpack_kraken_result)?;
+            cursor = page.next_cursor;
+            Ok(Some(page.data))
+        };
+        PageIter::new(self, Box::new(fetch_page), |status| Decimal::from(status.time), false)
+    }
+
+    /// (Private) Iterate over the status of all deposits matching a request, following `next_cursor`.
+    ///
+    /// If the request has no cursor, pagination is enabled starting from the first page.
+    pub fn deposit_status_iter(&self, request: DepositStatusRequest) -> PageIter<'_, DepositStatus> {
+        let mut request = request;
+        let mut cursor = Some(request.cursor.take().unwrap_or_else(|| "true".to_owned()));
+        let fetch_page = move |api: &KrakenRestAPI, until| {
+            let Some(current) = cursor.take() else {
+                return Ok(None);
+            };
+            let result: Result<KrakenResult<CursorPage<DepositStatus>>> = api.client.query_private(
+                "DepositStatus",
+                DepositStatusRequest {
+                    cursor: Some(current),
+                    end: end_or_until(&request.end, until),
+                    ..request.clone()
+                },
+            );
+            let page = result.and_then(unpack_kraken_result)?;
+            cursor = page.next_cursor;
+            Ok(Some(page.data))
+        };
+        PageIter::new(self, Box::new(fetch_page), |status| Decimal::from(status.time), false)
+    }
+}
+
+// The end of a request: the end it already has, or else the `until` of the iterator
+pub(crate) fn end_or_until(end: &Option<String>, until: Option<Decimal>) -> Option<String> {
+    end.clone().or_else(|| until.map(|until| until.to_string()))
+}
+
+// The time used to order closed orders: close time if available, otherwise open time
+pub(crate) fn closed_order_time((_, order): &(TxId, OrderInfo)) -> Decimal {
+    order.closetm.unwrap_or(order.opentm)
+}
+
+/// The CursorPage struct is a helper to deal with the cursor-paginated
+/// DepositStatus and WithdrawStatus APIs.
+///
+/// Without a cursor these APIs return a plain json array. When a cursor is passed,
+/// they return a json object with two members:
+/// * one page of data at a key which depends on the API,
+/// * "next_cursor", which is passed as the cursor to get the next page, and is
+///   omitted or null when there are no more pages.
+///
+/// Like [crate::LastAndData], this accepts the data at any key.
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct CursorPage<T> {
+    /// Used to find the next page of data
+    pub next_cursor: Option<String>,
+    /// Data associated to this response
+    pub data: Vec<T>,
+}
+
+impl<'de, T> Deserialize<'de> for CursorPage<T>
+where
+    T: Deserialize<'de>,
+{
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> core::result::Result<Self, D::Error> {
+        deserializer.deserialize_any(CursorPageVisitor { _data: PhantomData })
+    }
+}
+
+struct CursorPageVisitor<T> {
+    _data: PhantomData<T>,
+}
+
+impl<'de, T> Visitor<'de> for CursorPageVisitor<T>
+where
+    T: Deserialize<'de>,
+{
+    type Value = CursorPage<T>;
+
+    fn expecting(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(
+            formatter,
+            "an array, or a map containing a 'next_cursor' and an array of data"
+        )
+    }
+
+    fn visit_seq<S: SeqAccess<'de>>(self, mut seq: S) -> core::result::Result<Self::Value, S::Error> {
+        let mut data = Vec::new();
+        while let Some(item) = seq.next_element()? {
+            data.push(item);
+        }
+        Ok(CursorPage {
+            next_cursor: None,
+            data,
+        })
+    }
+
+    fn visit_map<M: MapAccess<'de>>(self, mut map: M) -> core::result::Result<Self::Value, M::Error> {
+        let mut next_cursor = None;
+        let mut data = None;
+        while let Some(key) = map.next_key::<String>()? {
+            if key == "next_cursor" {
+                next_cursor = map.next_value::<Option<String>>()?;
+            } else if data.is_none() {
+                data = Some(map.next_value()?);
+            } else {
+                return Err(M::Error::custom("Expected map with two keys: next_cursor, and data"));
+            }
+        }
+        Ok(CursorPage {
+            next_cursor: next_cursor.filter(|cursor| !cursor.is_empty()),
+            data: data.ok_or(M::Error::custom("Missing data"))?,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cursor_page_array() {
+        let text = r#"[1, 2, 3]"#;
+
+        let obj: CursorPage<u64> = serde_json::from_str(text).unwrap();
+
+        assert_eq!(obj.data, vec![1, 2, 3]);
+        assert_eq!(obj.next_cursor, None);
+    }
+
+    #[test]
+    fn test_cursor_page_map() {
+        let text = r#"{"withdrawals":[1, 2, 3],"next_cursor":"MTc0NjQ1NDQ1Nw"}"#;
+
+        let obj: CursorPage<u64> = serde_json::from_str(text).unwrap();
+
+        assert_eq!(obj.data, vec![1, 2, 3]);
+        assert_eq!(obj.next_cursor.as_deref(), Some("MTc0NjQ1NDQ1Nw"));
+    }
+
+    #[test]
+    fn test_cursor_page_map_last_page() {
+        let text = r#"{"next_cursor":null,"deposit":[4]}"#;
+
+        let obj: CursorPage<u64> = serde_json::from_str(text).unwrap();
+
+        assert_eq!(obj.data, vec![4]);
+        assert_eq!(obj.next_cursor, None);
+    }
+}
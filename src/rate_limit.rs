@@ -0,0 +1,772 @@
+//! A client-side rate limiter, which models Kraken's rate limiting counters
+//! so that we can avoid `EAPI:Rate limit exceeded` and `EOrder:Rate limit exceeded` errors.
+//! <https://docs.kraken.com/api/docs/guides/spot-rest-ratelimits>
+//!
+//! Kraken uses a "decaying counter" scheme:
+//! * Every private REST call increases a per-account counter (most by 1, ledger and trade history calls by 2).
+//!   The counter decays at a rate which depends on the verification tier. If the counter would exceed
+//!   the maximum for the tier, the call is rejected.
+//! * Order placement and cancellation don't affect that counter. Instead, the matching engine keeps a
+//!   separate counter per asset pair. Placing an order increases it by 1, and canceling or editing an order
+//!   increases it by a penalty which is larger the more recently the order was placed.
+//!   Amending an order in place incurs a smaller penalty, which also depends on the age of the order.
+
+use crate::{Error, Result, TxId};
+use std::{
+    collections::{HashMap, HashSet},
+    sync::{Arc, Mutex},
+    thread,
+    time::{Duration, Instant},
+};
+
+/// The verification tier of a Kraken account, which determines its rate limits
+#[derive(Debug, Default, Copy, Clone, Eq, PartialEq)]
+pub enum VerificationTier {
+    /// Starter
+    #[default]
+    Starter,
+    /// Intermediate
+    Intermediate,
+    /// Pro
+    Pro,
+}
+
+impl VerificationTier {
+    /// The maximum value of the REST API call counter
+    pub fn max_counter(&self) -> f64 {
+        match self {
+            Self::Starter => 15.0,
+            Self::Intermediate => 20.0,
+            Self::Pro => 20.0,
+        }
+    }
+
+    /// How much the REST API call counter decays per second
+    pub fn decay_per_sec(&self) -> f64 {
+        match self {
+            Self::Starter => 0.33,
+            Self::Intermediate => 0.5,
+            Self::Pro => 1.0,
+        }
+    }
+
+    /// The maximum value of the matching engine counter (per asset pair)
+    pub fn max_order_counter(&self) -> f64 {
+        match self {
+            Self::Starter => 60.0,
+            Self::Intermediate => 125.0,
+            Self::Pro => 180.0,
+        }
+    }
+
+    /// How much the matching engine counter (per asset pair) decays per second
+    pub fn order_decay_per_sec(&self) -> f64 {
+        match self {
+            Self::Starter => 1.0,
+            Self::Intermediate => 2.34,
+            Self::Pro => 3.75,
+        }
+    }
+}
+
+/// What the rate limiter does when a call is not allowed yet
+#[derive(Debug, Default, Copy, Clone, Eq, PartialEq)]
+pub enum RateLimitMode {
+    /// Block the calling thread until the call is allowed
+    #[default]
+    Block,
+    /// Return `Error::RateLimited` immediately
+    Error,
+}
+
+/// Configuration for the client-side rate limiter
+#[derive(Debug, Default, Copy, Clone, Eq, PartialEq)]
+pub struct RateLimitConfig {
+    /// The verification tier of the account
+    pub tier: VerificationTier,
+    /// What to do when a call is not allowed yet
+    pub mode: RateLimitMode,
+}
+
+/// A source of time for the rate limiter. This is a trait so that the
+/// rate limiter can be tested without waiting in real time.
+pub trait Clock: Send + Sync {
+    /// The current time
+    fn now(&self) -> Instant;
+    /// Block the current thread for a duration
+    fn sleep(&self, duration: Duration);
+}
+
+/// The system clock
+#[derive(Debug, Default, Copy, Clone)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> Instant {
+        Instant::now()
+    }
+    fn sleep(&self, duration: Duration) {
+        thread::sleep(duration)
+    }
+}
+
+/// The REST API counter cost of a private method
+pub fn method_cost(method: &str) -> f64 {
+    match method {
+        // Ledger and trade history calls
+        "Ledgers" | "QueryLedgers" | "TradesHistory" | "QueryTrades" => 2.0,
+        // Trading calls are limited by the matching engine instead
+        "AddOrder"
+        | "AddOrderBatch"
+        | "EditOrder"
+        | "AmendOrder"
+        | "CancelOrder"
+        | "CancelOrderBatch"
+        | "CancelAll"
+        | "CancelAllOrdersAfter" => 0.0,
+        _ => 1.0,
+    }
+}
+
+/// The matching engine counter penalty for canceling an order, given its age
+pub fn cancel_penalty(age: Duration) -> f64 {
+    match age.as_secs() {
+        0..5 => 8.0,
+        5..10 => 6.0,
+        10..15 => 5.0,
+        15..45 => 4.0,
+        45..90 => 2.0,
+        90..300 => 1.0,
+        _ => 0.0,
+    }
+}
+
+/// The matching engine counter penalty for editing an order, given its age
+pub fn edit_penalty(age: Duration) -> f64 {
+    match age.as_secs() {
+        0..5 => 6.0,
+        5..10 => 5.0,
+        10..15 => 4.0,
+        15..45 => 2.0,
+        45..90 => 1.0,
+        _ => 0.0,
+    }
+}
+
+/// The matching engine counter penalty for amending an order, given its age
+pub fn amend_penalty(age: Duration) -> f64 {
+    match age.as_secs() {
+        0..5 => 3.0,
+        5..10 => 2.0,
+        10..45 => 1.0,
+        _ => 0.0,
+    }
+}
+
+/// A client-side model of Kraken's rate limit counters.
+///
+/// This is shared by all calls made through a client, and is thread-safe.
+pub struct RateLimiter {
+    config: RateLimitConfig,
+    clock: Arc<dyn Clock>,
+    state: Mutex<RateLimitState>,
+}
+
+#[derive(Default)]
+struct RateLimitState {
+    /// The REST API call counter
+    counter: DecayingCounter,
+    /// The matching engine counters, per asset pair
+    pair_counters: HashMap<String, DecayingCounter>,
+    /// Orders we placed which may still be open: txid -> (asset pair, time placed)
+    orders: HashMap<TxId, (String, Instant)>,
+    /// Client order ids of those orders: cl_ord_id -> txid
+    cl_ord_ids: HashMap<String, TxId>,
+}
+
+impl RateLimitState {
+    /// Find the txid of an order we placed, given its txid or client order id
+    fn resolve(&self, id: &str) -> Option<&TxId> {
+        match self.orders.get_key_value(id) {
+            Some((txid, _)) => Some(txid),
+            None => self.cl_ord_ids.get(id),
+        }
+    }
+}
+
+/// A counter which decays linearly over time, down to zero
+#[derive(Default, Clone, Debug)]
+struct DecayingCounter {
+    value: f64,
+    last_update: Option<Instant>,
+}
+
+impl DecayingCounter {
+    /// Decay the counter up to now
+    fn decay(&mut self, now: Instant, decay_per_sec: f64) {
+        if let Some(last_update) = self.last_update {
+            let elapsed = now.saturating_duration_since(last_update).as_secs_f64();
+            self.value = (self.value - elapsed * decay_per_sec).max(0.0);
+        }
+        self.last_update = Some(now);
+    }
+
+    /// Check if cost could be added to the counter. Returns how long to wait if this would exceed the max.
+    fn check(&mut self, now: Instant, cost: f64, max: f64, decay_per_sec: f64) -> Option<Duration> {
+        self.decay(now, decay_per_sec);
+        let excess = self.value + cost - max;
+        // Tolerate floating point error, so that waiting for the returned duration is always enough
+        (excess > 1e-6 && self.value > 0.0).then(|| Duration::from_secs_f64(excess.min(self.value) / decay_per_sec))
+    }
+
+    /// Try to add cost to the counter. Returns how long to wait if this would exceed the max.
+    fn try_add(&mut self, now: Instant, cost: f64, max: f64, decay_per_sec: f64) -> Option<Duration> {
+        let wait = self.check(now, cost, max, decay_per_sec);
+        if wait.is_none() {
+            self.value += cost;
+        }
+        wait
+    }
+}
+
+impl RateLimiter {
+    /// Create a new rate limiter using the system clock
+    pub fn new(config: RateLimitConfig) -> Self {
+        Self::with_clock(config, Arc::new(SystemClock))
+    }
+
+    /// Create a new rate limiter using a particular clock
+    pub fn with_clock(config: RateLimitConfig, clock: Arc<dyn Clock>) -> Self {
+        Self {
+            config,
+            clock,
+            state: Default::default(),
+        }
+    }
+
+    /// Get the config of this rate limiter
+    pub fn config(&self) -> &RateLimitConfig {
+        &self.config
+    }
+
+    /// Get the current value of the REST API call counter
+    pub fn counter(&self) -> f64 {
+        let mut state = self.state.lock().expect("mutex poisoned");
+        state.counter.decay(self.clock.now(), self.config.tier.decay_per_sec());
+        state.counter.value
+    }
+
+    /// Get the current value of the matching engine counter for an asset pair
+    pub fn order_counter(&self, pair: &str) -> f64 {
+        let mut state = self.state.lock().expect("mutex poisoned");
+        let now = self.clock.now();
+        let decay_per_sec = self.config.tier.order_decay_per_sec();
+        state
+            .pair_counters
+            .get_mut(pair)
+            .map(|counter| {
+                counter.decay(now, decay_per_sec);
+                counter.value
+            })
+            .unwrap_or(0.0)
+    }
+
+    /// Try to make a private REST API call. Returns how long to wait if it is not allowed yet.
+    pub fn try_acquire(&self, method: &str) -> Option<Duration> {
+        let cost = method_cost(method);
+        if cost == 0.0 {
+            return None;
+        }
+        let mut state = self.state.lock().expect("mutex poisoned");
+        let tier = self.config.tier;
+        state
+            .counter
+            .try_add(self.clock.now(), cost, tier.max_counter(), tier.decay_per_sec())
+    }
+
+    /// Try to place an order in an asset pair. Returns how long to wait if it is not allowed yet.
+    pub fn try_acquire_order(&self, pair: &str) -> Option<Duration> {
+        self.try_add_order_cost(pair, 1.0)
+    }
+
+    /// Try to place a batch of orders in an asset pair. Returns how long to wait if it is not allowed yet.
+    ///
+    /// Each order in the batch counts as if it were placed separately.
+    pub fn try_acquire_order_batch(&self, pair: &str, count: usize) -> Option<Duration> {
+        self.try_add_order_cost(pair, count as f64)
+    }
+
+    /// Try to cancel an order. Returns how long to wait if it is not allowed yet.
+    ///
+    /// The penalty depends on the age of the order, if it was placed through this rate limiter.
+    /// Otherwise the order is assumed to be old enough to have no penalty.
+    pub fn try_acquire_cancel(&self, txid: &str) -> Option<Duration> {
+        self.try_add_order_penalty(txid, cancel_penalty)
+    }
+
+    /// Try to cancel a batch of orders. Returns how long to wait if it is not allowed yet.
+    ///
+    /// The penalties of all the orders are added up (per asset pair), and either all of them are added
+    /// to the counters, or none are.
+    pub fn try_acquire_cancel_batch(&self, ids: &[String]) -> Option<Duration> {
+        let mut state = self.state.lock().expect("mutex poisoned");
+        let now = self.clock.now();
+        // An order may appear under both its txid and its client order id, but is only canceled once
+        let txids: HashSet<&TxId> = ids.iter().filter_map(|id| state.resolve(id)).collect();
+        let mut penalties = HashMap::<String, f64>::new();
+        for txid in txids {
+            let (pair, placed) = &state.orders[txid];
+            *penalties.entry(pair.clone()).or_default() += cancel_penalty(now.saturating_duration_since(*placed));
+        }
+        let tier = self.config.tier;
+        let (max, decay_per_sec) = (tier.max_order_counter(), tier.order_decay_per_sec());
+        let wait = penalties
+            .iter()
+            .filter_map(|(pair, penalty)| {
+                let counter = state.pair_counters.entry(pair.clone()).or_default();
+                counter.check(now, *penalty, max, decay_per_sec)
+            })
+            .max();
+        if wait.is_none() {
+            for (pair, penalty) in penalties {
+                state.pair_counters.entry(pair).or_default().value += penalty;
+            }
+        }
+        wait
+    }
+
+    /// Try to edit an order. Returns how long to wait if it is not allowed yet.
+    ///
+    /// The penalty depends on the age of the order, as for canceling.
+    pub fn try_acquire_edit(&self, txid: &str) -> Option<Duration> {
+        self.try_add_order_penalty(txid, edit_penalty)
+    }
+
+    /// Try to amend an order, given its txid or client order id. Returns how long to wait if it is not allowed yet.
+    ///
+    /// The penalty depends on the age of the order, as for canceling.
+    pub fn try_acquire_amend(&self, id: &str) -> Option<Duration> {
+        self.try_add_order_penalty(id, amend_penalty)
+    }
+
+    /// Record that an order was placed, so that the penalty for canceling it can be computed.
+    /// If the order has a client order id, it may then be canceled by either id.
+    pub fn record_order(&self, txid: TxId, cl_ord_id: Option<String>, pair: String) {
+        let mut state = self.state.lock().expect("mutex poisoned");
+        let now = self.clock.now();
+        // Forget about orders which are too old to incur any cancel penalty
+        state
+            .orders
+            .retain(|_, (_, placed)| cancel_penalty(now.saturating_duration_since(*placed)) > 0.0);
+        let RateLimitState { orders, cl_ord_ids, .. } = &mut *state;
+        cl_ord_ids.retain(|_, txid| orders.contains_key(txid));
+        if let Some(cl_ord_id) = cl_ord_id {
+            state.cl_ord_ids.insert(cl_ord_id, txid.clone());
+        }
+        state.orders.insert(txid, (pair, now));
+    }
+
+    /// Forget about an order (e.g. because it was canceled), given its txid or client order id
+    pub fn forget_order(&self, id: &str) {
+        let mut state = self.state.lock().expect("mutex poisoned");
+        if let Some(txid) = state.resolve(id).cloned() {
+            state.orders.remove(&txid);
+            state.cl_ord_ids.retain(|_, other| *other != txid);
+        }
+    }
+
+    /// Make a private REST API call, blocking or returning an error if it isn't allowed yet,
+    /// according to the mode.
+    pub fn acquire(&self, method: &str) -> Result<()> {
+        self.wait_for(|| self.try_acquire(method))
+    }
+
+    /// Place an order in an asset pair, blocking or returning an error if it isn't allowed yet,
+    /// according to the mode.
+    pub fn acquire_order(&self, pair: &str) -> Result<()> {
+        self.wait_for(|| self.try_acquire_order(pair))
+    }
+
+    /// Place a batch of orders in an asset pair, blocking or returning an error if it isn't allowed yet,
+    /// according to the mode.
+    pub fn acquire_order_batch(&self, pair: &str, count: usize) -> Result<()> {
+        self.wait_for(|| self.try_acquire_order_batch(pair, count))
+    }
+
+    /// Cancel an order, blocking or returning an error if it isn't allowed yet,
+    /// according to the mode.
+    ///
+    /// The order should be forgotten with `forget_order` once it has been canceled.
+    pub fn acquire_cancel(&self, txid: &str) -> Result<()> {
+        self.wait_for(|| self.try_acquire_cancel(txid))
+    }
+
+    /// Cancel a batch of orders, blocking or returning an error if it isn't allowed yet,
+    /// according to the mode.
+    ///
+    /// The orders should be forgotten with `forget_order` once they have been canceled.
+    pub fn acquire_cancel_batch(&self, ids: &[String]) -> Result<()> {
+        self.wait_for(|| self.try_acquire_cancel_batch(ids))
+    }
+
+    /// Edit an order, blocking or returning an error if it isn't allowed yet,
+    /// according to the mode.
+    ///
+    /// Editing an order replaces it with a new one. Once the edit succeeds, the original order should be
+    /// forgotten with `forget_order`, and the new one recorded with `record_order`.
+    pub fn acquire_edit(&self, txid: &str) -> Result<()> {
+        self.wait_for(|| self.try_acquire_edit(txid))
+    }
+
+    /// Amend an order, blocking or returning an error if it isn't allowed yet,
+    /// according to the mode.
+    ///
+    /// Amending keeps the order (and its txid), so it is not forgotten.
+    pub fn acquire_amend(&self, id: &str) -> Result<()> {
+        self.wait_for(|| self.try_acquire_amend(id))
+    }
+
+    /// Make a private REST API call, waiting asynchronously or returning an error if it isn't allowed yet,
+    /// according to the mode.
+    #[cfg(feature = "async")]
+    pub async fn acquire_async(&self, method: &str) -> Result<()> {
+        self.wait_for_async(|| self.try_acquire(method)).await
+    }
+
+    /// Place an order in an asset pair, waiting asynchronously or returning an error if it isn't allowed yet,
+    /// according to the mode.
+    #[cfg(feature = "async")]
+    pub async fn acquire_order_async(&self, pair: &str) -> Result<()> {
+        self.wait_for_async(|| self.try_acquire_order(pair)).await
+    }
+
+    /// Place a batch of orders in an asset pair, waiting asynchronously or returning an error if it isn't allowed yet,
+    /// according to the mode.
+    #[cfg(feature = "async")]
+    pub async fn acquire_order_batch_async(&self, pair: &str, count: usize) -> Result<()> {
+        self.wait_for_async(|| self.try_acquire_order_batch(pair, count)).await
+    }
+
+    /// Cancel an order, waiting asynchronously or returning an error if it isn't allowed yet,
+    /// according to the mode.
+    #[cfg(feature = "async")]
+    pub async fn acquire_cancel_async(&self, txid: &str) -> Result<()> {
+        self.wait_for_async(|| self.try_acquire_cancel(txid)).await
+    }
+
+    /// Cancel a batch of orders, waiting asynchronously or returning an error if it isn't allowed yet,
+    /// according to the mode.
+    #[cfg(feature = "async")]
+    pub async fn acquire_cancel_batch_async(&self, ids: &[String]) -> Result<()> {
+        self.wait_for_async(|| self.try_acquire_cancel_batch(ids)).await
+    }
+
+    /// Edit an order, waiting asynchronously or returning an error if it isn't allowed yet,
+    /// according to the mode.
+    #[cfg(feature = "async")]
+    pub async fn acquire_edit_async(&self, txid: &str) -> Result<()> {
+        self.wait_for_async(|| self.try_acquire_edit(txid)).await
+    }
+
+    /// Amend an order, waiting asynchronously or returning an error if it isn't allowed yet,
+    /// according to the mode.
+    #[cfg(feature = "async")]
+    pub async fn acquire_amend_async(&self, id: &str) -> Result<()> {
+        self.wait_for_async(|| self.try_acquire_amend(id)).await
+    }
+
+    // Look up the order and add its penalty under a single lock, so that concurrent cancels both count
+    fn try_add_order_penalty(&self, id: &str, penalty: fn(Duration) -> f64) -> Option<Duration> {
+        let mut state = self.state.lock().expect("mutex poisoned");
+        let now = self.clock.now();
+        let txid = state.resolve(id)?;
+        let (pair, placed) = state.orders[txid].clone();
+        let penalty = penalty(now.saturating_duration_since(placed));
+        if penalty == 0.0 {
+            return None;
+        }
+        self.add_order_cost(&mut state, now, &pair, penalty)
+    }
+
+    fn try_add_order_cost(&self, pair: &str, cost: f64) -> Option<Duration> {
+        let mut state = self.state.lock().expect("mutex poisoned");
+        self.add_order_cost(&mut state, self.clock.now(), pair, cost)
+    }
+
+    fn add_order_cost(&self, state: &mut RateLimitState, now: Instant, pair: &str, cost: f64) -> Option<Duration> {
+        let tier = self.config.tier;
+        state.pair_counters.entry(pair.to_owned()).or_default().try_add(
+            now,
+            cost,
+            tier.max_order_counter(),
+            tier.order_decay_per_sec(),
+        )
+    }
+
+    fn wait_for(&self, mut try_acquire: impl FnMut() -> Option<Duration>) -> Result<()> {
+        while let Some(retry_after) = try_acquire() {
+            match self.config.mode {
+                RateLimitMode::Block => self.clock.sleep(retry_after),
+                RateLimitMode::Error => return Err(Error::RateLimited { retry_after }),
+            }
+        }
+        Ok(())
+    }
+
+    // Like wait_for, but sleeps on the tokio timer rather than the clock, so as not to block the executor
+    #[cfg(feature = "async")]
+    async fn wait_for_async(&self, mut try_acquire: impl FnMut() -> Option<Duration>) -> Result<()> {
+        while let Some(retry_after) = try_acquire() {
+            match self.config.mode {
+                RateLimitMode::Block => tokio::time::sleep(retry_after).await,
+                RateLimitMode::Error => return Err(Error::RateLimited { retry_after }),
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // A clock which only advances when slept on
+    struct FakeClock {
+        now: Mutex<Instant>,
+    }
+
+    impl FakeClock {
+        fn new() -> Arc<Self> {
+            Arc::new(Self {
+                now: Mutex::new(Instant::now()),
+            })
+        }
+
+        fn advance(&self, duration: Duration) {
+            *self.now.lock().unwrap() += duration;
+        }
+    }
+
+    impl Clock for FakeClock {
+        fn now(&self) -> Instant {
+            *self.now.lock().unwrap()
+        }
+        fn sleep(&self, duration: Duration) {
+            self.advance(duration);
+        }
+    }
+
+    fn limiter(tier: VerificationTier, mode: RateLimitMode, clock: &Arc<FakeClock>) -> RateLimiter {
+        RateLimiter::with_clock(RateLimitConfig { tier, mode }, clock.clone())
+    }
+
+    #[test]
+    fn test_counter_max() {
+        let clock = FakeClock::new();
+        let limiter = limiter(VerificationTier::Starter, RateLimitMode::Error, &clock);
+
+        for _ in 0..15 {
+            limiter.acquire("Balance").unwrap();
+        }
+        assert_eq!(limiter.counter(), 15.0);
+
+        let Err(Error::RateLimited { retry_after }) = limiter.acquire("Balance") else {
+            panic!("expected rate limited error");
+        };
+        // One call needs one unit of decay, which is 1 / 0.33 seconds
+        assert_eq!(retry_after, Duration::from_secs_f64(1.0 / 0.33));
+
+        clock.advance(retry_after);
+        limiter.acquire("Balance").unwrap();
+    }
+
+    #[test]
+    fn test_counter_decay() {
+        let clock = FakeClock::new();
+        let limiter = limiter(VerificationTier::Pro, RateLimitMode::Error, &clock);
+
+        for _ in 0..10 {
+            limiter.acquire("TradesHistory").unwrap();
+        }
+        assert_eq!(limiter.counter(), 20.0);
+        assert!(limiter.acquire("OpenOrders").is_err());
+
+        clock.advance(Duration::from_secs(5));
+        assert_eq!(limiter.counter(), 15.0);
+
+        clock.advance(Duration::from_secs(100));
+        assert_eq!(limiter.counter(), 0.0);
+    }
+
+    #[test]
+    fn test_block_mode_sleeps() {
+        let clock = FakeClock::new();
+        let start = clock.now();
+        let limiter = limiter(VerificationTier::Intermediate, RateLimitMode::Block, &clock);
+
+        for _ in 0..10 {
+            limiter.acquire("Ledgers").unwrap();
+        }
+        assert_eq!(clock.now(), start);
+
+        // This blocks until 2 units have decayed
+        limiter.acquire("Ledgers").unwrap();
+        assert_eq!(clock.now(), start + Duration::from_secs(4));
+    }
+
+    #[test]
+    fn test_trading_calls_do_not_affect_counter() {
+        let clock = FakeClock::new();
+        let limiter = limiter(VerificationTier::Starter, RateLimitMode::Error, &clock);
+
+        for _ in 0..100 {
+            limiter.acquire("AddOrder").unwrap();
+            limiter.acquire("CancelOrder").unwrap();
+        }
+        assert_eq!(limiter.counter(), 0.0);
+    }
+
+    #[test]
+    fn test_order_limits_per_pair() {
+        let clock = FakeClock::new();
+        let limiter = limiter(VerificationTier::Starter, RateLimitMode::Error, &clock);
+
+        for _ in 0..60 {
+            limiter.acquire_order("XBTUSD").unwrap();
+        }
+        assert!(limiter.acquire_order("XBTUSD").is_err());
+        // Other pairs have separate counters
+        limiter.acquire_order("ETHUSD").unwrap();
+
+        clock.advance(Duration::from_secs(1));
+        limiter.acquire_order("XBTUSD").unwrap();
+        assert_eq!(limiter.order_counter("XBTUSD"), 60.0);
+        assert_eq!(limiter.order_counter("ETHUSD"), 0.0);
+    }
+
+    #[test]
+    fn test_cancel_penalty() {
+        let clock = FakeClock::new();
+        let limiter = limiter(VerificationTier::Pro, RateLimitMode::Error, &clock);
+
+        limiter.acquire_order("XBTUSD").unwrap();
+        limiter.record_order("O1".into(), None, "XBTUSD".into());
+        limiter.acquire_order("XBTUSD").unwrap();
+        limiter.record_order("O2".into(), None, "XBTUSD".into());
+        assert_eq!(limiter.order_counter("XBTUSD"), 2.0);
+
+        // Cancel the first order almost immediately, this costs 8
+        limiter.acquire_cancel("O1").unwrap();
+        limiter.forget_order("O1");
+        assert_eq!(limiter.order_counter("XBTUSD"), 10.0);
+
+        // Cancel the second order after 20 seconds, this costs 4
+        clock.advance(Duration::from_secs(20));
+        limiter.acquire_cancel("O2").unwrap();
+        assert_eq!(limiter.order_counter("XBTUSD"), 4.0);
+
+        // Orders we don't know about don't incur a penalty
+        limiter.acquire_cancel("O3").unwrap();
+        assert_eq!(limiter.order_counter("XBTUSD"), 4.0);
+    }
+
+    #[test]
+    fn test_cancel_by_client_order_id() {
+        let clock = FakeClock::new();
+        let limiter = limiter(VerificationTier::Pro, RateLimitMode::Error, &clock);
+
+        limiter.record_order("O1".into(), Some("my-order-1".into()), "XBTUSD".into());
+        limiter.acquire_cancel("my-order-1").unwrap();
+        assert_eq!(limiter.order_counter("XBTUSD"), 8.0);
+        // Until the cancel succeeds and the order is forgotten, another attempt is charged again
+        limiter.acquire_cancel("O1").unwrap();
+        assert_eq!(limiter.order_counter("XBTUSD"), 16.0);
+        // The order is forgotten under both ids, so canceling it again has no penalty
+        limiter.forget_order("my-order-1");
+        limiter.acquire_cancel("O1").unwrap();
+        limiter.acquire_cancel("my-order-1").unwrap();
+        assert_eq!(limiter.order_counter("XBTUSD"), 16.0);
+    }
+
+    #[test]
+    fn test_cancel_batch() {
+        let clock = FakeClock::new();
+        let limiter = limiter(VerificationTier::Starter, RateLimitMode::Error, &clock);
+
+        for txid in ["O1", "O2", "O3"] {
+            limiter.acquire_order("XBTUSD").unwrap();
+            limiter.record_order(txid.into(), Some(format!("{txid}-client")), "XBTUSD".into());
+        }
+        for _ in 0..37 {
+            limiter.acquire_order("XBTUSD").unwrap();
+        }
+        assert_eq!(limiter.order_counter("XBTUSD"), 40.0);
+
+        // Together the penalties exceed the limit, so none of them are charged, and no orders are forgotten
+        let batch = vec!["O1".to_owned(), "O2".to_owned(), "O3".to_owned()];
+        assert!(matches!(
+            limiter.acquire_cancel_batch(&batch),
+            Err(Error::RateLimited { .. })
+        ));
+        assert_eq!(limiter.order_counter("XBTUSD"), 40.0);
+
+        // An order listed under both its ids is only charged once
+        clock.advance(Duration::from_secs(4));
+        let batch = vec![
+            "O1".to_owned(),
+            "O1-client".to_owned(),
+            "O2".to_owned(),
+            "O3".to_owned(),
+        ];
+        limiter.acquire_cancel_batch(&batch).unwrap();
+        assert_eq!(limiter.order_counter("XBTUSD"), 60.0);
+        for id in &batch {
+            limiter.forget_order(id);
+        }
+        limiter.acquire_cancel("O2-client").unwrap();
+        assert_eq!(limiter.order_counter("XBTUSD"), 60.0);
+    }
+
+    #[test]
+    fn test_edit_penalty() {
+        let clock = FakeClock::new();
+        let limiter = limiter(VerificationTier::Pro, RateLimitMode::Error, &clock);
+
+        limiter.record_order("O1".into(), None, "XBTUSD".into());
+        clock.advance(Duration::from_secs(7));
+        limiter.acquire_edit("O1").unwrap();
+        assert_eq!(limiter.order_counter("XBTUSD"), 5.0);
+        // Once the original order is forgotten, editing it again has no penalty
+        limiter.forget_order("O1");
+        limiter.acquire_edit("O1").unwrap();
+        assert_eq!(limiter.order_counter("XBTUSD"), 5.0);
+    }
+
+    #[test]
+    fn test_amend_penalty() {
+        let clock = FakeClock::new();
+        let limiter = limiter(VerificationTier::Pro, RateLimitMode::Error, &clock);
+
+        limiter.record_order("O1".into(), Some("my-order-1".into()), "XBTUSD".into());
+        limiter.acquire_amend("O1").unwrap();
+        assert_eq!(limiter.order_counter("XBTUSD"), 3.0);
+        // The order keeps its txid, so amending it again still has a penalty
+        clock.advance(Duration::from_secs(6));
+        limiter.acquire_amend("my-order-1").unwrap();
+        assert_eq!(limiter.order_counter("XBTUSD"), 2.0);
+        clock.advance(Duration::from_secs(60));
+        limiter.acquire_amend("O1").unwrap();
+        assert_eq!(limiter.order_counter("XBTUSD"), 0.0);
+    }
+
+    #[test]
+    fn test_cancel_penalty_table() {
+        assert_eq!(cancel_penalty(Duration::from_millis(100)), 8.0);
+        assert_eq!(cancel_penalty(Duration::from_secs(7)), 6.0);
+        assert_eq!(cancel_penalty(Duration::from_secs(12)), 5.0);
+        assert_eq!(cancel_penalty(Duration::from_secs(44)), 4.0);
+        assert_eq!(cancel_penalty(Duration::from_secs(60)), 2.0);
+        assert_eq!(cancel_penalty(Duration::from_secs(200)), 1.0);
+        assert_eq!(cancel_penalty(Duration::from_secs(300)), 0.0);
+    }
+}
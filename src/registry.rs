@@ -0,0 +1,261 @@
+//! A registry of Kraken's assets and asset pairs, which resolves the many spellings of their names.
+//!
+//! Kraken refers to the same pair as `XXBTZUSD` (its key in the AssetPairs API), `XBTUSD` (its altname),
+//! `XBT/USD` (its websockets name), and sometimes `BTC/USD`. Similarly, an asset may be `XXBT` (its key
+//! in the Assets API), `XBT` (its altname), or `BTC`. Different APIs use different forms, e.g. balances
+//! are keyed by asset key, while websockets feeds use the websockets name of the pair.
+//!
+//! A [PairRegistry] resolves any of these spellings to a canonical [AssetId] or [PairId], which can then be
+//! converted to the form that a particular API requires.
+
+use crate::{AssetInfo, AssetPair, AssetPairsResponse, AssetsResponse, BalanceResponse, KrakenRestAPI, Result};
+use rust_decimal::Decimal;
+use std::{collections::HashMap, fmt};
+
+/// Common names of assets, which Kraken knows under a different altname
+const ASSET_ALIASES: &[(&str, &str)] = &[("BTC", "XBT"), ("DOGE", "XDG")];
+
+/// The canonical id of an asset: its key in the Assets API, e.g. `XXBT`
+#[derive(Clone, Debug, Eq, PartialEq, Ord, PartialOrd, Hash)]
+pub struct AssetId(String);
+
+impl AssetId {
+    /// The asset key, as used in balances and ledgers
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+impl fmt::Display for AssetId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+/// The canonical id of an asset pair: its key in the AssetPairs API, e.g. `XXBTZUSD`
+#[derive(Clone, Debug, Eq, PartialEq, Ord, PartialOrd, Hash)]
+pub struct PairId(String);
+
+impl PairId {
+    /// The pair key, which is accepted by all REST APIs
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+impl fmt::Display for PairId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+/// Resolves the names of assets and asset pairs, built from the results of the Assets and AssetPairs APIs.
+///
+/// Names are resolved in this order:
+/// * An asset's key or altname, or a common name like `BTC`
+/// * A pair's key, altname or websockets name
+/// * A pair written as two asset names, with or without a `/` between them, e.g. `BTC/USD` or `XBTZUSD`
+#[derive(Clone, Debug, Default)]
+pub struct PairRegistry {
+    /// Info for each asset
+    assets: HashMap<AssetId, AssetInfo>,
+    /// Asset ids by key, altname and alias
+    asset_names: HashMap<String, AssetId>,
+    /// Info for each pair
+    pairs: HashMap<PairId, AssetPair>,
+    /// Pair ids by key, altname and websockets name
+    pair_names: HashMap<String, PairId>,
+    /// Pair ids by base and quote asset
+    pairs_by_assets: HashMap<(AssetId, AssetId), PairId>,
+}
+
+impl PairRegistry {
+    /// Create a registry from the results of the Assets and AssetPairs APIs
+    pub fn new(assets: AssetsResponse, asset_pairs: AssetPairsResponse) -> Self {
+        let mut result = Self::default();
+        for (key, info) in assets {
+            let id = AssetId(key.clone());
+            result.asset_names.insert(info.altname.clone(), id.clone());
+            result.asset_names.insert(key, id.clone());
+            result.assets.insert(id, info);
+        }
+        for (alias, altname) in ASSET_ALIASES {
+            if let Some(id) = result.asset_names.get(*altname).cloned() {
+                result.asset_names.entry(alias.to_string()).or_insert(id);
+            }
+        }
+        for (key, pair) in asset_pairs {
+            let id = PairId(key.clone());
+            for name in [pair.alt_name.as_ref(), pair.wsname.as_ref()].into_iter().flatten() {
+                result.pair_names.insert(name.clone(), id.clone());
+            }
+            result.pair_names.insert(key, id.clone());
+            if let (Some(base), Some(quote)) = (result.asset_id(&pair.base), result.asset_id(&pair.quote)) {
+                result.pairs_by_assets.insert((base, quote), id.clone());
+            }
+            result.pairs.insert(id, pair);
+        }
+        result
+    }
+
+    /// Fetch the assets and asset pairs from Kraken, and create a registry
+    pub fn fetch(api: &KrakenRestAPI) -> Result<Self> {
+        Ok(Self::new(api.assets()?, api.asset_pairs(vec![])?))
+    }
+
+    /// Fetch the assets and asset pairs from Kraken, and create a registry
+    #[cfg(feature = "async")]
+    pub async fn fetch_async(api: &crate::AsyncKrakenRestAPI) -> Result<Self> {
+        Ok(Self::new(api.assets().await?, api.asset_pairs(vec![]).await?))
+    }
+
+    /// Resolve any name of an asset to its id
+    pub fn asset_id(&self, name: &str) -> Option<AssetId> {
+        self.asset_names.get(name).cloned()
+    }
+
+    /// Resolve any name of an asset pair to its id
+    pub fn pair_id(&self, name: &str) -> Option<PairId> {
+        if let Some(id) = self.pair_names.get(name) {
+            return Some(id.clone());
+        }
+        if let Some((base, quote)) = name.split_once('/') {
+            return self.pair_of(base, quote);
+        }
+        (1..name.len())
+            .filter(|idx| name.is_char_boundary(*idx))
+            .find_map(|idx| self.pair_of(&name[..idx], &name[idx..]))
+    }
+
+    /// Get the info for an asset
+    pub fn asset(&self, id: &AssetId) -> Option<&AssetInfo> {
+        self.assets.get(id)
+    }
+
+    /// Get the info for an asset pair
+    pub fn pair(&self, id: &PairId) -> Option<&AssetPair> {
+        self.pairs.get(id)
+    }
+
+    /// Get the altname of an asset, e.g. `XBT`
+    pub fn asset_altname(&self, id: &AssetId) -> Option<&str> {
+        self.asset(id).map(|info| info.altname.as_str())
+    }
+
+    /// Get the altname of an asset pair, e.g. `XBTUSD`
+    pub fn pair_altname(&self, id: &PairId) -> Option<&str> {
+        self.pair(id).and_then(|pair| pair.alt_name.as_deref())
+    }
+
+    /// Get the websockets name of an asset pair, e.g. `XBT/USD`, as used by websockets feeds and orders.
+    /// This is None if the pair is not available over websockets.
+    pub fn ws_name(&self, id: &PairId) -> Option<&str> {
+        self.pair(id).and_then(|pair| pair.wsname.as_deref())
+    }
+
+    /// Get the base and quote assets of an asset pair
+    pub fn pair_assets(&self, id: &PairId) -> Option<(AssetId, AssetId)> {
+        let pair = self.pair(id)?;
+        Some((self.asset_id(&pair.base)?, self.asset_id(&pair.quote)?))
+    }
+
+    /// Key balances by asset id. Balances of unknown assets are logged and dropped.
+    pub fn balances(&self, balances: BalanceResponse) -> HashMap<AssetId, Decimal> {
+        balances
+            .into_iter()
+            .filter_map(|(name, amount)| match self.asset_id(&name) {
+                Some(id) => Some((id, amount)),
+                None => {
+                    log::warn!("balance of unknown asset {}", name);
+                    None
+                }
+            })
+            .collect()
+    }
+
+    // Find the pair with a given base and quote asset
+    fn pair_of(&self, base: &str, quote: &str) -> Option<PairId> {
+        let key = (self.asset_id(base)?, self.asset_id(quote)?);
+        self.pairs_by_assets.get(&key).cloned()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn registry() -> PairRegistry {
+        let asset = |altname: &str| AssetInfo {
+            altname: altname.into(),
+            aclass: "currency".into(),
+            decimals: 8,
+            display_decimals: 5,
+        };
+        let pair = |altname: &str, wsname: &str, base: &str, quote: &str| AssetPair {
+            alt_name: Some(altname.into()),
+            wsname: Some(wsname.into()),
+            base: base.into(),
+            quote: quote.into(),
+            ..Default::default()
+        };
+        let assets = [
+            ("XXBT", asset("XBT")),
+            ("ZUSD", asset("USD")),
+            ("XETH", asset("ETH")),
+            ("XXDG", asset("XDG")),
+            ("XBT.M", asset("XBT.M")),
+        ];
+        let pairs = [
+            ("XXBTZUSD", pair("XBTUSD", "XBT/USD", "XXBT", "ZUSD")),
+            ("XETHXXBT", pair("ETHXBT", "ETH/XBT", "XETH", "XXBT")),
+            ("XDGUSD", pair("XDGUSD", "XDG/USD", "XXDG", "ZUSD")),
+        ];
+        PairRegistry::new(
+            assets.into_iter().map(|(key, info)| (key.to_owned(), info)).collect(),
+            pairs.into_iter().map(|(key, pair)| (key.to_owned(), pair)).collect(),
+        )
+    }
+
+    #[test]
+    fn test_resolve_names() {
+        let registry = registry();
+        let btc = registry.asset_id("XXBT").unwrap();
+        assert_eq!(btc.as_str(), "XXBT");
+        assert_eq!(registry.asset_id("XBT"), Some(btc.clone()));
+        assert_eq!(registry.asset_id("BTC"), Some(btc.clone()));
+        assert_eq!(registry.asset_altname(&btc), Some("XBT"));
+        assert_eq!(registry.asset_id("XBT.M").unwrap().as_str(), "XBT.M");
+        assert_eq!(registry.asset_id("LTC"), None);
+
+        let btc_usd = registry.pair_id("XXBTZUSD").unwrap();
+        for name in ["XBTUSD", "XBT/USD", "BTC/USD", "BTCUSD", "XXBT/ZUSD"] {
+            assert_eq!(registry.pair_id(name), Some(btc_usd.clone()), "{}", name);
+        }
+        assert_eq!(registry.pair_altname(&btc_usd), Some("XBTUSD"));
+        assert_eq!(registry.ws_name(&btc_usd), Some("XBT/USD"));
+        assert_eq!(
+            registry.pair_assets(&btc_usd),
+            Some((btc, registry.asset_id("USD").unwrap()))
+        );
+
+        let doge_usd = registry.pair_id("DOGE/USD").unwrap();
+        assert_eq!(doge_usd.as_str(), "XDGUSD");
+        assert_eq!(registry.pair_id("BTC/ETH"), None);
+        assert_eq!(registry.pair_id("ETHBTC").unwrap().as_str(), "XETHXXBT");
+    }
+
+    #[test]
+    fn test_balances() {
+        let registry = registry();
+        let balances: BalanceResponse = [
+            ("XXBT".to_owned(), Decimal::new(15, 1)),
+            ("ZUSD".to_owned(), Decimal::new(100, 0)),
+            ("NOPE".to_owned(), Decimal::ONE),
+        ]
+        .into_iter()
+        .collect();
+        let balances = registry.balances(balances);
+        assert_eq!(balances.len(), 2);
+        assert_eq!(balances[&registry.asset_id("BTC").unwrap()], Decimal::new(15, 1));
+    }
+}
@@ -0,0 +1,729 @@
+//! The methods of the REST API, which are shared by [crate::KrakenRestAPI] and the async `AsyncKrakenRestAPI`.
+//!
+//! The methods are written once, in a macro which is expanded into both impls. They build the same requests
+//! and do the same bookkeeping (validation, rate limiting), and only differ in whether they `.await`.
+
+// Expand the REST API methods, for a type with a `client` field which is a `KrakenRestClient` (blocking),
+// or an `AsyncKrakenRestClient` (async). The types used by the methods must be imported where this is expanded.
+macro_rules! rest_api_methods {
+    (blocking) => {
+        rest_api_methods!(@methods blocking [] []);
+    };
+    (async) => {
+        rest_api_methods!(@methods async [async] [.await]);
+    };
+    (@methods $mode:tt [$($async:tt)?] [$($await:tt)*]) => {
+        /// (Public) Get the kraken system's time
+        pub $($async)? fn time(&self) -> Result<TimeResponse> {
+            let result: Result<KrakenResult<TimeResponse>> = self.client.query_public("Time", Empty {})$($await)*;
+            result.and_then(unpack_kraken_result)
+        }
+
+        /// (Public) Get the kraken system's status
+        pub $($async)? fn system_status(&self) -> Result<SystemStatusResponse> {
+            let result: Result<KrakenResult<SystemStatusResponse>> =
+                self.client.query_public("SystemStatus", Empty {})$($await)*;
+            result.and_then(unpack_kraken_result)
+        }
+
+        /// (Public) Get the list of kraken's supported assets, and info
+        pub $($async)? fn assets(&self) -> Result<AssetsResponse> {
+            let result: Result<KrakenResult<AssetsResponse>> = self.client.query_public("Assets", Empty {})$($await)*;
+            result.and_then(unpack_kraken_result)
+        }
+
+        /// (Public) Get the list of kraken's asset pairs, and info
+        ///
+        /// Arguments:
+        /// * pairs: A list of Kraken asset pair strings to get info about. If empty then all asset pairs
+        pub $($async)? fn asset_pairs(&self, pairs: Vec<String>) -> Result<AssetPairsResponse> {
+            self.asset_pairs_with_info(pairs, AssetPairInfo::Info)$($await)*
+        }
+
+        /// (Public) Get some of the info about kraken's asset pairs
+        ///
+        /// Arguments:
+        /// * pairs: A list of Kraken asset pair strings to get info about. If empty then all asset pairs
+        /// * info: Which info to get. Fields of the response which are not part of this info are defaulted.
+        pub $($async)? fn asset_pairs_with_info(
+            &self,
+            pairs: Vec<String>,
+            info: AssetPairInfo,
+        ) -> Result<AssetPairsResponse> {
+            let result: Result<KrakenResult<AssetPairsResponse>> = self
+                .client
+                .query_public(
+                    "AssetPairs",
+                    AssetPairsRequest {
+                        pair: pairs.join(","),
+                        info: Some(info).filter(|info| *info != AssetPairInfo::Info),
+                    },
+                )
+                $($await)*;
+            result.and_then(unpack_kraken_result)
+        }
+
+        /// (Public) Get the ticker price for one or more asset pairs
+        ///
+        /// Arguments:
+        /// * pairs: A list of Kraken asset pair strings to get ticker info about
+        pub $($async)? fn ticker(&self, pairs: Vec<String>) -> Result<TickerResponse> {
+            let result: Result<KrakenResult<TickerResponse>> = self
+                .client
+                .query_public(
+                    "Ticker",
+                    TickerRequest {
+                        pair: pairs.join(","),
+                    },
+                )
+                $($await)*;
+            result.and_then(unpack_kraken_result)
+        }
+
+        /// (Public) Get OHLC data for an asset pair, at one minute intervals.
+        /// Optionally pass "since", and will only return data after that timestamp.
+        /// (Intended for incremental updates).
+        /// Returns up to 720 of the most recent entries.
+        /// Older data cannot be retrieved, regardless of the value of since.
+        ///
+        /// Arguments:
+        /// * pair: Which asset pair to get data for
+        /// * since: A timestamp to get data since
+        pub $($async)? fn ohlc(&self, pair: String, since: Option<String>) -> Result<GetOHLCDataResponse> {
+            let result: Result<KrakenResult<GetOHLCDataResponse>> = self
+                .client
+                .query_public(
+                    "OHLC",
+                    GetOHLCDataRequest {
+                        pair,
+                        since,
+                        interval: None,
+                    },
+                )
+                $($await)*;
+            result.and_then(unpack_kraken_result)
+        }
+
+        /// (Public) Get OHLC data for an asset pair, at user-specified interval number of minutes.
+        /// Valid intervals are 1, 5, 15, 30, 60, 240, 1440, 10080, 21600.
+        ///
+        /// Optionally pass "since", and will only return data after that timestamp.
+        /// (Intended for incremental updates).
+        /// Returns up to 720 of the most recent entries.
+        /// Older data cannot be retrieved, regardless of the value of since.
+        ///
+        /// Arguments:
+        /// * pair: Which asset pair to get data for
+        /// * since: A timestamp to get data since
+        pub $($async)? fn ohlc_at_interval(
+            &self,
+            pair: String,
+            interval: u16,
+            since: Option<String>,
+        ) -> Result<GetOHLCDataResponse> {
+            let result: Result<KrakenResult<GetOHLCDataResponse>> = self
+                .client
+                .query_public(
+                    "OHLC",
+                    GetOHLCDataRequest {
+                        pair,
+                        since,
+                        interval: Some(interval),
+                    },
+                )
+                $($await)*;
+            result.and_then(unpack_kraken_result)
+        }
+
+        /// (Public) Get 1000 most recent trades in an asset pair, optionally, since a particular timestamp.
+        /// The response contains a "last" number which can be used as "since" to get the next page if desired.
+        pub $($async)? fn get_recent_trades(
+            &self,
+            pair: String,
+            since: Option<String>,
+        ) -> Result<GetRecentTradesResponse> {
+            let result: Result<KrakenResult<GetRecentTradesResponse>> = self
+                .client
+                .query_public(
+                    "Trades",
+                    GetRecentTradesRequest {
+                        pair,
+                        since,
+                        count: None,
+                    },
+                )
+                $($await)*;
+            result.and_then(unpack_kraken_result)
+        }
+
+        /// (Public) Get the order book of an asset pair
+        ///
+        /// Arguments:
+        /// * pair: The asset pair
+        /// * count: The maximum number of asks and bids, from 1 to 500 (default 100)
+        ///
+        /// This is a snapshot. The websockets API can be used to maintain the book as it changes.
+        pub $($async)? fn get_order_book(&self, pair: String, count: Option<u16>) -> Result<BookData> {
+            let result: Result<KrakenResult<GetOrderBookResponse>> = self
+                .client
+                .query_public("Depth", GetOrderBookRequest { pair, count })
+                $($await)*;
+            let response = result.and_then(unpack_kraken_result)?;
+            // The response is keyed by Kraken's name for the pair, which may not be the name we used
+            let depth = response.into_values().next().ok_or(Error::MissingResultJson)?;
+            Ok(depth.into())
+        }
+
+        /// (Public) Get the recent best bid and ask prices of an asset pair, optionally, since a particular timestamp.
+        /// The response contains a "last" number which can be used as "since" to get the next page if desired.
+        pub $($async)? fn get_recent_spreads(
+            &self,
+            pair: String,
+            since: Option<String>,
+        ) -> Result<GetRecentSpreadsResponse> {
+            let result: Result<KrakenResult<GetRecentSpreadsResponse>> = self
+                .client
+                .query_public("Spread", GetRecentSpreadsRequest { pair, since })
+                $($await)*;
+            result.and_then(unpack_kraken_result)
+        }
+
+        /// (Private) Get the balance
+        pub $($async)? fn get_account_balance(&self) -> Result<BalanceResponse> {
+            let result: Result<KrakenResult<BalanceResponse>> =
+                self.client.query_private("Balance", Empty {})$($await)*;
+            result.and_then(unpack_kraken_result)
+        }
+
+        /// (Private) Get trade volume and fee tier info, per asset pair
+        pub $($async)? fn get_trade_volume(&self, asset_pairs: Vec<String>) -> Result<GetTradeVolumeResponse> {
+            let result: Result<KrakenResult<GetTradeVolumeResponse>> = self
+                .client
+                .query_private(
+                    "TradeVolume",
+                    GetTradeVolumeRequest {
+                        pair: asset_pairs.join(","),
+                    },
+                )
+                $($await)*;
+            result.and_then(unpack_kraken_result)
+        }
+
+        /// (Private) Get the margin trading balance, and the value and profit/loss of open positions
+        ///
+        /// Arguments:
+        /// * asset: The asset to express values in (default ZUSD)
+        pub $($async)? fn get_trade_balance(&self, asset: Option<String>) -> Result<TradeBalanceResponse> {
+            let result: Result<KrakenResult<TradeBalanceResponse>> = self
+                .client
+                .query_private("TradeBalance", TradeBalanceRequest { asset })
+                $($await)*;
+            result.and_then(unpack_kraken_result)
+        }
+
+        /// (Private) Get open margin positions
+        ///
+        /// Arguments:
+        /// * txids: Position txids to restrict the output to (all positions if empty)
+        /// * docalcs: If true, include the current value and profit/loss of each position
+        pub $($async)? fn get_open_positions(
+            &self,
+            txids: Vec<String>,
+            docalcs: bool,
+        ) -> Result<OpenPositionsResponse> {
+            let result: Result<KrakenResult<OpenPositionsResponse>> = self
+                .client
+                .query_private(
+                    "OpenPositions",
+                    OpenPositionsRequest {
+                        txid: txids.join(","),
+                        docalcs,
+                    },
+                )
+                $($await)*;
+            result.and_then(unpack_kraken_result)
+        }
+
+        /// (Private) Get a websockets authentication token
+        pub $($async)? fn get_websockets_token(&self) -> Result<GetWebSocketsTokenResponse> {
+            let result: Result<KrakenResult<GetWebSocketsTokenResponse>> =
+                self.client.query_private("GetWebSocketsToken", Empty {})$($await)*;
+            result.and_then(unpack_kraken_result)
+        }
+
+        /// (Private) Query orders by order id
+        pub $($async)? fn query_orders(&self, order_ids: Vec<String>) -> Result<QueryOrdersResponse> {
+            let result: Result<KrakenResult<QueryOrdersResponse>> = self
+                .client
+                .query_private(
+                    "QueryOrders",
+                    QueryOrdersRequest {
+                        txid: order_ids.join(","),
+                        ..Default::default()
+                    },
+                )
+                $($await)*;
+            result.and_then(unpack_kraken_result)
+        }
+
+        /// (Private) Query an order by its client order id
+        pub $($async)? fn query_orders_by_cl_ord_id(&self, cl_ord_id: String) -> Result<QueryOrdersResponse> {
+            let result: Result<KrakenResult<QueryOrdersResponse>> = self
+                .client
+                .query_private(
+                    "QueryOrders",
+                    QueryOrdersRequest {
+                        cl_ord_id: Some(cl_ord_id),
+                        ..Default::default()
+                    },
+                )
+                $($await)*;
+            result.and_then(unpack_kraken_result)
+        }
+
+        /// (Private) Get the list of open orders
+        ///
+        /// Arguments:
+        /// * userref: An optional user-reference to filter the list of open orders by
+        pub $($async)? fn get_open_orders(&self, userref: Option<UserRefId>) -> Result<GetOpenOrdersResponse> {
+            let result: Result<KrakenResult<GetOpenOrdersResponse>> = self
+                .client
+                .query_private("OpenOrders", GetOpenOrdersRequest { userref })
+                $($await)*;
+            result.and_then(unpack_kraken_result)
+        }
+
+        /// (Private) Get the list of closed orders (at most 50 per call)
+        ///
+        /// Use `ofs` in the request to page through results. The response contains
+        /// a `count` of all the closed orders matching the criteria.
+        pub $($async)? fn get_closed_orders(&self, request: GetClosedOrdersRequest) -> Result<GetClosedOrdersResponse> {
+            let result: Result<KrakenResult<GetClosedOrdersResponse>> =
+                self.client.query_private("ClosedOrders", request)$($await)*;
+            result.and_then(unpack_kraken_result)
+        }
+
+        /// (Private) Get the history of our trades (fills), at most 50 per call
+        ///
+        /// Use `ofs` in the request to page through results. The response contains
+        /// a `count` of all the trades matching the criteria.
+        pub $($async)? fn get_trades_history(&self, request: TradesHistoryRequest) -> Result<TradesHistoryResponse> {
+            let result: Result<KrakenResult<TradesHistoryResponse>> =
+                self.client.query_private("TradesHistory", request)$($await)*;
+            result.and_then(unpack_kraken_result)
+        }
+
+        /// (Private) Query trades (fills) by trade id
+        ///
+        /// Arguments:
+        /// * trade_ids: Up to 20 trade ids to get info about
+        pub $($async)? fn query_trades(&self, trade_ids: Vec<String>) -> Result<QueryTradesResponse> {
+            let result: Result<KrakenResult<QueryTradesResponse>> = self
+                .client
+                .query_private(
+                    "QueryTrades",
+                    QueryTradesRequest {
+                        txid: trade_ids.join(","),
+                        trades: false,
+                    },
+                )
+                $($await)*;
+            result.and_then(unpack_kraken_result)
+        }
+
+        /// (Private) Get ledger entries (at most 50 per call)
+        ///
+        /// Every deposit, withdrawal, trade, fee, staking reward etc. creates ledger entries.
+        /// Use `ofs` in the request to page through results.
+        pub $($async)? fn get_ledgers(&self, request: LedgersRequest) -> Result<LedgersResponse> {
+            let result: Result<KrakenResult<LedgersResponse>> = self.client.query_private("Ledgers", request)$($await)*;
+            result.and_then(unpack_kraken_result)
+        }
+
+        /// (Private) Query ledger entries by ledger id
+        ///
+        /// Arguments:
+        /// * ledger_ids: Up to 20 ledger ids to get info about
+        pub $($async)? fn query_ledgers(&self, ledger_ids: Vec<String>) -> Result<QueryLedgersResponse> {
+            let result: Result<KrakenResult<QueryLedgersResponse>> = self
+                .client
+                .query_private(
+                    "QueryLedgers",
+                    QueryLedgersRequest {
+                        id: ledger_ids.join(","),
+                        trades: false,
+                    },
+                )
+                $($await)*;
+            result.and_then(unpack_kraken_result)
+        }
+
+        /// (Private) Cancel order
+        ///
+        /// Arguments:
+        /// * id: A TxId (OR a UserRefId) of order(s) to cancel
+        pub $($async)? fn cancel_order(&self, id: String) -> Result<CancelOrderResponse> {
+            if let Some(rate_limiter) = self.client.rate_limiter() {
+                acquire!(
+                    $mode,
+                    rate_limiter.acquire_cancel(&id),
+                    rate_limiter.acquire_cancel_async(&id)
+                )?;
+            }
+            let result: Result<KrakenResult<CancelOrderResponse>> = self
+                .client
+                .query_private(
+                    "CancelOrder",
+                    CancelOrderRequest {
+                        txid: id.clone(),
+                        ..Default::default()
+                    },
+                )
+                $($await)*;
+            let response = result.and_then(unpack_kraken_result)?;
+            if let Some(rate_limiter) = self.client.rate_limiter() {
+                rate_limiter.forget_order(&id);
+            }
+            Ok(response)
+        }
+
+        /// (Private) Cancel an order by its client order id
+        ///
+        /// This can be used to cancel an order whose TxId is not known, e.g. because placing it timed out.
+        pub $($async)? fn cancel_order_by_cl_ord_id(&self, cl_ord_id: String) -> Result<CancelOrderResponse> {
+            if let Some(rate_limiter) = self.client.rate_limiter() {
+                acquire!(
+                    $mode,
+                    rate_limiter.acquire_cancel(&cl_ord_id),
+                    rate_limiter.acquire_cancel_async(&cl_ord_id)
+                )?;
+            }
+            let result: Result<KrakenResult<CancelOrderResponse>> = self
+                .client
+                .query_private(
+                    "CancelOrder",
+                    CancelOrderRequest {
+                        cl_ord_id: Some(cl_ord_id.clone()),
+                        ..Default::default()
+                    },
+                )
+                $($await)*;
+            let response = result.and_then(unpack_kraken_result)?;
+            if let Some(rate_limiter) = self.client.rate_limiter() {
+                rate_limiter.forget_order(&cl_ord_id);
+            }
+            Ok(response)
+        }
+
+        /// (Private) Cancel all orders (regardless of user ref or tx id)
+        pub $($async)? fn cancel_all_orders(&self) -> Result<CancelAllOrdersResponse> {
+            let result: Result<KrakenResult<CancelAllOrdersResponse>> =
+                self.client.query_private("CancelAll", Empty {})$($await)*;
+            result.and_then(unpack_kraken_result)
+        }
+
+        /// (Private) Cancel all orders after
+        ///
+        /// Arguments:
+        /// * timeout: Integer timeout specified in seconds. 0 to disable the timer.
+        pub $($async)? fn cancel_all_orders_after(&self, timeout: u64) -> Result<CancelAllOrdersAfterResponse> {
+            let result: Result<KrakenResult<CancelAllOrdersAfterResponse>> = self
+                .client
+                .query_private("CancelAllOrdersAfter", CancelAllOrdersAfterRequest { timeout })
+                $($await)*;
+            result.and_then(unpack_kraken_result)
+        }
+
+        /// (Private) Place an order of any type
+        ///
+        /// Arguments:
+        /// * order: Order object describing the parameters of the order
+        /// * validate: If true, the order is only validated and is not actually placed
+        ///
+        /// If an order validator is configured, the order is checked (and possibly rounded) before it is placed.
+        pub $($async)? fn add_order(&self, order: Order, validate: bool) -> Result<AddOrderResponse> {
+            let order = match self.client.order_validator() {
+                Some(validator) => validator.apply(order)?,
+                None => order,
+            };
+            self.submit_order(order.into_request(validate))$($await)*
+        }
+
+        /// (Private) Place a market order
+        ///
+        /// Arguments:
+        /// * market_order: Market order object describing the parameters of the order
+        /// * user_ref_id: Optional user ref id to attach to the order
+        /// * validate: If true, the order is only validated and is not actually placed
+        pub $($async)? fn add_market_order(
+            &self,
+            market_order: MarketOrder,
+            user_ref_id: Option<UserRefId>,
+            validate: bool,
+        ) -> Result<AddOrderResponse> {
+            let order = Order {
+                userref: user_ref_id,
+                ..market_order.into()
+            };
+            self.add_order(order, validate)$($await)*
+        }
+
+        /// (Private) Place a limit order
+        ///
+        /// Arguments:
+        /// * limit_order: Limit order object describing the parameters of the order
+        /// * user_ref_id: Optional user ref id to attach to the order
+        /// * validate: If true, the order is only validated and is not actually placed
+        pub $($async)? fn add_limit_order(
+            &self,
+            limit_order: LimitOrder,
+            user_ref_id: Option<UserRefId>,
+            validate: bool,
+        ) -> Result<AddOrderResponse> {
+            let order = Order {
+                userref: user_ref_id,
+                ..limit_order.into()
+            };
+            self.add_order(order, validate)$($await)*
+        }
+
+        // Submit an AddOrder request, respecting the matching engine rate limits if rate limiting is enabled
+        $($async)? fn submit_order(&self, req: AddOrderRequest) -> Result<AddOrderResponse> {
+            let rate_limiter = self.client.rate_limiter();
+            if let Some(rate_limiter) = rate_limiter {
+                acquire!(
+                    $mode,
+                    rate_limiter.acquire_order(&req.pair),
+                    rate_limiter.acquire_order_async(&req.pair)
+                )?;
+            }
+            let pair = req.pair.clone();
+            let cl_ord_id = req.cl_ord_id.clone();
+            let result: Result<KrakenResult<AddOrderResponse>> = self.client.query_private("AddOrder", req)$($await)*;
+            let response = result.and_then(unpack_kraken_result)?;
+            if let Some(rate_limiter) = rate_limiter {
+                for txid in &response.txid {
+                    rate_limiter.record_order(txid.clone(), cl_ord_id.clone(), pair.clone());
+                }
+            }
+            Ok(response)
+        }
+
+        /// (Private) Place a batch of orders in the same pair
+        ///
+        /// Arguments:
+        /// * orders: Between 1 and [crate::MAX_ORDER_BATCH] orders, which must all be in the same pair.
+        ///   The `deadline` of individual orders is ignored.
+        /// * deadline: Optional RFC3339 timestamp after which the matching engine should reject the batch
+        /// * validate: If true, the orders are only validated and are not actually placed
+        ///
+        /// Returns the result of each order, in order. Some orders may be placed even if others are rejected.
+        /// If an order validator is configured, the batch is rejected unless every order passes validation.
+        pub $($async)? fn add_order_batch(
+            &self,
+            orders: Vec<Order>,
+            deadline: Option<String>,
+            validate: bool,
+        ) -> Result<AddOrderBatchResponse> {
+            let orders = match self.client.order_validator() {
+                Some(validator) => orders
+                    .into_iter()
+                    .map(|order| validator.apply(order))
+                    .collect::<core::result::Result<_, _>>()?,
+                None => orders,
+            };
+            let req = crate::order::add_order_batch_request(orders, deadline, validate)?;
+            let rate_limiter = self.client.rate_limiter();
+            if let Some(rate_limiter) = rate_limiter {
+                acquire!(
+                    $mode,
+                    rate_limiter.acquire_order_batch(&req.pair, req.orders.len()),
+                    rate_limiter.acquire_order_batch_async(&req.pair, req.orders.len())
+                )?;
+            }
+            let pair = req.pair.clone();
+            let cl_ord_ids: Vec<Option<String>> = req.orders.iter().map(|order| order.cl_ord_id.clone()).collect();
+            let result: Result<KrakenResult<AddOrderBatchResponse>> =
+                self.client.query_private("AddOrderBatch", req)$($await)*;
+            let response = result.and_then(unpack_kraken_result)?;
+            if let Some(rate_limiter) = rate_limiter {
+                for (order, cl_ord_id) in response.orders.iter().zip(cl_ord_ids) {
+                    if let Some(txid) = order.txid.as_ref() {
+                        rate_limiter.record_order(txid.clone(), cl_ord_id, pair.clone());
+                    }
+                }
+            }
+            Ok(response)
+        }
+
+        /// (Private) Cancel a batch of orders
+        ///
+        /// Arguments:
+        /// * ids: Between 1 and [crate::MAX_CANCEL_BATCH] TxIds or user ref ids of orders to cancel
+        ///
+        /// Kraken only reports the total number of orders canceled, not which ones.
+        pub $($async)? fn cancel_order_batch(&self, ids: Vec<String>) -> Result<CancelOrderResponse> {
+            let req = crate::order::cancel_order_batch_request(ids)?;
+            if let Some(rate_limiter) = self.client.rate_limiter() {
+                acquire!(
+                    $mode,
+                    rate_limiter.acquire_cancel_batch(&req.orders),
+                    rate_limiter.acquire_cancel_batch_async(&req.orders)
+                )?;
+            }
+            let ids = req.orders.clone();
+            let result: Result<KrakenResult<CancelOrderResponse>> =
+                self.client.query_private("CancelOrderBatch", req)$($await)*;
+            let response = result.and_then(unpack_kraken_result)?;
+            if let Some(rate_limiter) = self.client.rate_limiter() {
+                for id in &ids {
+                    rate_limiter.forget_order(id);
+                }
+            }
+            Ok(response)
+        }
+
+        /// (Private) Edit an open order
+        ///
+        /// This cancels the order and places a new one, with a new txid.
+        /// The new order loses the queue priority of the original. See also `amend_order`.
+        ///
+        /// If an order validator is configured, the edit is checked (and its volumes possibly rounded)
+        /// before it is sent.
+        pub $($async)? fn edit_order(&self, request: EditOrderRequest) -> Result<EditOrderResponse> {
+            let request = match self.client.order_validator() {
+                Some(validator) => validator.apply_edit(request)?,
+                None => request,
+            };
+            let rate_limiter = self.client.rate_limiter();
+            if let Some(rate_limiter) = rate_limiter {
+                acquire!(
+                    $mode,
+                    rate_limiter.acquire_edit(&request.txid),
+                    rate_limiter.acquire_edit_async(&request.txid)
+                )?;
+            }
+            let (original, pair) = (request.txid.clone(), request.pair.clone());
+            let result: Result<KrakenResult<EditOrderResponse>> =
+                self.client.query_private("EditOrder", request)$($await)*;
+            let response = result.and_then(unpack_kraken_result)?;
+            if let Some(rate_limiter) = rate_limiter
+                && let Some(txid) = response.txid.as_ref()
+            {
+                rate_limiter.forget_order(&original);
+                rate_limiter.record_order(txid.clone(), None, pair);
+            }
+            Ok(response)
+        }
+
+        /// (Private) Amend an open order in place
+        ///
+        /// The order keeps its txid, and keeps its queue priority where possible
+        /// (e.g. when only the quantity is reduced).
+        ///
+        /// If an order validator is configured, the request must have its `pair` set, and the amend is checked
+        /// (and its quantities possibly rounded) before it is sent.
+        pub $($async)? fn amend_order(&self, request: AmendOrderRequest) -> Result<AmendOrderResponse> {
+            let request = match self.client.order_validator() {
+                Some(validator) => validator.apply_amend(request)?,
+                None => request,
+            };
+            if let Some(rate_limiter) = self.client.rate_limiter()
+                && let Some(id) = request.txid.as_ref().or(request.cl_ord_id.as_ref())
+            {
+                acquire!(
+                    $mode,
+                    rate_limiter.acquire_amend(id),
+                    rate_limiter.acquire_amend_async(id)
+                )?;
+            }
+            let result: Result<KrakenResult<AmendOrderResponse>> =
+                self.client.query_private("AmendOrder", request)$($await)*;
+            result.and_then(unpack_kraken_result)
+        }
+
+        /// (Private) Get deposit methods for an asset
+        ///
+        /// Arguments:
+        /// * asset: Asset name to get deposit methods for (e.g. "BTC")
+        pub $($async)? fn get_deposit_methods(&self, asset: String) -> Result<DepositMethodsResponse> {
+            let result: Result<KrakenResult<DepositMethodsResponse>> = self
+                .client
+                .query_private("DepositMethods", DepositMethodsRequest { asset })
+                $($await)*;
+            result.and_then(unpack_kraken_result)
+        }
+
+        /// (Private) Get deposit addresses for an asset and method
+        pub $($async)? fn get_deposit_addresses(
+            &self,
+            request: DepositAddressesRequest,
+        ) -> Result<DepositAddressesResponse> {
+            let result: Result<KrakenResult<DepositAddressesResponse>> =
+                self.client.query_private("DepositAddresses", request)$($await)*;
+            result.and_then(unpack_kraken_result)
+        }
+
+        /// (Private) Get status of recent deposits
+        pub $($async)? fn get_deposit_status(&self, request: DepositStatusRequest) -> Result<DepositStatusResponse> {
+            let result: Result<KrakenResult<DepositStatusResponse>> =
+                self.client.query_private("DepositStatus", request)$($await)*;
+            result.and_then(unpack_kraken_result)
+        }
+
+        /// (Private) Get withdrawal addresses
+        ///
+        /// Arguments:
+        /// * asset: Optional asset to filter by (e.g. "BTC")
+        /// * method: Optional withdrawal method to filter by
+        pub $($async)? fn get_withdrawal_addresses(
+            &self,
+            asset: Option<String>,
+            method: Option<String>,
+        ) -> Result<WithdrawAddressesResponse> {
+            let result: Result<KrakenResult<WithdrawAddressesResponse>> = self
+                .client
+                .query_private(
+                    "WithdrawAddresses",
+                    WithdrawAddressesRequest {
+                        aclass: None,
+                        asset,
+                        method,
+                    },
+                )
+                $($await)*;
+            result.and_then(unpack_kraken_result)
+        }
+
+        /// (Private) Withdraw funds
+        pub $($async)? fn withdraw(&self, request: WithdrawRequest) -> Result<WithdrawResponse> {
+            let result: Result<KrakenResult<WithdrawResponse>> =
+                self.client.query_private("Withdraw", request)$($await)*;
+            result.and_then(unpack_kraken_result)
+        }
+
+        /// (Private) Get withdrawal fee information
+        pub $($async)? fn get_withdraw_info(&self, request: WithdrawInfoRequest) -> Result<WithdrawInfoResponse> {
+            let result: Result<KrakenResult<WithdrawInfoResponse>> =
+                self.client.query_private("WithdrawInfo", request)$($await)*;
+            result.and_then(unpack_kraken_result)
+        }
+
+        /// (Private) Get status of recent withdrawals
+        pub $($async)? fn get_withdraw_status(&self, request: WithdrawStatusRequest) -> Result<WithdrawStatusResponse> {
+            let result: Result<KrakenResult<WithdrawStatusResponse>> =
+                self.client.query_private("WithdrawStatus", request)$($await)*;
+            result.and_then(unpack_kraken_result)
+        }
+    };
+}
+
+// Acquire from the rate limiter, using the blocking or async version of the method
+macro_rules! acquire {
+    (blocking, $blocking:expr, $async:expr) => {
+        $blocking
+    };
+    (async, $blocking:expr, $async:expr) => {
+        $async.await
+    };
+}
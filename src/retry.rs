@@ -0,0 +1,229 @@
+//! A policy for automatically retrying REST API calls which fail for transient reasons,
+//! such as connection errors, timeouts, or `EService:Unavailable`.
+//!
+//! Calls which are not idempotent (e.g. placing an order or withdrawing funds) are never retried,
+//! because if the first attempt timed out, it may have succeeded anyways. Retrying post-only limit
+//! orders with a client order id can be opted into, see [RetryPolicy::retry_orders_with_client_id].
+
+use crate::{Error, KrakenErrorCategory, KrakenErrorKind};
+use reqwest::StatusCode;
+use std::{
+    collections::hash_map::RandomState,
+    hash::{BuildHasher, Hasher},
+    time::Duration,
+};
+
+/// Private methods which are not idempotent, and so are not retried by default
+pub const NON_IDEMPOTENT_METHODS: &[&str] = &[
+    "AddOrder",
+    "AddOrderBatch",
+    "EditOrder",
+    "AmendOrder",
+    "Withdraw",
+    "WithdrawCancel",
+    "WalletTransfer",
+];
+
+/// A policy for retrying failed REST API calls
+#[derive(Clone, Debug, PartialEq)]
+pub struct RetryPolicy {
+    /// The maximum number of attempts, including the first one
+    pub max_attempts: u32,
+    /// The delay before the first retry. This doubles with each further retry.
+    pub initial_backoff: Duration,
+    /// The maximum delay between retries
+    pub max_backoff: Duration,
+    /// If true, each delay is randomly chosen between half and all of the backoff
+    pub jitter: bool,
+    /// Kraken errors in these categories are retried
+    pub retryable_categories: Vec<KrakenErrorCategory>,
+    /// Kraken errors of these kinds are retried, regardless of category
+    pub retryable_kinds: Vec<KrakenErrorKind>,
+    /// If true, connection errors, timeouts, and 5xx / 429 http statuses are retried
+    pub retry_transport_errors: bool,
+    /// If true, AddOrder and AddOrderBatch are retried if every order in the call is a post-only limit order
+    /// with a client order id (`cl_ord_id`), which is not immediate-or-cancel.
+    ///
+    /// Kraken only rejects an order whose client order id duplicates that of an *open* order. A post-only
+    /// order cannot fill when it is placed, so if the first attempt went through, the order is normally still
+    /// open when the retry arrives, and the retry is rejected. But if the order was filled (by another trader)
+    /// or canceled in the meantime, the retry places it a second time. Only enable this if that risk is acceptable.
+    ///
+    /// Other orders are never retried, since e.g. a market or IOC order may have filled immediately.
+    /// Editing and amending orders are never retried: there the `cl_ord_id` identifies the existing order,
+    /// and does not prevent the change from being applied twice.
+    pub retry_orders_with_client_id: bool,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: 3,
+            initial_backoff: Duration::from_millis(500),
+            max_backoff: Duration::from_secs(10),
+            jitter: true,
+            retryable_categories: vec![KrakenErrorCategory::Service],
+            retryable_kinds: vec![KrakenErrorKind::InvalidNonce],
+            retry_transport_errors: true,
+            retry_orders_with_client_id: false,
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Check if an error returned by an attempt should be retried
+    pub fn is_retryable(&self, err: &Error) -> bool {
+        match err {
+            Error::KrakenErrors(errors) => errors.iter().filter(|err| err.is_error()).any(|err| {
+                self.retryable_categories.contains(&err.category) || self.retryable_kinds.contains(&err.kind)
+            }),
+            Error::Reqwest(err) => self.retry_transport_errors && (err.is_timeout() || err.is_connect()),
+            Error::BadStatus(response) => self.is_retryable_status(response.status()),
+            #[cfg(feature = "async")]
+            Error::AsyncBadStatus(response) => self.is_retryable_status(response.status()),
+            _ => false,
+        }
+    }
+
+    fn is_retryable_status(&self, status: StatusCode) -> bool {
+        self.retry_transport_errors && (status.is_server_error() || status == StatusCode::TOO_MANY_REQUESTS)
+    }
+
+    /// Check if a private method may be retried, given its encoded parameters
+    pub fn is_retryable_call(&self, method: &str, query_string: &str) -> bool {
+        if !NON_IDEMPOTENT_METHODS.contains(&method) {
+            return true;
+        }
+        if !self.retry_orders_with_client_id {
+            return false;
+        }
+        let params: Vec<(String, String)> = url::form_urlencoded::parse(query_string.as_bytes())
+            .map(|(key, value)| (key.into_owned(), value.into_owned()))
+            .collect();
+        match method {
+            "AddOrder" => is_resting_order_with_client_id(&params, ""),
+            // Every order in the batch must qualify
+            "AddOrderBatch" => {
+                let num_orders = params.iter().filter(|(key, _)| key.ends_with("[ordertype]")).count();
+                num_orders > 0
+                    && (0..num_orders).all(|idx| is_resting_order_with_client_id(&params, &format!("orders[{idx}]")))
+            }
+            _ => false,
+        }
+    }
+
+    /// The delay before a retry, given the number of attempts made so far (at least 1)
+    pub fn backoff(&self, attempts: u32) -> Duration {
+        let backoff = self
+            .initial_backoff
+            .saturating_mul(2u32.saturating_pow(attempts.saturating_sub(1)))
+            .min(self.max_backoff);
+        if self.jitter {
+            // Uniformly random fraction in [0.5, 1.0]
+            let random = RandomState::new().build_hasher().finish();
+            backoff.mul_f64(0.5 + 0.5 * (random as f64 / u64::MAX as f64))
+        } else {
+            backoff
+        }
+    }
+}
+
+// Check if the order with the given prefix (e.g. `orders[0]` in a batch) is a post-only limit order,
+// which is not immediate-or-cancel, and has a client order id
+fn is_resting_order_with_client_id(params: &[(String, String)], prefix: &str) -> bool {
+    let get = |name: &str| {
+        let key = if prefix.is_empty() {
+            name.to_owned()
+        } else {
+            format!("{prefix}[{name}]")
+        };
+        params.iter().find(|(k, _)| *k == key).map(|(_, value)| value.as_str())
+    };
+    get("cl_ord_id").is_some()
+        && get("ordertype") == Some("limit")
+        && get("oflags").is_some_and(|oflags| oflags.split(',').any(|flag| flag == "post"))
+        && get("timeinforce") != Some("IOC")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::KrakenError;
+
+    #[test]
+    fn test_backoff() {
+        let policy = RetryPolicy {
+            jitter: false,
+            initial_backoff: Duration::from_millis(100),
+            max_backoff: Duration::from_millis(1000),
+            ..Default::default()
+        };
+        assert_eq!(policy.backoff(1), Duration::from_millis(100));
+        assert_eq!(policy.backoff(2), Duration::from_millis(200));
+        assert_eq!(policy.backoff(4), Duration::from_millis(800));
+        assert_eq!(policy.backoff(5), Duration::from_millis(1000));
+        assert_eq!(policy.backoff(100), Duration::from_millis(1000));
+
+        let policy = RetryPolicy { jitter: true, ..policy };
+        for _ in 0..100 {
+            let backoff = policy.backoff(3);
+            assert!(backoff >= Duration::from_millis(200) && backoff <= Duration::from_millis(400));
+        }
+    }
+
+    #[test]
+    fn test_retryable_errors() {
+        let policy = RetryPolicy::default();
+        let kraken_errors = |src: &str| Error::KrakenErrors(vec![KrakenError::parse(src)]);
+
+        assert!(policy.is_retryable(&kraken_errors("EService:Unavailable")));
+        assert!(policy.is_retryable(&kraken_errors("EService:Busy")));
+        assert!(policy.is_retryable(&kraken_errors("EAPI:Invalid nonce")));
+        assert!(!policy.is_retryable(&kraken_errors("EAPI:Invalid key")));
+        assert!(!policy.is_retryable(&kraken_errors("EOrder:Insufficient funds")));
+        assert!(!policy.is_retryable(&kraken_errors("WService:Unavailable")));
+        assert!(!policy.is_retryable(&Error::MissingCredentials));
+        assert!(!policy.is_retryable(&Error::RateLimited {
+            retry_after: Duration::from_secs(1)
+        }));
+    }
+
+    #[test]
+    fn test_retryable_calls() {
+        let policy = RetryPolicy::default();
+        assert!(policy.is_retryable_call("Balance", ""));
+        assert!(policy.is_retryable_call("CancelOrder", "txid=OABC"));
+        assert!(!policy.is_retryable_call("AddOrder", "pair=XBTUSD&cl_ord_id=abc"));
+        assert!(!policy.is_retryable_call("Withdraw", "asset=XBT"));
+
+        let policy = RetryPolicy {
+            retry_orders_with_client_id: true,
+            ..Default::default()
+        };
+        let post = "pair=XBTUSD&ordertype=limit&oflags=post";
+        assert!(policy.is_retryable_call("AddOrder", &format!("{post}&cl_ord_id=abc")));
+        assert!(policy.is_retryable_call("AddOrder", &format!("{post},fciq&timeinforce=GTD&cl_ord_id=abc")));
+        assert!(!policy.is_retryable_call("AddOrder", post));
+        assert!(!policy.is_retryable_call("AddOrder", &format!("{post}&timeinforce=IOC&cl_ord_id=abc")));
+        assert!(!policy.is_retryable_call("AddOrder", "pair=XBTUSD&ordertype=limit&cl_ord_id=abc"));
+        assert!(!policy.is_retryable_call("AddOrder", "pair=XBTUSD&ordertype=market&cl_ord_id=abc"));
+        assert!(!policy.is_retryable_call("EditOrder", "txid=OABC&cl_ord_id=abc"));
+        assert!(!policy.is_retryable_call("AmendOrder", "cl_ord_id=abc"));
+        assert!(!policy.is_retryable_call("Withdraw", "asset=XBT"));
+        assert!(policy.is_retryable_call(
+            "AddOrderBatch",
+            "orders[0][ordertype]=limit&orders[0][oflags]=post&orders[0][cl_ord_id]=a\
+             &orders[1][ordertype]=limit&orders[1][oflags]=post&orders[1][cl_ord_id]=b"
+        ));
+        assert!(!policy.is_retryable_call(
+            "AddOrderBatch",
+            "orders[0][ordertype]=limit&orders[0][oflags]=post&orders[0][cl_ord_id]=a\
+             &orders[1][ordertype]=limit&orders[1][oflags]=post"
+        ));
+        assert!(!policy.is_retryable_call(
+            "AddOrderBatch",
+            "orders[0][ordertype]=limit&orders[0][oflags]=post&orders[0][cl_ord_id]=a\
+             &orders[1][ordertype]=limit&orders[1][cl_ord_id]=b"
+        ));
+    }
+}
@@ -0,0 +1,438 @@
+//! An in-process mock Kraken server, for testing code which uses krakenrs
+//! without talking to the live exchange. This requires the `mock` feature.
+//!
+//! [MockKrakenServer] serves the REST API over http and the websockets API over ws,
+//! on local ports, using the same json shapes as Kraken. Use [MockKrakenServer::rest_config]
+//! and [MockKrakenServer::ws_config] to get config builders which point at it.
+//!
+//! Tests can:
+//! * script REST responses per method, and inspect the requests that were made.
+//!   Signatures of private requests are checked against the mock credentials.
+//! * set order books and push book updates over websockets, with valid checksums.
+//! * script the acknowledgments to websockets orders, and push open orders and own trades.
+
+use crate::{
+    KrakenCredentials, KrakenRestConfig, KrakenRestConfigBuilder,
+    ws::{BookData, KrakenWsConfig, KrakenWsConfigBuilder},
+};
+use base64ct::{Base64, Encoding};
+use serde_json::{Value, json};
+use std::{
+    collections::{BTreeMap, HashMap, HashSet, VecDeque},
+    io::Error as IoError,
+    net::SocketAddr,
+    sync::{Arc, Mutex},
+    thread,
+    time::{Duration, Instant, SystemTime},
+};
+use tokio::{
+    runtime,
+    sync::{broadcast, oneshot},
+};
+
+mod rest;
+pub use rest::RecordedRequest;
+
+mod ws;
+pub use ws::WsOrderAck;
+use ws::WsPush;
+
+/// The websockets token which the mock server hands out and accepts
+pub const MOCK_WS_TOKEN: &str = "mock-ws-token";
+
+/// The current time which the mock server reports in websockets `cancelAllOrdersAfter` replies
+pub const MOCK_CURRENT_TIME: &str = "2023-07-06T18:50:48Z";
+
+/// The trigger time which the mock server reports in websockets `cancelAllOrdersAfter` replies,
+/// unless the switch is disabled
+pub const MOCK_TRIGGER_TIME: &str = "2023-07-06T18:51:48Z";
+
+/// An in-process mock of the Kraken REST and websockets APIs.
+///
+/// The server runs on a background thread, and is stopped when this is dropped.
+pub struct MockKrakenServer {
+    // The address the http server listens on
+    rest_addr: SocketAddr,
+    // The address the websockets server listens on
+    ws_addr: SocketAddr,
+    // State shared with the server tasks
+    state: Arc<Mutex<MockState>>,
+    // Sender for messages to push to websockets connections
+    ws_push: broadcast::Sender<WsPush>,
+    // Signals the server thread to stop
+    stop: Option<oneshot::Sender<()>>,
+    // The server thread
+    worker_thread: Option<thread::JoinHandle<()>>,
+}
+
+/// A function computing the result of a REST method from the parameters of a request
+pub(crate) type RestHandler = Box<dyn FnMut(&BTreeMap<String, String>) -> Value + Send>;
+
+/// State of the mock server, shared with the server tasks
+pub(crate) struct MockState {
+    /// Credentials that private requests must be signed with
+    pub(crate) creds: KrakenCredentials,
+    /// If true, nonces of private requests must be strictly increasing
+    pub(crate) strict_nonces: bool,
+    /// The last nonce we saw on a private request
+    pub(crate) last_nonce: u64,
+    /// Scripted REST responses, per method
+    pub(crate) responses: HashMap<String, VecDeque<Value>>,
+    /// Functions computing REST results from request parameters, per method
+    pub(crate) handlers: HashMap<String, RestHandler>,
+    /// REST requests received so far
+    pub(crate) requests: Vec<RecordedRequest>,
+    /// Order books, per asset pair
+    pub(crate) books: HashMap<String, BookData>,
+    /// Scripted acknowledgments for websockets orders
+    pub(crate) order_acks: VecDeque<WsOrderAck>,
+    /// Txids of websockets orders that were accepted and not canceled
+    pub(crate) open_txids: HashSet<String>,
+    /// Counter for generating txids
+    pub(crate) next_txid: u64,
+    /// Websockets requests received so far
+    pub(crate) ws_requests: Vec<Value>,
+    /// Websockets subscriptions made so far: (subscription name, asset pair)
+    pub(crate) ws_subscriptions: Vec<(String, Option<String>)>,
+    /// If true, websockets requests are recorded but not answered
+    pub(crate) ws_silent: bool,
+}
+
+impl Default for MockState {
+    fn default() -> Self {
+        Self {
+            creds: KrakenCredentials {
+                key: "mock-api-key".into(),
+                secret: Base64::encode_string(b"mock-api-secret"),
+            },
+            strict_nonces: false,
+            last_nonce: 0,
+            responses: Default::default(),
+            handlers: Default::default(),
+            requests: Default::default(),
+            books: Default::default(),
+            order_acks: Default::default(),
+            open_txids: Default::default(),
+            next_txid: 0,
+            ws_requests: Default::default(),
+            ws_subscriptions: Default::default(),
+            ws_silent: false,
+        }
+    }
+}
+
+impl MockState {
+    /// Generate a fresh txid
+    pub(crate) fn gen_txid(&mut self) -> String {
+        self.next_txid += 1;
+        format!(
+            "OMOCK{:02}-{:05}-MOCKTX",
+            self.next_txid / 100_000,
+            self.next_txid % 100_000
+        )
+    }
+}
+
+impl MockKrakenServer {
+    /// Start a mock server listening on local ports
+    pub fn start() -> Result<Self, IoError> {
+        let rt = runtime::Builder::new_current_thread().enable_all().build()?;
+
+        let rest_listener = std::net::TcpListener::bind("127.0.0.1:0")?;
+        let ws_listener = std::net::TcpListener::bind("127.0.0.1:0")?;
+        rest_listener.set_nonblocking(true)?;
+        ws_listener.set_nonblocking(true)?;
+        let rest_addr = rest_listener.local_addr()?;
+        let ws_addr = ws_listener.local_addr()?;
+
+        let (rest_listener, ws_listener) = {
+            let _guard = rt.enter();
+            (
+                tokio::net::TcpListener::from_std(rest_listener)?,
+                tokio::net::TcpListener::from_std(ws_listener)?,
+            )
+        };
+
+        let state = Arc::new(Mutex::new(MockState::default()));
+        let (ws_push, _) = broadcast::channel(1024);
+        let (stop, stop_receiver) = oneshot::channel::<()>();
+
+        let thread_state = state.clone();
+        let thread_ws_push = ws_push.clone();
+        let worker_thread = thread::Builder::new()
+            .name("kraken-mock-server".into())
+            .spawn(move || {
+                rt.block_on(async move {
+                    tokio::spawn(rest::serve(rest_listener, thread_state.clone()));
+                    tokio::spawn(ws::serve(ws_listener, thread_state, thread_ws_push));
+                    drop(stop_receiver.await);
+                })
+            })?;
+
+        Ok(Self {
+            rest_addr,
+            ws_addr,
+            state,
+            ws_push,
+            stop: Some(stop),
+            worker_thread: Some(worker_thread),
+        })
+    }
+
+    /// The base url of the REST API
+    pub fn base_url(&self) -> String {
+        format!("http://{}/", self.rest_addr)
+    }
+
+    /// The url of the websockets API. (The same url serves public and private feeds.)
+    pub fn ws_url(&self) -> String {
+        format!("ws://{}", self.ws_addr)
+    }
+
+    /// The credentials which private REST requests must be signed with
+    pub fn creds(&self) -> KrakenCredentials {
+        self.lock().creds.clone()
+    }
+
+    /// Set the credentials which private REST requests must be signed with
+    pub fn set_creds(&self, creds: KrakenCredentials) {
+        self.lock().creds = creds;
+    }
+
+    /// If true, private REST requests are rejected with `EAPI:Invalid nonce` unless
+    /// their nonce is larger than that of the previous private request, as on Kraken.
+    /// This is off by default.
+    pub fn set_strict_nonces(&self, strict_nonces: bool) {
+        self.lock().strict_nonces = strict_nonces;
+    }
+
+    /// A REST config builder pointed at this server, with the mock credentials
+    pub fn rest_config(&self) -> KrakenRestConfigBuilder {
+        KrakenRestConfig::builder()
+            .base_url(self.base_url())
+            .creds(self.creds())
+    }
+
+    /// A websockets config builder pointed at this server.
+    /// Set the token to [MOCK_WS_TOKEN] to use private feeds.
+    pub fn ws_config(&self) -> KrakenWsConfigBuilder {
+        KrakenWsConfig::builder()
+            .ws_url(self.ws_url())
+            .ws_auth_url(self.ws_url())
+    }
+
+    /// Script a successful response to a REST method. The value is the `result` field of the response.
+    ///
+    /// Responses for a method are returned in the order they were scripted. The last one
+    /// is repeated for any further requests.
+    ///
+    /// `GetWebSocketsToken` returns [MOCK_WS_TOKEN] unless scripted. Other methods which
+    /// are not scripted return `EGeneral:Unknown method`.
+    pub fn respond(&self, method: &str, result: Value) {
+        self.push_response(method, json!({ "error": [], "result": result }));
+    }
+
+    /// Script an error response to a REST method, see [MockKrakenServer::respond]
+    pub fn respond_error(&self, method: &str, errors: &[&str]) {
+        self.push_response(method, json!({ "error": errors }));
+    }
+
+    /// Respond to a REST method with a result computed from the parameters of each request,
+    /// e.g. to model results which change between requests. This takes precedence over scripted responses.
+    ///
+    /// The function is called with the server state locked, so it must not call back into the server.
+    pub fn respond_with(&self, method: &str, handler: impl FnMut(&BTreeMap<String, String>) -> Value + Send + 'static) {
+        self.lock().handlers.insert(method.to_owned(), Box::new(handler));
+    }
+
+    /// Get all REST requests which were received so far
+    pub fn requests(&self) -> Vec<RecordedRequest> {
+        self.lock().requests.clone()
+    }
+
+    /// Get all REST requests for a particular method which were received so far
+    pub fn requests_for(&self, method: &str) -> Vec<RecordedRequest> {
+        self.lock()
+            .requests
+            .iter()
+            .filter(|req| req.method == method)
+            .cloned()
+            .collect()
+    }
+
+    /// Set the order book of an asset pair, from (price, volume) levels.
+    ///
+    /// A snapshot is sent to current subscribers, and to any later subscribers.
+    pub fn set_book(&self, pair: &str, asks: &[(&str, &str)], bids: &[(&str, &str)]) {
+        let mut book = BookData::default();
+        apply_levels(&mut book, asks, bids);
+        self.lock().books.insert(pair.to_owned(), book);
+        drop(self.ws_push.send(WsPush::BookSnapshot { pair: pair.to_owned() }));
+    }
+
+    /// Update the order book of an asset pair, with (price, volume) levels.
+    /// A volume of zero removes a price level.
+    ///
+    /// An update is sent to current subscribers, with the checksum of the resulting book.
+    pub fn update_book(&self, pair: &str, asks: &[(&str, &str)], bids: &[(&str, &str)]) {
+        let checksum = {
+            let mut state = self.lock();
+            let book = state.books.entry(pair.to_owned()).or_default();
+            apply_levels(book, asks, bids);
+            book.checksum()
+        };
+        self.update_book_with_checksum(pair, asks, bids, checksum);
+    }
+
+    /// Send a book update to current subscribers with a particular checksum, without
+    /// changing the book on the server. This can be used to test checksum failures.
+    pub fn update_book_with_checksum(&self, pair: &str, asks: &[(&str, &str)], bids: &[(&str, &str)], checksum: u32) {
+        let mut update = serde_json::Map::new();
+        if !asks.is_empty() {
+            update.insert("a".into(), levels_json(asks));
+        }
+        if !bids.is_empty() {
+            update.insert("b".into(), levels_json(bids));
+        }
+        update.insert("c".into(), checksum.to_string().into());
+        drop(self.ws_push.send(WsPush::BookUpdate {
+            pair: pair.to_owned(),
+            update: update.into(),
+        }));
+    }
+
+    /// Script the acknowledgment of the next websockets `addOrder` request.
+    /// Orders are accepted with a generated txid unless scripted.
+    pub fn queue_order_ack(&self, ack: WsOrderAck) {
+        self.lock().order_acks.push_back(ack);
+    }
+
+    /// Push open order updates to subscribers of `openOrders`.
+    /// This is an array of objects mapping txids to order info.
+    pub fn push_open_orders(&self, orders: Value) {
+        drop(self.ws_push.send(WsPush::Private {
+            channel: "openOrders",
+            data: orders,
+        }));
+    }
+
+    /// Push trades to subscribers of `ownTrades`.
+    /// This is an array of objects mapping trade ids to trade info.
+    pub fn push_own_trades(&self, trades: Value) {
+        drop(self.ws_push.send(WsPush::Private {
+            channel: "ownTrades",
+            data: trades,
+        }));
+    }
+
+    /// Send an arbitrary message to all websockets connections
+    pub fn push_ws_message(&self, message: Value) {
+        drop(self.ws_push.send(WsPush::Raw(message)));
+    }
+
+    /// Set whether websockets requests go unanswered (they are still recorded),
+    /// as if the connection were open but no longer working
+    pub fn set_ws_silent(&self, ws_silent: bool) {
+        self.lock().ws_silent = ws_silent;
+    }
+
+    /// Get all websockets requests which were received so far
+    pub fn ws_requests(&self) -> Vec<Value> {
+        self.lock().ws_requests.clone()
+    }
+
+    /// Wait until a websockets client has subscribed to a feed (e.g. "book", "openOrders"),
+    /// optionally for a particular asset pair. Returns false if this times out.
+    pub fn wait_for_subscription(&self, name: &str, pair: Option<&str>, timeout: Duration) -> bool {
+        let deadline = Instant::now() + timeout;
+        loop {
+            if self
+                .lock()
+                .ws_subscriptions
+                .iter()
+                .any(|(sub_name, sub_pair)| sub_name == name && (pair.is_none() || sub_pair.as_deref() == pair))
+            {
+                return true;
+            }
+            if Instant::now() > deadline {
+                return false;
+            }
+            thread::sleep(Duration::from_millis(10));
+        }
+    }
+
+    fn push_response(&self, method: &str, response: Value) {
+        self.lock()
+            .responses
+            .entry(method.to_owned())
+            .or_default()
+            .push_back(response);
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, MockState> {
+        self.state.lock().expect("mutex poisoned")
+    }
+}
+
+impl Drop for MockKrakenServer {
+    fn drop(&mut self) {
+        if let Some(stop) = self.stop.take() {
+            let _ = stop.send(());
+        }
+        if let Some(worker_thread) = self.worker_thread.take() {
+            drop(worker_thread.join());
+        }
+    }
+}
+
+// Apply (price, volume) levels to a book, the same way the websockets client does
+fn apply_levels(book: &mut BookData, asks: &[(&str, &str)], bids: &[(&str, &str)]) {
+    book.update_asks(&levels_json(asks), usize::MAX)
+        .expect("invalid ask levels");
+    book.update_bids(&levels_json(bids), usize::MAX)
+        .expect("invalid bid levels");
+}
+
+// Format (price, volume) levels as kraken does, with the current time as the timestamp
+fn levels_json(levels: &[(&str, &str)]) -> Value {
+    let timestamp = format!("{:.6}", now_secs());
+    levels
+        .iter()
+        .map(|(price, volume)| json!([price, volume, timestamp]))
+        .collect()
+}
+
+// The current time in seconds since the epoch
+fn now_secs() -> f64 {
+    SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs_f64()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rust_decimal::Decimal;
+    use std::str::FromStr;
+
+    #[test]
+    fn test_apply_levels() {
+        let mut book = BookData::default();
+        apply_levels(
+            &mut book,
+            &[("30001.0", "1.5"), ("30002.0", "0.25")],
+            &[("29999.5", "2.0")],
+        );
+        assert_eq!(book.ask.len(), 2);
+        assert_eq!(
+            book.bid[&Decimal::from_str("29999.5").unwrap()].volume,
+            Decimal::from_str("2.0").unwrap()
+        );
+
+        // A level with zero volume is removed
+        apply_levels(&mut book, &[("30001.0", "0.00000000")], &[]);
+        assert_eq!(book.ask.len(), 1);
+        assert!(!book.ask.contains_key(&Decimal::from_str("30001.0").unwrap()));
+    }
+}
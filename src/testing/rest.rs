@@ -0,0 +1,201 @@
+//! The http side of the mock server. This is a minimal HTTP/1.1 server which handles
+//! one request per connection, which is all that the REST client needs.
+
+use super::{MOCK_WS_TOKEN, MockState};
+use base64ct::{Base64, Encoding};
+use hmac::{Hmac, Mac};
+use serde_json::{Value, json};
+use sha2::{Digest, Sha256, Sha512};
+use std::{
+    collections::BTreeMap,
+    sync::{Arc, Mutex},
+};
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::{TcpListener, TcpStream},
+};
+
+/// A REST request received by the mock server
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[non_exhaustive]
+pub struct RecordedRequest {
+    /// The method name, e.g. "Balance"
+    pub method: String,
+    /// True if this was a request to a private method
+    pub private: bool,
+    /// The url-decoded form parameters of the request (including the nonce, for private requests)
+    pub params: BTreeMap<String, String>,
+    /// The error the request was rejected with, if authentication of a private request failed
+    pub rejected: Option<String>,
+}
+
+/// Accept http connections until the task is dropped
+pub(crate) async fn serve(listener: TcpListener, state: Arc<Mutex<MockState>>) {
+    loop {
+        match listener.accept().await {
+            Ok((stream, _addr)) => {
+                tokio::spawn(handle_conn(stream, state.clone()));
+            }
+            Err(err) => {
+                log::error!("mock server: accept failed: {}", err);
+            }
+        }
+    }
+}
+
+// Read one request from the connection, and write the response
+async fn handle_conn(mut stream: TcpStream, state: Arc<Mutex<MockState>>) {
+    let (path, headers, body) = match read_request(&mut stream).await {
+        Ok(req) => req,
+        Err(err) => {
+            log::error!("mock server: bad http request: {}", err);
+            return;
+        }
+    };
+
+    let response = handle_request(&state, &path, &headers, &body).to_string();
+    let text = format!(
+        "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        response.len(),
+        response
+    );
+    if let Err(err) = stream.write_all(text.as_bytes()).await {
+        log::error!("mock server: write failed: {}", err);
+    }
+    drop(stream.shutdown().await);
+}
+
+// Read the request path, headers (with lowercase names) and body
+async fn read_request(stream: &mut TcpStream) -> Result<(String, BTreeMap<String, String>, String), &'static str> {
+    let mut buf = Vec::new();
+    let header_end = loop {
+        if let Some(pos) = buf.windows(4).position(|window| window == b"\r\n\r\n") {
+            break pos;
+        }
+        let mut chunk = [0u8; 4096];
+        let n = stream.read(&mut chunk).await.map_err(|_| "read failed")?;
+        if n == 0 {
+            return Err("connection closed before end of headers");
+        }
+        buf.extend_from_slice(&chunk[..n]);
+    };
+
+    let head = std::str::from_utf8(&buf[..header_end]).map_err(|_| "headers were not utf8")?;
+    let mut lines = head.split("\r\n");
+    let request_line = lines.next().ok_or("missing request line")?;
+    let path = request_line
+        .split(' ')
+        .nth(1)
+        .ok_or("missing path in request line")?
+        .to_owned();
+    let headers: BTreeMap<String, String> = lines
+        .filter_map(|line| line.split_once(':'))
+        .map(|(name, value)| (name.trim().to_ascii_lowercase(), value.trim().to_owned()))
+        .collect();
+
+    let content_length: usize = headers
+        .get("content-length")
+        .map(|len| len.parse().map_err(|_| "invalid content-length"))
+        .transpose()?
+        .unwrap_or(0);
+    let mut body = buf.split_off(header_end + 4);
+    while body.len() < content_length {
+        let mut chunk = [0u8; 4096];
+        let n = stream.read(&mut chunk).await.map_err(|_| "read failed")?;
+        if n == 0 {
+            return Err("connection closed before end of body");
+        }
+        body.extend_from_slice(&chunk[..n]);
+    }
+    let body = String::from_utf8(body).map_err(|_| "body was not utf8")?;
+
+    Ok((path, headers, body))
+}
+
+// Produce the json response to a request, and record it
+fn handle_request(state: &Mutex<MockState>, path: &str, headers: &BTreeMap<String, String>, body: &str) -> Value {
+    let mut state = state.lock().expect("mutex poisoned");
+
+    // The path looks like /0/public/Time or /0/private/Balance
+    let mut segments = path.rsplit('/');
+    let method = segments.next().unwrap_or_default().to_owned();
+    let private = segments.next() == Some("private");
+    let params: BTreeMap<String, String> = url::form_urlencoded::parse(body.as_bytes()).into_owned().collect();
+
+    let rejected = if private {
+        authenticate(&mut state, path, headers, body, &params).err()
+    } else {
+        None
+    };
+
+    state.requests.push(RecordedRequest {
+        method: method.clone(),
+        private,
+        params,
+        rejected: rejected.clone(),
+    });
+
+    if let Some(err) = rejected {
+        return json!({ "error": [err] });
+    }
+
+    let MockState { handlers, requests, .. } = &mut *state;
+    if let Some(handler) = handlers.get_mut(&method) {
+        let params = &requests.last().expect("request was recorded").params;
+        return json!({ "error": [], "result": handler(params) });
+    }
+
+    if let Some(responses) = state.responses.get_mut(&method)
+        && let Some(response) = if responses.len() > 1 {
+            responses.pop_front()
+        } else {
+            responses.front().cloned()
+        }
+    {
+        return response;
+    }
+
+    match method.as_str() {
+        "GetWebSocketsToken" => json!({
+            "error": [],
+            "result": { "token": MOCK_WS_TOKEN, "expires": 900 },
+        }),
+        _ => json!({ "error": ["EGeneral:Unknown method"] }),
+    }
+}
+
+// The API-Sign of a request, computed independently of the client, as kraken documents it:
+// base64(HMAC-SHA512(secret, path ‖ SHA256(nonce ‖ post data)))
+fn expected_signature(secret: &[u8], path: &str, nonce: &str, body: &str) -> String {
+    let hash = Sha256::digest(format!("{nonce}{body}"));
+    let mut mac = Hmac::<Sha512>::new_from_slice(secret).expect("hmac accepts any key length");
+    mac.update(path.as_bytes());
+    mac.update(&hash);
+    Base64::encode_string(&mac.finalize().into_bytes())
+}
+
+// Check the API-Key and API-Sign headers, and the nonce of a private request
+fn authenticate(
+    state: &mut MockState,
+    path: &str,
+    headers: &BTreeMap<String, String>,
+    body: &str,
+    params: &BTreeMap<String, String>,
+) -> Result<(), String> {
+    if headers.get("api-key") != Some(&state.creds.key) {
+        return Err("EAPI:Invalid key".into());
+    }
+    let nonce: u64 = params
+        .get("nonce")
+        .and_then(|nonce| nonce.parse().ok())
+        .ok_or_else(|| "EAPI:Invalid nonce".to_owned())?;
+    let secret = Base64::decode_vec(&state.creds.secret).map_err(|_| "EAPI:Invalid key".to_owned())?;
+    if headers.get("api-sign") != Some(&expected_signature(&secret, path, params["nonce"].as_str(), body)) {
+        return Err("EAPI:Invalid signature".into());
+    }
+    if state.strict_nonces && nonce <= state.last_nonce {
+        return Err("EAPI:Invalid nonce".into());
+    }
+    state.last_nonce = state.last_nonce.max(nonce);
+    Ok(())
+}
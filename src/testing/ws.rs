@@ -0,0 +1,400 @@
+//! The websockets side of the mock server, speaking the Kraken websockets v1 protocol.
+
+use super::{MOCK_CURRENT_TIME, MOCK_TRIGGER_TIME, MOCK_WS_TOKEN, MockState};
+use futures::{SinkExt, StreamExt};
+use serde_json::{Value, json};
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+use tokio::{
+    net::{TcpListener, TcpStream},
+    sync::broadcast,
+    time,
+};
+use tokio_tungstenite::tungstenite::Message;
+
+/// How the mock server acknowledges a websockets `addOrder` or `editOrder` request
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum WsOrderAck {
+    /// Accept the order, with a generated txid
+    Accept,
+    /// Accept the order, with a particular txid
+    AcceptWithTxid(String),
+    /// Reject the order, with an error message (e.g. "EOrder:Insufficient funds")
+    Reject(String),
+}
+
+/// A message for the server to push to websockets connections
+#[derive(Clone, Debug)]
+pub(crate) enum WsPush {
+    /// Send a snapshot of the book of a pair to its subscribers
+    BookSnapshot { pair: String },
+    /// Send an update of the book of a pair to its subscribers
+    BookUpdate { pair: String, update: Value },
+    /// Send data to subscribers of a private channel
+    Private { channel: &'static str, data: Value },
+    /// Send a message to all connections
+    Raw(Value),
+}
+
+/// Accept websockets connections until the task is dropped
+pub(crate) async fn serve(listener: TcpListener, state: Arc<Mutex<MockState>>, push: broadcast::Sender<WsPush>) {
+    loop {
+        match listener.accept().await {
+            Ok((stream, _addr)) => {
+                tokio::spawn(handle_conn(stream, state.clone(), push.subscribe()));
+            }
+            Err(err) => {
+                log::error!("mock server: accept failed: {}", err);
+            }
+        }
+    }
+}
+
+/// The subscriptions of one websockets connection
+#[derive(Default)]
+struct ConnState {
+    /// Channel ids of public subscriptions (subscription name, pair) -> channel id
+    channel_ids: HashMap<(String, String), u64>,
+    /// Subscribed books: pair -> depth
+    books: HashMap<String, u64>,
+    /// Sequence numbers of subscribed private channels
+    sequences: HashMap<&'static str, u64>,
+}
+
+async fn handle_conn(stream: TcpStream, state: Arc<Mutex<MockState>>, mut push: broadcast::Receiver<WsPush>) {
+    let socket = match tokio_tungstenite::accept_async(stream).await {
+        Ok(socket) => socket,
+        Err(err) => {
+            log::error!("mock server: websockets handshake failed: {}", err);
+            return;
+        }
+    };
+    let (mut sink, mut stream) = socket.split();
+    let mut conn = ConnState::default();
+
+    let mut outgoing = vec![json!({
+        "connectionID": 1,
+        "event": "systemStatus",
+        "status": "online",
+        "version": "1.9.0",
+    })];
+
+    // Kraken sends a heartbeat about every second when there is no other traffic
+    let mut heartbeat = time::interval(Duration::from_secs(1));
+
+    loop {
+        for msg in outgoing.drain(..) {
+            if sink.send(Message::Text(msg.to_string().into())).await.is_err() {
+                return;
+            }
+        }
+        tokio::select! {
+            msg = stream.next() => {
+                match msg {
+                    Some(Ok(Message::Text(text))) => {
+                        outgoing = handle_request(&state, &mut conn, text.as_str());
+                    }
+                    Some(Ok(Message::Close(_))) | Some(Err(_)) | None => return,
+                    Some(Ok(_)) => {}
+                }
+            }
+            msg = push.recv() => {
+                match msg {
+                    Ok(msg) => outgoing = handle_push(&state, &mut conn, msg),
+                    Err(broadcast::error::RecvError::Lagged(_)) => {}
+                    Err(broadcast::error::RecvError::Closed) => return,
+                }
+            }
+            _ = heartbeat.tick() => {
+                outgoing = vec![json!({ "event": "heartbeat" })];
+            }
+        }
+    }
+}
+
+// Handle a request from the client, returning the messages to send in reply
+fn handle_request(state: &Mutex<MockState>, conn: &mut ConnState, text: &str) -> Vec<Value> {
+    let Ok(request) = serde_json::from_str::<Value>(text) else {
+        return vec![json!({ "event": "error", "errorMessage": "Malformed request" })];
+    };
+    let mut state = state.lock().expect("mutex poisoned");
+    state.ws_requests.push(request.clone());
+    if state.ws_silent {
+        return vec![];
+    }
+
+    let event = request["event"].as_str().unwrap_or_default();
+    let reqid = request.get("reqid").cloned();
+    let with_reqid = |mut reply: Value| {
+        if let Some(reqid) = reqid.clone() {
+            reply["reqid"] = reqid;
+        }
+        reply
+    };
+
+    match event {
+        "ping" => vec![with_reqid(json!({ "event": "pong" }))],
+        "subscribe" | "unsubscribe" => {
+            let subscription = &request["subscription"];
+            let name = subscription["name"].as_str().unwrap_or_default().to_owned();
+            let status = if event == "subscribe" {
+                "subscribed"
+            } else {
+                "unsubscribed"
+            };
+            let mut replies = Vec::new();
+            match name.as_str() {
+                "openOrders" | "ownTrades" => {
+                    if subscription["token"].as_str() != Some(MOCK_WS_TOKEN) {
+                        return vec![with_reqid(json!({
+                            "event": "subscriptionStatus",
+                            "status": "error",
+                            "errorMessage": "EGeneral:Invalid arguments:token",
+                            "subscription": { "name": name },
+                        }))];
+                    }
+                    let channel = if name == "openOrders" {
+                        "openOrders"
+                    } else {
+                        "ownTrades"
+                    };
+                    if event == "subscribe" {
+                        conn.sequences.insert(channel, 0);
+                        state.ws_subscriptions.push((name.clone(), None));
+                    } else {
+                        conn.sequences.remove(channel);
+                    }
+                    replies.push(with_reqid(json!({
+                        "channelName": name,
+                        "event": "subscriptionStatus",
+                        "status": status,
+                        "subscription": { "name": name },
+                    })));
+                }
+                "book" | "trade" | "ohlc" => {
+                    let pairs = request["pair"].as_array().cloned().unwrap_or_default();
+                    for pair in pairs.iter().filter_map(Value::as_str) {
+                        let channel_name = match name.as_str() {
+                            "book" => format!("book-{}", subscription["depth"].as_u64().unwrap_or(10)),
+                            "ohlc" => format!("ohlc-{}", subscription["interval"].as_u64().unwrap_or(1)),
+                            _ => name.clone(),
+                        };
+                        let next_id = conn.channel_ids.len() as u64 + 1;
+                        let channel_id = *conn
+                            .channel_ids
+                            .entry((name.clone(), pair.to_owned()))
+                            .or_insert(next_id);
+                        replies.push(with_reqid(json!({
+                            "channelID": channel_id,
+                            "channelName": channel_name,
+                            "event": "subscriptionStatus",
+                            "pair": pair,
+                            "status": status,
+                            "subscription": subscription,
+                        })));
+                        if event == "subscribe" {
+                            state.ws_subscriptions.push((name.clone(), Some(pair.to_owned())));
+                            if name == "book" {
+                                conn.books
+                                    .insert(pair.to_owned(), subscription["depth"].as_u64().unwrap_or(10));
+                                replies.extend(book_snapshot(&state, conn, pair));
+                            }
+                        } else if name == "book" {
+                            conn.books.remove(pair);
+                        }
+                    }
+                }
+                _ => {
+                    replies.push(with_reqid(json!({
+                        "event": "subscriptionStatus",
+                        "status": "error",
+                        "errorMessage": "Subscription name invalid",
+                        "subscription": subscription,
+                    })));
+                }
+            }
+            replies
+        }
+        "addOrder" | "editOrder" | "cancelOrder" | "cancelAll" | "cancelAllOrdersAfter"
+            if request["token"].as_str() != Some(MOCK_WS_TOKEN) =>
+        {
+            vec![with_reqid(json!({
+                "event": format!("{event}Status"),
+                "status": "error",
+                "errorMessage": "EGeneral:Invalid arguments:token",
+            }))]
+        }
+        "addOrder" => {
+            let ack = state.order_acks.pop_front().unwrap_or(WsOrderAck::Accept);
+            let txid = match ack {
+                WsOrderAck::Accept => state.gen_txid(),
+                WsOrderAck::AcceptWithTxid(txid) => txid,
+                WsOrderAck::Reject(err) => {
+                    return vec![with_reqid(json!({
+                        "event": "addOrderStatus",
+                        "status": "error",
+                        "errorMessage": err,
+                    }))];
+                }
+            };
+            let descr = format!(
+                "{} {} {} @ {} {}",
+                request["type"].as_str().unwrap_or_default(),
+                request["volume"].as_str().unwrap_or_default(),
+                request["pair"].as_str().unwrap_or_default(),
+                request["ordertype"].as_str().unwrap_or_default(),
+                request["price"].as_str().unwrap_or_default(),
+            );
+            // Orders which are only validated get no txid
+            if request["validate"].as_str() == Some("true") {
+                return vec![with_reqid(json!({
+                    "event": "addOrderStatus",
+                    "status": "ok",
+                    "descr": descr,
+                }))];
+            }
+            state.open_txids.insert(txid.clone());
+            vec![with_reqid(json!({
+                "event": "addOrderStatus",
+                "status": "ok",
+                "txid": txid,
+                "descr": descr,
+            }))]
+        }
+        "editOrder" => {
+            let original = request["orderid"].as_str().unwrap_or_default().to_owned();
+            if !state.open_txids.contains(&original) {
+                return vec![with_reqid(json!({
+                    "event": "editOrderStatus",
+                    "status": "error",
+                    "errorMessage": "EOrder:Unknown order",
+                }))];
+            }
+            let ack = state.order_acks.pop_front().unwrap_or(WsOrderAck::Accept);
+            let txid = match ack {
+                WsOrderAck::Accept => state.gen_txid(),
+                WsOrderAck::AcceptWithTxid(txid) => txid,
+                WsOrderAck::Reject(err) => {
+                    return vec![with_reqid(json!({
+                        "event": "editOrderStatus",
+                        "status": "error",
+                        "errorMessage": err,
+                    }))];
+                }
+            };
+            let descr = format!(
+                "{} {} @ {}",
+                request["volume"].as_str().unwrap_or_default(),
+                request["pair"].as_str().unwrap_or_default(),
+                request["price"].as_str().unwrap_or_default(),
+            );
+            // Edits which are only validated get no new txid
+            if request["validate"].as_str() == Some("true") {
+                return vec![with_reqid(json!({
+                    "event": "editOrderStatus",
+                    "status": "ok",
+                    "originaltxid": original,
+                    "descr": descr,
+                }))];
+            }
+            state.open_txids.remove(&original);
+            state.open_txids.insert(txid.clone());
+            vec![with_reqid(json!({
+                "event": "editOrderStatus",
+                "status": "ok",
+                "txid": txid,
+                "originaltxid": original,
+                "descr": descr,
+            }))]
+        }
+        "cancelOrder" => {
+            for txid in request["txid"]
+                .as_array()
+                .into_iter()
+                .flatten()
+                .filter_map(Value::as_str)
+            {
+                state.open_txids.remove(txid);
+            }
+            vec![with_reqid(json!({ "event": "cancelOrderStatus", "status": "ok" }))]
+        }
+        "cancelAll" => {
+            let count = state.open_txids.len();
+            state.open_txids.clear();
+            vec![with_reqid(
+                json!({ "event": "cancelAllStatus", "status": "ok", "count": count }),
+            )]
+        }
+        "cancelAllOrdersAfter" => {
+            // Kraken reports a trigger time of "0" when the switch is disabled
+            let trigger_time = match request["timeout"].as_u64().unwrap_or_default() {
+                0 => "0",
+                _ => MOCK_TRIGGER_TIME,
+            };
+            vec![with_reqid(json!({
+                "event": "cancelAllOrdersAfterStatus",
+                "status": "ok",
+                "currentTime": MOCK_CURRENT_TIME,
+                "triggerTime": trigger_time,
+            }))]
+        }
+        _ => vec![with_reqid(
+            json!({ "event": "error", "errorMessage": "Unsupported event" }),
+        )],
+    }
+}
+
+// Handle a message pushed by the test, returning the messages to send on this connection
+fn handle_push(state: &Mutex<MockState>, conn: &mut ConnState, msg: WsPush) -> Vec<Value> {
+    match msg {
+        WsPush::BookSnapshot { pair } => {
+            let state = state.lock().expect("mutex poisoned");
+            book_snapshot(&state, conn, &pair).into_iter().collect()
+        }
+        WsPush::BookUpdate { pair, mut update } => {
+            let Some(depth) = conn.books.get(&pair) else {
+                return vec![];
+            };
+            let channel_id = conn.channel_ids[&("book".to_owned(), pair.clone())];
+            // Kraken sends the ask and bid updates as separate objects, with the checksum in the last
+            let mut objects = Vec::new();
+            if let Some(asks) = update.as_object_mut().and_then(|obj| obj.remove("a")) {
+                objects.push(json!({ "a": asks }));
+            }
+            if let Some(bids) = update.as_object_mut().and_then(|obj| obj.remove("b")) {
+                objects.push(json!({ "b": bids }));
+            }
+            match objects.last_mut() {
+                Some(last) => last["c"] = update["c"].clone(),
+                None => return vec![],
+            }
+            let mut message = vec![json!(channel_id)];
+            message.extend(objects);
+            message.push(json!(format!("book-{depth}")));
+            message.push(json!(pair));
+            vec![Value::Array(message)]
+        }
+        WsPush::Private { channel, data } => {
+            let Some(sequence) = conn.sequences.get_mut(channel) else {
+                return vec![];
+            };
+            *sequence += 1;
+            vec![json!([data, channel, { "sequence": *sequence }])]
+        }
+        WsPush::Raw(message) => vec![message],
+    }
+}
+
+// A snapshot of the book of a pair, if this connection is subscribed to it
+fn book_snapshot(state: &MockState, conn: &ConnState, pair: &str) -> Option<Value> {
+    let depth = *conn.books.get(pair)? as usize;
+    let book = state.books.get(pair)?;
+    let channel_id = conn.channel_ids[&("book".to_owned(), pair.to_owned())];
+    let level = |entry: &crate::ws::BookEntry| json!([entry.price_str, entry.volume_str, entry.timestamp.to_string()]);
+    let asks: Vec<Value> = book.ask.values().take(depth).map(level).collect();
+    let bids: Vec<Value> = book.bid.values().rev().take(depth).map(level).collect();
+    Some(json!([channel_id, { "as": asks, "bs": bids }, format!("book-{depth}"), pair]))
+}
@@ -0,0 +1,538 @@
+//! Client-side validation of orders against the precision, minimums and trading status of asset pairs,
+//! so that orders which Kraken would reject are caught before they are submitted.
+//! <https://support.kraken.com/hc/en-us/articles/360000767986-Cryptocurrency-pairs-trading-minimums>
+//!
+//! An [OrderValidator] is built from the result of the `AssetPairs` API. It can be used directly,
+//! or installed on a `KrakenRestConfig` or `KrakenWsConfig`, in which case orders are validated
+//! (and optionally rounded) before they are sent.
+
+use crate::{
+    AmendOrderRequest, AssetPair, AssetPairStatus, AssetPairsResponse, BsType, EditOrderRequest, Order, OrderFlag,
+    OrderPrice, OrderType,
+};
+use displaydoc::Display;
+use rust_decimal::Decimal;
+use std::{collections::HashMap, str::FromStr};
+
+/// An error found when validating an order against its asset pair
+#[derive(Clone, Debug, Display, Eq, PartialEq)]
+#[non_exhaustive]
+pub enum OrderValidationError {
+    /// Unknown asset pair: {0}
+    UnknownPair(String),
+    /// The asset pair of the amend is not set, so it can't be checked
+    MissingPair,
+    /// Asset pair {0} has no precision and minimums info, which must be requested with the default `info`
+    MissingPairInfo(String),
+    /// Asset pair {pair} is in {status} mode, which does not accept this order
+    PairStatus {
+        /// The asset pair
+        pair: String,
+        /// The trading status of the pair
+        status: AssetPairStatus,
+    },
+    /// Invalid volume: {0}
+    InvalidVolume(String),
+    /// Invalid price: {0}
+    InvalidPrice(String),
+    /// Volume {volume} has more than {lot_decimals} decimal places
+    VolumePrecision {
+        /// The volume of the order
+        volume: Decimal,
+        /// The number of decimal places allowed for volume
+        lot_decimals: u32,
+    },
+    /// Price {price} is not a multiple of the tick size {tick_size}
+    PricePrecision {
+        /// The price of the order
+        price: Decimal,
+        /// The minimum increment between prices
+        tick_size: Decimal,
+    },
+    /// Volume {volume} is below the order minimum {ordermin}
+    OrderMinimum {
+        /// The volume of the order
+        volume: Decimal,
+        /// The minimum volume of an order
+        ordermin: Decimal,
+    },
+    /// Cost {cost} is below the cost minimum {costmin}
+    CostMinimum {
+        /// The cost of the order
+        cost: Decimal,
+        /// The minimum cost of an order
+        costmin: Decimal,
+    },
+}
+
+/// Checks orders against the asset pairs they trade, and optionally rounds them to the pair's precision.
+///
+/// * The pair must be known, and its trading status must accept the order. Pairs in `post_only` mode
+///   accept only post-only limit orders, pairs in `limit_only` mode accept only limit orders, and pairs in
+///   `reduce_only` mode accept only reduce-only orders.
+/// * The volume must have at most `lot_decimals` decimal places, and be at least `ordermin`.
+/// * Fixed prices, including those of the conditional close, must be a multiple of `tick_size`
+///   (or have at most `pair_decimals` decimal places).
+///   Relative prices are resolved by Kraken, and are not checked.
+/// * If the order has a fixed price, its cost (price times volume) must be at least `costmin`.
+///
+/// Orders with the `viqc` flag have their volume in the quote currency, so their volume is only
+/// checked against `costmin`. Settle-position orders are not checked against the minimums.
+///
+/// Edits and amends are checked the same way, for the volumes and prices they change. Their prices are never
+/// rounded, because the side of the order isn't known.
+///
+/// Pairs are looked up by their key, alternate name or websockets name, so the validator can be used
+/// with both REST and websockets orders.
+#[derive(Clone, Debug, Default)]
+pub struct OrderValidator {
+    /// The asset pairs
+    pairs: Vec<(String, AssetPair)>,
+    /// Index into pairs, by key, alternate name and websockets name
+    index: HashMap<String, usize>,
+    /// Whether orders are rounded before they are checked
+    rounding: bool,
+}
+
+impl OrderValidator {
+    /// Create a validator from the result of the `AssetPairs` API.
+    ///
+    /// The pair info must be the default `info`, which includes the precision and minimums. Otherwise the
+    /// precision would default to zero decimal places, so this fails if any pair is missing its `ordermin`.
+    pub fn new(asset_pairs: AssetPairsResponse) -> Result<Self, OrderValidationError> {
+        let mut result = Self::default();
+        for (key, pair) in asset_pairs {
+            if pair.ordermin.is_none() {
+                return Err(OrderValidationError::MissingPairInfo(key));
+            }
+            let idx = result.pairs.len();
+            for name in [Some(&key), pair.alt_name.as_ref(), pair.wsname.as_ref()]
+                .into_iter()
+                .flatten()
+            {
+                result.index.insert(name.clone(), idx);
+            }
+            result.pairs.push((key, pair));
+        }
+        Ok(result)
+    }
+
+    /// Set whether orders are rounded to the precision of their pair before they are checked, when they are
+    /// validated with `apply`. This is disabled by default, so imprecise orders are rejected.
+    pub fn rounding(mut self, rounding: bool) -> Self {
+        self.rounding = rounding;
+        self
+    }
+
+    /// Get the info for an asset pair, by its key, alternate name or websockets name
+    pub fn pair(&self, name: &str) -> Option<&AssetPair> {
+        self.index.get(name).map(|idx| &self.pairs[*idx].1)
+    }
+
+    /// Round the order if rounding is enabled, and then check it
+    pub fn apply(&self, order: Order) -> Result<Order, OrderValidationError> {
+        let order = if self.rounding { self.round(order)? } else { order };
+        self.validate(&order)?;
+        Ok(order)
+    }
+
+    /// Round the volume and fixed prices of an order to the precision of its pair.
+    ///
+    /// Volume is rounded down. Prices are rounded to a tick, down for buy orders and up for sell orders,
+    /// so that a limit price never becomes more aggressive. The prices of the conditional close are rounded
+    /// the same way, for the opposite side.
+    pub fn round(&self, mut order: Order) -> Result<Order, OrderValidationError> {
+        let (_, pair) = self.lookup(&order.pair)?;
+        if !order.oflags.contains(&OrderFlag::Viqc) && !order.volume.is_empty() {
+            let volume = parse_volume(&order.volume)?;
+            order.volume = pair.round_volume(volume).to_string();
+        }
+        for price in [&mut order.price, &mut order.price2] {
+            if let Some(fixed) = parse_price(price)?.and_then(|price| price.fixed()) {
+                *price = pair.round_price(&order.bs_type, fixed).to_string();
+            }
+        }
+        // The conditional close is on the opposite side of the order
+        if let Some(close) = order.close.as_mut() {
+            let close_side = match order.bs_type {
+                BsType::Buy => BsType::Sell,
+                BsType::Sell => BsType::Buy,
+            };
+            for price in [&mut close.price, &mut close.price2] {
+                if let Some(fixed) = parse_price(price)?.and_then(|price| price.fixed()) {
+                    *price = pair.round_price(&close_side, fixed).to_string();
+                }
+            }
+        }
+        Ok(order)
+    }
+
+    /// Check an order against its pair, without modifying it
+    pub fn validate(&self, order: &Order) -> Result<(), OrderValidationError> {
+        let (name, pair) = self.lookup(&order.pair)?;
+        check_status(name, pair, order)?;
+
+        let tick_size = pair.price_increment();
+        let mut fixed_price = None;
+        for price in [&order.price, &order.price2] {
+            if let Some(fixed) = parse_price(price)?.and_then(|price| price.fixed()) {
+                check_tick(fixed, tick_size)?;
+                fixed_price.get_or_insert(fixed);
+            }
+        }
+        if let Some(close) = order.close.as_ref() {
+            for price in [&close.price, &close.price2] {
+                if let Some(fixed) = parse_price(price)?.and_then(|price| price.fixed()) {
+                    check_tick(fixed, tick_size)?;
+                }
+            }
+        }
+
+        if order.ordertype == OrderType::SettlePosition {
+            return Ok(());
+        }
+        let volume = parse_volume(&order.volume)?;
+        if order.oflags.contains(&OrderFlag::Viqc) {
+            return check_costmin(pair, volume);
+        }
+        let lot_decimals = pair.lot_decimals as u32;
+        if volume.normalize().scale() > lot_decimals {
+            return Err(OrderValidationError::VolumePrecision { volume, lot_decimals });
+        }
+        if let Some(ordermin) = pair.ordermin
+            && volume < ordermin
+        {
+            return Err(OrderValidationError::OrderMinimum { volume, ordermin });
+        }
+        match fixed_price {
+            Some(price) => check_costmin(pair, price * volume),
+            None => Ok(()),
+        }
+    }
+
+    /// Round the new volumes of an edit if rounding is enabled, and then check it.
+    ///
+    /// The side of the edited order isn't known, so new prices are only checked, never rounded.
+    pub fn apply_edit(&self, mut request: EditOrderRequest) -> Result<EditOrderRequest, OrderValidationError> {
+        if self.rounding {
+            let (_, pair) = self.lookup(&request.pair)?;
+            round_volume(pair, &mut request.volume)?;
+            round_volume(pair, &mut request.displayvol)?;
+        }
+        self.validate_edit(&request)?;
+        Ok(request)
+    }
+
+    /// Check the new volumes and prices of an edit against the pair of the order, without modifying it
+    pub fn validate_edit(&self, request: &EditOrderRequest) -> Result<(), OrderValidationError> {
+        let (_, pair) = self.lookup(&request.pair)?;
+        let volume = if request.oflags.contains(&OrderFlag::Viqc) {
+            ""
+        } else {
+            &request.volume
+        };
+        check_changes(pair, volume, &request.displayvol, [&request.price, &request.price2])
+    }
+
+    /// Round the new quantities of an amend if rounding is enabled, and then check it.
+    ///
+    /// The amend must have its `pair` set. The side of the amended order isn't known, so new prices
+    /// are only checked, never rounded.
+    pub fn apply_amend(&self, mut request: AmendOrderRequest) -> Result<AmendOrderRequest, OrderValidationError> {
+        if self.rounding {
+            let (_, pair) = self.lookup_amend(&request)?;
+            round_volume(pair, &mut request.order_qty)?;
+            round_volume(pair, &mut request.display_qty)?;
+        }
+        self.validate_amend(&request)?;
+        Ok(request)
+    }
+
+    /// Check the new quantities and prices of an amend against the pair of the order, without modifying it
+    pub fn validate_amend(&self, request: &AmendOrderRequest) -> Result<(), OrderValidationError> {
+        let (_, pair) = self.lookup_amend(request)?;
+        check_changes(
+            pair,
+            &request.order_qty,
+            &request.display_qty,
+            [&request.limit_price, &request.trigger_price],
+        )
+    }
+
+    fn lookup_amend(&self, request: &AmendOrderRequest) -> Result<(&str, &AssetPair), OrderValidationError> {
+        match request.pair.as_deref() {
+            Some(pair) => self.lookup(pair),
+            None => Err(OrderValidationError::MissingPair),
+        }
+    }
+
+    // Find a pair, and the name it is listed under in the AssetPairs response
+    fn lookup(&self, name: &str) -> Result<(&str, &AssetPair), OrderValidationError> {
+        let idx = self
+            .index
+            .get(name)
+            .ok_or_else(|| OrderValidationError::UnknownPair(name.to_owned()))?;
+        let (key, pair) = &self.pairs[*idx];
+        Ok((key, pair))
+    }
+}
+
+impl TryFrom<AssetPairsResponse> for OrderValidator {
+    type Error = OrderValidationError;
+    fn try_from(src: AssetPairsResponse) -> Result<Self, Self::Error> {
+        Self::new(src)
+    }
+}
+
+// Check that the trading status of a pair accepts an order
+fn check_status(name: &str, pair: &AssetPair, order: &Order) -> Result<(), OrderValidationError> {
+    let is_limit = matches!(order.ordertype, OrderType::Limit | OrderType::Iceberg);
+    let accepted = match pair.status {
+        None | Some(AssetPairStatus::Online) => true,
+        Some(AssetPairStatus::CancelOnly) => false,
+        Some(AssetPairStatus::PostOnly) => is_limit && order.oflags.contains(&OrderFlag::Post),
+        Some(AssetPairStatus::LimitOnly) => is_limit,
+        Some(AssetPairStatus::ReduceOnly) => order.reduce_only,
+    };
+    match pair.status {
+        Some(status) if !accepted => Err(OrderValidationError::PairStatus {
+            pair: name.to_owned(),
+            status,
+        }),
+        _ => Ok(()),
+    }
+}
+
+// Check the new volumes and prices of an edit or amend, each of which is empty if it isn't changed
+fn check_changes(
+    pair: &AssetPair,
+    volume: &str,
+    display_volume: &str,
+    prices: [&str; 2],
+) -> Result<(), OrderValidationError> {
+    let tick_size = pair.price_increment();
+    let mut fixed_price = None;
+    for price in prices {
+        if let Some(fixed) = parse_price(price)?.and_then(|price| price.fixed()) {
+            check_tick(fixed, tick_size)?;
+            fixed_price.get_or_insert(fixed);
+        }
+    }
+    let lot_decimals = pair.lot_decimals as u32;
+    if !display_volume.is_empty() {
+        let display_volume = parse_volume(display_volume)?;
+        if display_volume.normalize().scale() > lot_decimals {
+            return Err(OrderValidationError::VolumePrecision {
+                volume: display_volume,
+                lot_decimals,
+            });
+        }
+    }
+    if volume.is_empty() {
+        return Ok(());
+    }
+    let volume = parse_volume(volume)?;
+    if volume.normalize().scale() > lot_decimals {
+        return Err(OrderValidationError::VolumePrecision { volume, lot_decimals });
+    }
+    if let Some(ordermin) = pair.ordermin
+        && volume < ordermin
+    {
+        return Err(OrderValidationError::OrderMinimum { volume, ordermin });
+    }
+    match fixed_price {
+        Some(price) => check_costmin(pair, price * volume),
+        None => Ok(()),
+    }
+}
+
+// Round a volume down to the precision of a pair, unless it is empty
+fn round_volume(pair: &AssetPair, volume: &mut String) -> Result<(), OrderValidationError> {
+    if !volume.is_empty() {
+        *volume = pair.round_volume(parse_volume(volume)?).to_string();
+    }
+    Ok(())
+}
+
+fn check_tick(price: Decimal, tick_size: Decimal) -> Result<(), OrderValidationError> {
+    if (price % tick_size).is_zero() {
+        Ok(())
+    } else {
+        Err(OrderValidationError::PricePrecision { price, tick_size })
+    }
+}
+
+fn check_costmin(pair: &AssetPair, cost: Decimal) -> Result<(), OrderValidationError> {
+    match pair.costmin {
+        Some(costmin) if cost < costmin => Err(OrderValidationError::CostMinimum { cost, costmin }),
+        _ => Ok(()),
+    }
+}
+
+fn parse_volume(src: &str) -> Result<Decimal, OrderValidationError> {
+    Decimal::from_str(src).map_err(|_| OrderValidationError::InvalidVolume(src.to_owned()))
+}
+
+// Parse a price string, which is empty if the order has no such price
+fn parse_price(src: &str) -> Result<Option<OrderPrice>, OrderValidationError> {
+    if src.is_empty() {
+        return Ok(None);
+    }
+    OrderPrice::from_str(src)
+        .map(Some)
+        .map_err(|_| OrderValidationError::InvalidPrice(src.to_owned()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ConditionalClose;
+
+    fn validator() -> OrderValidator {
+        let pair = AssetPair {
+            alt_name: Some("XBTUSD".into()),
+            wsname: Some("XBT/USD".into()),
+            pair_decimals: 1,
+            lot_decimals: 8,
+            ordermin: Some(Decimal::new(1, 4)),
+            costmin: Some(Decimal::new(5, 1)),
+            tick_size: Some(Decimal::new(1, 1)),
+            status: Some(AssetPairStatus::Online),
+            ..Default::default()
+        };
+        let limited = AssetPair {
+            alt_name: Some("ETHUSD".into()),
+            wsname: Some("ETH/USD".into()),
+            pair_decimals: 2,
+            lot_decimals: 8,
+            ordermin: Some(Decimal::new(1, 3)),
+            status: Some(AssetPairStatus::PostOnly),
+            ..Default::default()
+        };
+        OrderValidator::new(
+            [("XXBTZUSD".to_owned(), pair), ("XETHZUSD".to_owned(), limited)]
+                .into_iter()
+                .collect(),
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn test_validate_order() {
+        let validator = validator();
+        let order = Order::limit(BsType::Buy, "0.01", "XBT/USD", "27000.1");
+        validator.validate(&order).unwrap();
+        assert!(validator.pair("XXBTZUSD").is_some());
+        assert!(validator.pair("XBTUSD").is_some());
+
+        let order = Order::limit(BsType::Buy, "0.01", "XBTEUR", "27000.1");
+        assert_eq!(
+            validator.validate(&order),
+            Err(OrderValidationError::UnknownPair("XBTEUR".into()))
+        );
+
+        let order = Order::limit(BsType::Buy, "0.01", "XBTUSD", "27000.15");
+        assert_eq!(
+            validator.validate(&order),
+            Err(OrderValidationError::PricePrecision {
+                price: Decimal::new(2700015, 2),
+                tick_size: Decimal::new(1, 1),
+            })
+        );
+
+        let order = Order::limit(BsType::Buy, "0.000000001", "XBTUSD", "27000.1");
+        assert!(matches!(
+            validator.validate(&order),
+            Err(OrderValidationError::VolumePrecision { lot_decimals: 8, .. })
+        ));
+
+        let order = Order::market(BsType::Sell, "0.00001", "XBTUSD");
+        assert!(matches!(
+            validator.validate(&order),
+            Err(OrderValidationError::OrderMinimum { .. })
+        ));
+
+        let order = Order::limit(BsType::Buy, "0.0001", "XBTUSD", "1000.0");
+        assert_eq!(
+            validator.validate(&order),
+            Err(OrderValidationError::CostMinimum {
+                cost: Decimal::new(1, 1),
+                costmin: Decimal::new(5, 1),
+            })
+        );
+
+        // The prices of the conditional close are checked too
+        let order = Order::limit(BsType::Buy, "0.01", "XBTUSD", "27000.1").close(ConditionalClose::limit("28000.05"));
+        assert_eq!(
+            validator.validate(&order),
+            Err(OrderValidationError::PricePrecision {
+                price: Decimal::new(2800005, 2),
+                tick_size: Decimal::new(1, 1),
+            })
+        );
+
+        // Relative prices are not checked
+        let order = Order::limit(BsType::Buy, "0.01", "XBTUSD", "+1.2345");
+        validator.validate(&order).unwrap();
+
+        // Settle-position orders have no minimums
+        let order = Order::settle_position(BsType::Sell, "0", "XBTUSD", Decimal::new(2, 0));
+        validator.validate(&order).unwrap();
+    }
+
+    #[test]
+    fn test_pair_status() {
+        let validator = validator();
+        let order = Order::limit(BsType::Buy, "1", "ETH/USD", "1800.25");
+        let err = validator.validate(&order).unwrap_err();
+        assert_eq!(
+            err,
+            OrderValidationError::PairStatus {
+                pair: "XETHZUSD".into(),
+                status: AssetPairStatus::PostOnly,
+            }
+        );
+
+        let order = order.oflag(OrderFlag::Post);
+        validator.validate(&order).unwrap();
+    }
+
+    #[test]
+    fn test_missing_pair_info() {
+        // A pair from an AssetPairs request for the `leverage` info
+        let pair = AssetPair {
+            leverage_buy: vec![2, 3],
+            leverage_sell: vec![2, 3],
+            ..Default::default()
+        };
+        let err = OrderValidator::new([("XXBTZUSD".to_owned(), pair)].into_iter().collect()).unwrap_err();
+        assert_eq!(err, OrderValidationError::MissingPairInfo("XXBTZUSD".into()));
+    }
+
+    #[test]
+    fn test_round_order() {
+        let validator = validator().rounding(true);
+        let order = Order::limit(BsType::Buy, "0.0123456789", "XBTUSD", "27000.19");
+        let order = validator.apply(order).unwrap();
+        assert_eq!(order.volume, "0.01234567");
+        assert_eq!(order.price, "27000.1");
+
+        let order = Order::limit(BsType::Sell, "0.01", "XBTUSD", "27000.11");
+        let order = validator.apply(order).unwrap();
+        assert_eq!(order.price, "27000.2");
+
+        // The conditional close is a sell, so its prices are rounded up
+        let order = Order::limit(BsType::Buy, "0.01", "XBTUSD", "27000.1")
+            .close(ConditionalClose::stop_loss_limit("26000.01", "25999.99"));
+        let close = validator.apply(order).unwrap().close.unwrap();
+        assert_eq!(close.price, "26000.1");
+        assert_eq!(close.price2, "26000.0");
+
+        // Rounding doesn't help an order below the minimum
+        let order = Order::limit(BsType::Sell, "0.00001", "XBTUSD", "27000.11");
+        assert!(matches!(
+            validator.apply(order),
+            Err(OrderValidationError::OrderMinimum { .. })
+        ));
+    }
+}
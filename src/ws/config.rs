@@ -1,4 +1,11 @@
-use crate::BuilderError;
+use crate::{BuilderError, OrderValidator};
+use http::Uri;
+use std::sync::Arc;
+
+/// The default url for public websockets feeds
+const DEFAULT_WS_URL: &str = "wss://ws.kraken.com";
+/// The default url for private (authenticated) websockets feeds
+const DEFAULT_WS_AUTH_URL: &str = "wss://ws-auth.kraken.com";
 
 /// Configuration for the websocket connection and feeds to subscribe to
 #[derive(Clone, Debug)]
@@ -16,6 +23,12 @@ pub struct KrakenWsConfig {
     pub(crate) ohlc_interval: u16,
     /// Optional configuration for private feeds
     pub(crate) private: Option<KrakenPrivateWsConfig>,
+    /// Url to connect to when there are no private feeds
+    pub(crate) ws_url: String,
+    /// Url to connect to when there are private feeds
+    pub(crate) ws_auth_url: String,
+    /// The validator to check orders against before placing them (if validation is enabled)
+    pub(crate) order_validator: Option<Arc<OrderValidator>>,
 }
 
 impl KrakenWsConfig {
@@ -34,6 +47,9 @@ impl Default for KrakenWsConfig {
             subscribe_ohlc: Default::default(),
             ohlc_interval: 1,
             private: None,
+            ws_url: DEFAULT_WS_URL.to_owned(),
+            ws_auth_url: DEFAULT_WS_AUTH_URL.to_owned(),
+            order_validator: None,
         }
     }
 }
@@ -120,8 +136,33 @@ impl KrakenWsConfigBuilder {
         self
     }
 
+    /// Set the url to connect to for public feeds. The default is `wss://ws.kraken.com`.
+    /// This can be used to target a proxy, a sandbox environment, or a local mock server.
+    pub fn ws_url(mut self, ws_url: impl Into<String>) -> Self {
+        self.config.ws_url = ws_url.into();
+        self
+    }
+
+    /// Set the url to connect to when a websockets token is set. The default is `wss://ws-auth.kraken.com`.
+    pub fn ws_auth_url(mut self, ws_auth_url: impl Into<String>) -> Self {
+        self.config.ws_auth_url = ws_auth_url.into();
+        self
+    }
+
+    /// Validate orders against their asset pairs before placing them. Orders which fail validation are
+    /// not sent, and an `Error::InvalidOrder` is returned instead. This is disabled by default.
+    pub fn order_validator(mut self, order_validator: OrderValidator) -> Self {
+        self.config.order_validator = Some(Arc::new(order_validator));
+        self
+    }
+
     /// Build a valid KrakenWsConfig if possible
     pub fn build(self) -> Result<KrakenWsConfig, BuilderError> {
+        for url in [&self.config.ws_url, &self.config.ws_auth_url] {
+            if url.parse::<Uri>().is_err() {
+                return Err(BuilderError::InvalidUrl(url.clone()));
+            }
+        }
         if let Some(private) = self.config.private.as_ref()
             && private.token.is_empty()
         {
@@ -1,11 +1,12 @@
 use super::{
     config::KrakenWsConfig,
     messages::{
-        AddOrderRequest, BsType, OrderInfo, OrderInfoPartialUpdate, OrderStatus, OwnTrade, SubscriptionStatus,
-        SystemStatus,
+        AddOrderRequest, BsType, EditOrderRequest, OrderInfo, OrderInfoPartialUpdate, OrderStatus, OwnTrade,
+        SubscriptionStatus, SystemStatus,
     },
     types::{BookData, Candle, PublicTrade, SubscriptionType},
 };
+use crate::{CancelAllOrdersAfterResponse, KrakenError};
 use futures::{
     SinkExt, StreamExt,
     stream::{SplitSink, SplitStream},
@@ -68,11 +69,16 @@ pub struct KrakenWsClient {
     /// Track subscription statuses of different channels
     subscription_tracker: SubscriptionTracker,
     /// Result senders for add_order calls
-    add_order_result_senders: HashMap<u64, oneshot::Sender<Result<String, String>>>,
+    add_order_result_senders: HashMap<u64, oneshot::Sender<Result<String, KrakenError>>>,
+    /// Result senders for edit_order calls
+    edit_order_result_senders: HashMap<u64, oneshot::Sender<Result<String, KrakenError>>>,
     /// Result senders for cancel_order calls
-    cancel_order_result_senders: HashMap<u64, oneshot::Sender<Result<(), String>>>,
+    cancel_order_result_senders: HashMap<u64, oneshot::Sender<Result<(), KrakenError>>>,
     /// Result senders for cancel_all_orders calls
-    cancel_all_orders_result_senders: HashMap<u64, oneshot::Sender<Result<u64, String>>>,
+    cancel_all_orders_result_senders: HashMap<u64, oneshot::Sender<Result<u64, KrakenError>>>,
+    /// Result senders for cancel_all_orders_after calls
+    cancel_all_orders_after_result_senders:
+        HashMap<u64, oneshot::Sender<Result<CancelAllOrdersAfterResponse, KrakenError>>>,
     /// Client req id ensures unique ids for different requests we make to kraken
     client_req_id: AtomicU64,
     /// The last time if any that we got a message from Kraken, including heartbeats
@@ -98,9 +104,9 @@ impl KrakenWsClient {
     ///   Note: [crate::ws::KrakenWsAPI] also conceals this detail.
     pub async fn new(config: KrakenWsConfig) -> Result<(Self, SplitStream<WsClient>, Arc<WsAPIResults>), Error> {
         let url: Uri = if config.private.is_some() {
-            "wss://ws-auth.kraken.com".parse().unwrap()
+            config.ws_auth_url.parse()?
         } else {
-            "wss://ws.kraken.com".parse().unwrap()
+            config.ws_url.parse()?
         };
         let (socket, _request) = tokio_tungstenite::connect_async(url).await?;
         let (sink, stream) = socket.split();
@@ -126,8 +132,10 @@ impl KrakenWsClient {
             output: output.clone(),
             subscription_tracker: Default::default(),
             add_order_result_senders: Default::default(),
+            edit_order_result_senders: Default::default(),
             cancel_order_result_senders: Default::default(),
             cancel_all_orders_result_senders: Default::default(),
+            cancel_all_orders_after_result_senders: Default::default(),
             client_req_id: Default::default(),
             last_msg_received: None,
             last_outstanding_ping: None,
@@ -334,7 +342,7 @@ impl KrakenWsClient {
     pub async fn add_order(
         &mut self,
         mut order: AddOrderRequest,
-        result_sender: oneshot::Sender<Result<String, String>>,
+        result_sender: oneshot::Sender<Result<String, KrakenError>>,
     ) -> Result<(), Error> {
         let token = if let Some(private_config) = self.config.private.as_ref() {
             private_config.token.clone()
@@ -366,6 +374,47 @@ impl KrakenWsClient {
         Ok(())
     }
 
+    /// Submit a request to edit an order over the websocket
+    ///
+    /// The oneshot::Sender is sent Ok if the edit is confirmed from Kraken,
+    /// and the TxID of the new order is returned. The error message from kraken is
+    /// returned otherwise. The sender gets nothing if we fail to submit the request
+    /// at all.
+    pub async fn edit_order(
+        &mut self,
+        mut request: EditOrderRequest,
+        result_sender: oneshot::Sender<Result<String, KrakenError>>,
+    ) -> Result<(), Error> {
+        let token = if let Some(private_config) = self.config.private.as_ref() {
+            private_config.token.clone()
+        } else {
+            log::error!("Tried to edit an order, but this is not an authenticated channel");
+            // Drop the result_sender and do not signal an error to the websocket
+            return Ok(());
+        };
+
+        let client_req_id = self.client_req_id.fetch_add(1, Ordering::SeqCst);
+        request.event = "editOrder".into();
+        request.reqid = Some(client_req_id);
+        request.token = token;
+
+        // This drops the result_sender if serialization or sending fails
+        match serde_json::to_string(&request) {
+            Err(err) => {
+                log::error!("Could not serialize edit order request: {}", err);
+                return Ok(());
+            }
+            Ok(text) => {
+                // We have to store the result_sender before awaiting
+                self.edit_order_result_senders.insert(client_req_id, result_sender);
+                self.sink.send(Message::Text(text.into())).await.inspect_err(|_err| {
+                    self.edit_order_result_senders.remove(&client_req_id);
+                })?;
+            }
+        }
+        Ok(())
+    }
+
     /// Submit a request to cancel an order over the websocket
     ///
     /// TxID may be a string used to identify an order, or a user-ref-id
@@ -376,7 +425,7 @@ impl KrakenWsClient {
     pub async fn cancel_order(
         &mut self,
         txid: String,
-        result_sender: oneshot::Sender<Result<(), String>>,
+        result_sender: oneshot::Sender<Result<(), KrakenError>>,
     ) -> Result<(), Error> {
         let token = if let Some(private_config) = self.config.private.as_ref() {
             private_config.token.clone()
@@ -417,7 +466,7 @@ impl KrakenWsClient {
     /// if we fail to submit the request at all.
     pub async fn cancel_all_orders(
         &mut self,
-        result_sender: oneshot::Sender<Result<u64, String>>,
+        result_sender: oneshot::Sender<Result<u64, KrakenError>>,
     ) -> Result<(), Error> {
         let token = if let Some(private_config) = self.config.private.as_ref() {
             private_config.token.clone()
@@ -450,6 +499,51 @@ impl KrakenWsClient {
         Ok(())
     }
 
+    /// Submit a request to cancel all orders after a timeout over the websocket.
+    /// This is a dead man's switch, which must be triggered again before the timeout elapses.
+    /// A timeout of 0 disables it.
+    ///
+    /// The oneshot::Sender is sent Ok if the request is successful, with
+    /// the current time and trigger time. The sender gets the error message from
+    /// kraken otherwise. The sender gets nothing
+    /// if we fail to submit the request at all.
+    pub async fn cancel_all_orders_after(
+        &mut self,
+        timeout: u64,
+        result_sender: oneshot::Sender<Result<CancelAllOrdersAfterResponse, KrakenError>>,
+    ) -> Result<(), Error> {
+        let token = if let Some(private_config) = self.config.private.as_ref() {
+            private_config.token.clone()
+        } else {
+            log::error!("Tried to set the dead man's switch, but this is not an authenticated channel");
+            // Drop the result_sender and do not signal an error to the websocket
+            return Ok(());
+        };
+
+        let client_req_id = self.client_req_id.fetch_add(1, Ordering::SeqCst);
+
+        let payload = json! ({
+            "event": "cancelAllOrdersAfter",
+            "token": token,
+            "timeout": timeout,
+            "reqid": client_req_id,
+        });
+
+        // We have to store the result_sender before awaiting
+        self.cancel_all_orders_after_result_senders
+            .insert(client_req_id, result_sender);
+
+        // This drops the result_sender if sending fails
+        self.sink
+            .send(Message::Text(payload.to_string().into()))
+            .await
+            .inspect_err(|_err| {
+                self.cancel_all_orders_after_result_senders.remove(&client_req_id);
+            })?;
+
+        Ok(())
+    }
+
     /// Send a ping to the kraken server. This is an application-level ping
     /// and not a websockets ping.
     ///
@@ -676,6 +770,10 @@ impl KrakenWsClient {
                         if let Err(err) = self.handle_add_order_status(map) {
                             log::error!("handling add order status: {}\n{}", err, text)
                         }
+                    } else if event == "editOrderStatus" {
+                        if let Err(err) = self.handle_edit_order_status(map) {
+                            log::error!("handling edit order status: {}\n{}", err, text)
+                        }
                     } else if event == "cancelOrderStatus" {
                         if let Err(err) = self.handle_cancel_order_status(map) {
                             log::error!("handling cancel order status: {}\n{}", err, text)
@@ -684,6 +782,10 @@ impl KrakenWsClient {
                         if let Err(err) = self.handle_cancel_all_orders_status(map) {
                             log::error!("handling cancel all order status: {}\n{}", err, text)
                         }
+                    } else if event == "cancelAllOrdersAfterStatus" {
+                        if let Err(err) = self.handle_cancel_all_orders_after_status(map) {
+                            log::error!("handling cancel all orders after status: {}\n{}", err, text)
+                        }
                     } else if event == "pong" {
                         if let Err(err) = self.handle_pong(map) {
                             log::error!("handling pong: {}\n{}", err, text)
@@ -1221,11 +1323,54 @@ impl KrakenWsClient {
                 .as_str()
                 .ok_or("errorMessage wasnt a string")?;
             log::error!("add_order: {}", err_msg);
-            drop(sender.send(Err(err_msg.to_string())));
+            drop(sender.send(Err(KrakenError::parse(err_msg))));
             Ok(())
         } else {
             log::error!("unexpected status: {}", status);
-            drop(sender.send(Err(format!("unexpected status: {}", status))));
+            // This is not an error from kraken, so the receiver produces no value
+            drop(sender);
+            Err("unexpected status")
+        }
+    }
+
+    fn handle_edit_order_status(&mut self, map: serde_json::Map<String, Value>) -> Result<(), &'static str> {
+        let req_id = map
+            .get("reqid")
+            .ok_or("missing req_id field")?
+            .as_u64()
+            .ok_or("reqid wasnt an integer")?;
+        let sender = self
+            .edit_order_result_senders
+            .remove(&req_id)
+            .ok_or("unknown edit_order reqid")?;
+        let status = map
+            .get("status")
+            .ok_or("missing status field")?
+            .as_str()
+            .ok_or("status wasnt a string")?;
+        if status == "ok" {
+            // txid of the new order is omitted when validate=true, so fall back to the original
+            let tx_id = map
+                .get("txid")
+                .or_else(|| map.get("originaltxid"))
+                .map(|val| val.as_str().ok_or("txid wasnt a string"))
+                .transpose()?
+                .unwrap_or_default();
+            drop(sender.send(Ok(tx_id.to_string())));
+            Ok(())
+        } else if status == "error" {
+            let err_msg = map
+                .get("errorMessage")
+                .ok_or("missing errorMessage field")?
+                .as_str()
+                .ok_or("errorMessage wasnt a string")?;
+            log::error!("edit_order: {}", err_msg);
+            drop(sender.send(Err(KrakenError::parse(err_msg))));
+            Ok(())
+        } else {
+            log::error!("unexpected status: {}", status);
+            // This is not an error from kraken, so the receiver produces no value
+            drop(sender);
             Err("unexpected status")
         }
     }
@@ -1261,11 +1406,12 @@ impl KrakenWsClient {
                 .as_str()
                 .ok_or("errorMessage wasnt a string")?;
             log::error!("cancel_order: {}", err_msg);
-            drop(sender.send(Err(err_msg.to_string())));
+            drop(sender.send(Err(KrakenError::parse(err_msg))));
             Ok(())
         } else {
             log::error!("unexpected status: {}", status);
-            drop(sender.send(Err(format!("unexpected status: {}", status))));
+            // This is not an error from kraken, so the receiver produces no value
+            drop(sender);
             Err("unexpected status")
         }
     }
@@ -1300,11 +1446,52 @@ impl KrakenWsClient {
                 .as_str()
                 .ok_or("errorMessage wasnt a string")?;
             log::error!("cancel_all_orders: {}", err_msg);
-            drop(sender.send(Err(err_msg.to_string())));
+            drop(sender.send(Err(KrakenError::parse(err_msg))));
+            Ok(())
+        } else {
+            log::error!("unexpected status: {}", status);
+            // This is not an error from kraken, so the receiver produces no value
+            drop(sender);
+            Err("unexpected status")
+        }
+    }
+
+    fn handle_cancel_all_orders_after_status(
+        &mut self,
+        map: serde_json::Map<String, Value>,
+    ) -> Result<(), &'static str> {
+        let req_id = map
+            .get("reqid")
+            .ok_or("missing req_id field")?
+            .as_u64()
+            .ok_or("reqid wasnt an integer")?;
+        let sender = self
+            .cancel_all_orders_after_result_senders
+            .remove(&req_id)
+            .ok_or("unknown cancel_all_orders_after reqid")?;
+        let status = map
+            .get("status")
+            .ok_or("missing status field")?
+            .as_str()
+            .ok_or("status wasnt a string")?;
+        if status == "ok" {
+            let response: CancelAllOrdersAfterResponse =
+                serde_json::from_value(Value::Object(map)).map_err(|_| "missing currentTime or triggerTime field")?;
+            drop(sender.send(Ok(response)));
+            Ok(())
+        } else if status == "error" {
+            let err_msg = map
+                .get("errorMessage")
+                .ok_or("missing errorMessage field")?
+                .as_str()
+                .ok_or("errorMessage wasnt a string")?;
+            log::error!("cancel_all_orders_after: {}", err_msg);
+            drop(sender.send(Err(KrakenError::parse(err_msg))));
             Ok(())
         } else {
             log::error!("unexpected status: {}", status);
-            drop(sender.send(Err(format!("unexpected status: {}", status))));
+            // This is not an error from kraken, so the receiver produces no value
+            drop(sender);
             Err("unexpected status")
         }
     }
@@ -5,7 +5,10 @@
 //! because there are actually slight differences in the schemas and strings
 //! used which make them incompatible, and the two APIs are versioned separately.
 
-use crate::serde_helpers::{comma_separated, display_fromstr};
+use crate::{
+    OrderPrice, OrderTime, OrderTrigger, SelfTradePrevention, TimeInForce,
+    serde_helpers::{comma_separated, display_fromstr},
+};
 use displaydoc::Display;
 use rust_decimal::Decimal;
 use serde::{Deserialize, Serialize, Serializer};
@@ -113,10 +116,33 @@ pub enum OrderType {
     StopLossLimit,
     /// Take-Profit-Limit
     TakeProfitLimit,
+    /// Trailing-Stop
+    TrailingStop,
+    /// Trailing-Stop-Limit
+    TrailingStopLimit,
+    /// Iceberg
+    Iceberg,
     /// Settle-Position
     SettlePosition,
 }
 
+impl From<crate::OrderType> for OrderType {
+    fn from(src: crate::OrderType) -> Self {
+        match src {
+            crate::OrderType::Market => Self::Market,
+            crate::OrderType::Limit => Self::Limit,
+            crate::OrderType::StopLoss => Self::StopLoss,
+            crate::OrderType::TakeProfit => Self::TakeProfit,
+            crate::OrderType::StopLossLimit => Self::StopLossLimit,
+            crate::OrderType::TakeProfitLimit => Self::TakeProfitLimit,
+            crate::OrderType::TrailingStop => Self::TrailingStop,
+            crate::OrderType::TrailingStopLimit => Self::TrailingStopLimit,
+            crate::OrderType::Iceberg => Self::Iceberg,
+            crate::OrderType::SettlePosition => Self::SettlePosition,
+        }
+    }
+}
+
 /// Possible order statuses in Kraken.
 /// These are kebab-case strings in json
 #[derive(Debug, Display, Clone, Serialize, Deserialize, Ord, PartialOrd, Eq, PartialEq)]
@@ -157,6 +183,9 @@ pub struct OrderInfoPartialUpdate {
 pub struct OrderInfo {
     /// User reference id for the order
     pub userref: UserRefId,
+    /// Client order id for the order, if it has one
+    #[serde(default)]
+    pub cl_ord_id: Option<String>,
     /// Status of the order
     pub status: OrderStatus,
     /// unix timestamp of when the order was placed
@@ -271,9 +300,9 @@ pub struct OrderDescriptionInfo {
     /// order type
     pub ordertype: OrderType,
     /// primary price
-    pub price: Decimal,
+    pub price: OrderPrice,
     /// secondary price
-    pub price2: Decimal,
+    pub price2: OrderPrice,
     /// leverage
     pub leverage: Option<Decimal>,
     /// human-readable description
@@ -306,16 +335,92 @@ pub struct AddOrderRequest {
     /// price
     #[serde(skip_serializing_if = "String::is_empty")]
     pub price: String,
+    /// secondary price (the limit price of stop-loss-limit and take-profit-limit orders)
+    #[serde(skip_serializing_if = "String::is_empty")]
+    pub price2: String,
+    /// price signal which triggers stop-loss and take-profit orders
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub trigger: Option<OrderTrigger>,
+    /// visible volume of iceberg orders
+    #[serde(skip_serializing_if = "String::is_empty")]
+    pub displayvol: String,
     /// order flags (comma separated list)
     #[serde(with = "comma_separated")]
     #[serde(skip_serializing_if = "BTreeSet::is_empty")]
     pub oflags: BTreeSet<OrderFlag>,
+    /// amount of leverage (margin orders only)
+    #[serde(skip_serializing_if = "String::is_empty")]
+    pub leverage: String,
+    /// If true, the order can only reduce an open margin position
+    #[serde(skip_serializing_if = "core::ops::Not::not")]
+    #[serde(with = "display_fromstr")]
+    pub reduce_only: bool,
+    /// self-trade prevention
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub stptype: Option<SelfTradePrevention>,
+    /// time-in-force
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub timeinforce: Option<TimeInForce>,
+    /// scheduled start time
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub starttm: Option<OrderTime>,
+    /// expiration time
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub expiretm: Option<OrderTime>,
+    /// RFC3339 timestamp after which the matching engine should reject the order
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub deadline: Option<String>,
+    /// conditional close order type
+    #[serde(rename = "close[ordertype]", skip_serializing_if = "Option::is_none")]
+    pub close_ordertype: Option<OrderType>,
+    /// conditional close order price
+    #[serde(rename = "close[price]", skip_serializing_if = "String::is_empty")]
+    pub close_price: String,
+    /// conditional close order secondary price
+    #[serde(rename = "close[price2]", skip_serializing_if = "String::is_empty")]
+    pub close_price2: String,
     /// validate: If true, do not submit order
     #[serde(skip_serializing_if = "core::ops::Not::not")]
     #[serde(with = "display_fromstr")]
     pub validate: bool,
 }
 
+/// Edit order request (websockets)
+#[derive(Default, Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
+pub struct EditOrderRequest {
+    /// The event will be "editOrder"
+    pub event: String,
+    /// The token used to authenticate
+    pub token: String,
+    /// A req-id associated to the request
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reqid: Option<u64>,
+    /// TxId (or user ref id) of the order to edit
+    pub orderid: String,
+    /// pair (AssetPair id or altname)
+    pub pair: String,
+    /// new volume (in lots)
+    #[serde(skip_serializing_if = "String::is_empty")]
+    pub volume: String,
+    /// new price
+    #[serde(skip_serializing_if = "String::is_empty")]
+    pub price: String,
+    /// new secondary price
+    #[serde(skip_serializing_if = "String::is_empty")]
+    pub price2: String,
+    /// new order flags (comma separated list)
+    #[serde(with = "comma_separated")]
+    #[serde(skip_serializing_if = "BTreeSet::is_empty")]
+    pub oflags: BTreeSet<OrderFlag>,
+    /// new user ref id
+    #[serde(serialize_with = "user_ref_ser", skip_serializing_if = "Option::is_none")]
+    pub newuserref: Option<UserRefId>,
+    /// validate: If true, do not submit the edit
+    #[serde(skip_serializing_if = "core::ops::Not::not")]
+    #[serde(with = "display_fromstr")]
+    pub validate: bool,
+}
+
 fn user_ref_ser<S>(src: &Option<UserRefId>, ser: S) -> Result<S::Ok, S::Error>
 where
     S: Serializer,
@@ -337,6 +442,9 @@ pub struct OwnTrade {
     pub trade_id: String,
     /// the unique id for the order this trade corresponds to
     pub ordertxid: String,
+    /// the client order id of the order this trade corresponds to, if it has one
+    #[serde(default)]
+    pub cl_ord_id: Option<String>,
     /// the asset pair the trade was made in
     pub pair: String,
     /// type of trade (buy/sell)
@@ -380,4 +488,40 @@ mod tests {
         assert_eq!(val.pair, "XBT/EUR");
         assert_eq!(val.bs_type, BsType::Sell);
     }
+
+    #[test]
+    fn deserialize_trailing_stop_order_info() {
+        let json = r#"{
+        "avg_price": "0.00000",
+        "cost": "0.00000",
+        "descr": {
+          "close": null,
+          "leverage": null,
+          "order": "sell 0.50000000 XBT/USD @ trailing stop +2.0000%",
+          "ordertype": "trailing-stop",
+          "pair": "XBT/USD",
+          "price": "+2.0000%",
+          "price2": "0.00000",
+          "type": "sell"
+        },
+        "expiretm": null,
+        "fee": "0.00000",
+        "limitprice": "0.00000",
+        "misc": "",
+        "oflags": "fciq",
+        "opentm": "1688669448.123456",
+        "refid": null,
+        "starttm": null,
+        "status": "open",
+        "stopprice": "0.00000",
+        "userref": 0,
+        "vol": "0.50000000",
+        "vol_exec": "0.00000000"
+      }"#;
+        let val: OrderInfo = serde_json::from_str(json).unwrap();
+
+        assert_eq!(val.descr.ordertype, OrderType::TrailingStop);
+        assert_eq!(val.descr.price, OrderPrice::PlusPercent("2.0000".parse().unwrap()));
+        assert_eq!(val.descr.price2.fixed(), Some(Decimal::ZERO));
+    }
 }
@@ -5,7 +5,10 @@
 //! See also: <https://tokio.rs/tokio/topics/bridging>
 //! and the `reqwest::blocking` module
 
-use crate::{LimitOrder, MarketOrder};
+use crate::{
+    CancelAllOrdersAfterResponse, KrakenError, LimitOrder, MarketOrder, Order, OrderValidator,
+    order::{check_cancel_batch, check_order_batch},
+};
 use futures::stream::StreamExt;
 use std::sync::{Arc, atomic::Ordering};
 use std::{
@@ -42,6 +45,8 @@ pub struct KrakenWsAPI {
     sender: mpsc::UnboundedSender<LocalRequest>,
     // Handle to the output of the worker thread
     output: Arc<WsAPIResults>,
+    // The validator to check orders against before placing them
+    order_validator: Option<Arc<OrderValidator>>,
 }
 
 impl KrakenWsAPI {
@@ -62,6 +67,7 @@ impl KrakenWsAPI {
         // panics.
         let rt = runtime::Builder::new_current_thread().enable_all().build().unwrap();
 
+        let order_validator = src.order_validator.clone();
         let (mut client, mut stream, output) = rt.block_on(KrakenWsClient::new(src))?;
         let (sender, mut receiver) = mpsc::unbounded_channel();
 
@@ -103,12 +109,19 @@ impl KrakenWsAPI {
                                         return;
                                     }
                                     Some(LocalRequest::AddOrder{request, result_sender}) => {
-                                        if let Err(err) = client.add_order(request, result_sender).await {
+                                        if let Err(err) = client.add_order(*request, result_sender).await {
                                             log::error!("error submitting an order, closing stream: {}", err);
                                             drop(client.close().await);
                                             return;
                                         }
                                     }
+                                    Some(LocalRequest::EditOrder{request, result_sender}) => {
+                                        if let Err(err) = client.edit_order(*request, result_sender).await {
+                                            log::error!("error editing an order, closing stream: {}", err);
+                                            drop(client.close().await);
+                                            return;
+                                        }
+                                    }
                                     Some(LocalRequest::CancelOrder{tx_id, result_sender}) => {
                                         if let Err(err) = client.cancel_order(tx_id, result_sender).await {
                                             log::error!("error canceling an order, closing stream: {}", err);
@@ -123,6 +136,13 @@ impl KrakenWsAPI {
                                             return;
                                         }
                                     }
+                                    Some(LocalRequest::CancelAllOrdersAfter{timeout, result_sender}) => {
+                                        if let Err(err) = client.cancel_all_orders_after(timeout, result_sender).await {
+                                            log::error!("error setting cancel all orders after, closing stream: {}", err);
+                                            drop(client.close().await);
+                                            return;
+                                        }
+                                    }
                                 }
                             }
                             _ = interval.tick() => {
@@ -158,6 +178,7 @@ impl KrakenWsAPI {
             worker_thread,
             sender,
             output,
+            order_validator,
         })
     }
 
@@ -233,12 +254,15 @@ impl KrakenWsAPI {
         self.output.stream_closed.load(Ordering::SeqCst)
     }
 
-    /// Submit a market order over the websockets connection.
+    /// Submit an order of any type over the websockets connection.
     /// This must be a private connection configured with the auth token.
     ///
+    /// Kraken's websockets API (v1) has no `cl_ord_id` field on addOrder, so orders with a client order id
+    /// are not sent, and an `Error::WsUnsupported` is returned instead.
+    /// Orders which need a client order id should be placed through the REST API.
+    ///
     /// Arguments:
-    /// market_order: The market order to place
-    /// user_ref_id: The user-ref-id to associate to this order. Orders may be filtered or canceled by user-ref-id.
+    /// order: The order to place
     /// validate: If true, we just validate that the order was well formed and the order doesn't actually hit the books.
     ///
     /// Returns:
@@ -246,27 +270,65 @@ impl KrakenWsAPI {
     /// The Receiver produces no value if the order could not be successfully placed, and this will be logged.
     /// The Receiver may be dropped if you don't care about the errors -- these error messages will be logged regardless.
     /// The return value will be None if the stream is already closed.
-    pub fn add_market_order(
+    /// If an order validator is configured and the order fails validation, an error is returned,
+    /// and the order is not sent.
+    pub fn add_order(
         &self,
-        market_order: MarketOrder,
-        user_ref_id: Option<i32>,
+        order: Order,
         validate: bool,
-    ) -> Option<oneshot::Receiver<Result<String, String>>> {
+    ) -> crate::Result<Option<oneshot::Receiver<Result<String, KrakenError>>>> {
+        let order = self.check_order(order)?;
+        Ok(self.send_order(order, validate))
+    }
+
+    // Check that an order can be sent over websockets, and apply the order validator (if any) to it
+    fn check_order(&self, order: Order) -> crate::Result<Order> {
+        if let Some(cl_ord_id) = order.cl_ord_id.as_ref() {
+            return Err(crate::Error::WsUnsupported(format!("client order id {cl_ord_id}")));
+        }
+        Ok(match self.order_validator.as_deref() {
+            Some(validator) => validator.apply(order)?,
+            None => order,
+        })
+    }
+
+    // Send an order which has been checked to the worker thread
+    fn send_order(&self, order: Order, validate: bool) -> Option<oneshot::Receiver<Result<String, KrakenError>>> {
         let (result_sender, result_receiver) = oneshot::channel();
+        let (close_ordertype, close_price, close_price2) = match order.close {
+            Some(close) => (Some(close.ordertype.into()), close.price, close.price2),
+            None => Default::default(),
+        };
         let request = AddOrderRequest {
-            ordertype: OrderType::Market,
-            bs_type: market_order.bs_type.into(),
-            volume: market_order.volume,
-            pair: market_order.pair,
-            price: Default::default(),
-            oflags: market_order.oflags.into_iter().map(OrderFlag::from).collect(),
-            userref: user_ref_id,
+            ordertype: order.ordertype.into(),
+            bs_type: order.bs_type.into(),
+            volume: order.volume,
+            pair: order.pair,
+            price: order.price,
+            price2: order.price2,
+            displayvol: order.displayvol,
+            trigger: order.trigger,
+            oflags: order.oflags.into_iter().map(OrderFlag::from).collect(),
+            leverage: order.leverage.map(|leverage| leverage.to_string()).unwrap_or_default(),
+            reduce_only: order.reduce_only,
+            stptype: order.stptype,
+            timeinforce: order.timeinforce,
+            starttm: order.starttm,
+            expiretm: order.expiretm,
+            deadline: order.deadline,
+            close_ordertype,
+            close_price,
+            close_price2,
+            userref: order.userref,
             validate,
             ..Default::default()
         };
         if self
             .sender
-            .send(LocalRequest::AddOrder { request, result_sender })
+            .send(LocalRequest::AddOrder {
+                request: Box::new(request),
+                result_sender,
+            })
             .is_ok()
         {
             Some(result_receiver)
@@ -275,6 +337,63 @@ impl KrakenWsAPI {
         }
     }
 
+    /// Submit a batch of orders over the websockets connection.
+    /// This must be a private connection configured with the auth token.
+    ///
+    /// The batch must follow the same rules as over REST: at most `MAX_ORDER_BATCH` orders, all in the same pair.
+    /// Kraken's websockets API has no batch request though, so each order is submitted in turn,
+    /// and some orders in the batch may be placed even if others fail.
+    ///
+    /// Arguments:
+    /// orders: The orders to place
+    /// validate: If true, we just validate that the orders were well formed and the orders don't actually hit the books.
+    ///
+    /// Returns:
+    /// A oneshot::Receiver for each order which was submitted, in order, as for `add_order`.
+    /// If the stream closes partway through, the rest of the orders are not submitted, so there are fewer
+    /// receivers than orders. (There are none if the stream is already closed.)
+    /// An error is returned if the batch is not allowed, or if any order in it fails the checks made by
+    /// `add_order`, and then no orders are submitted.
+    pub fn add_order_batch(
+        &self,
+        orders: Vec<Order>,
+        validate: bool,
+    ) -> crate::Result<Vec<oneshot::Receiver<Result<String, KrakenError>>>> {
+        check_order_batch(&orders)?;
+        // Check every order before sending any, so that an invalid order rejects the whole batch
+        let orders = orders
+            .into_iter()
+            .map(|order| self.check_order(order))
+            .collect::<crate::Result<Vec<_>>>()?;
+        Ok(orders
+            .into_iter()
+            .map_while(|order| self.send_order(order, validate))
+            .collect())
+    }
+
+    /// Submit a market order over the websockets connection.
+    /// This must be a private connection configured with the auth token.
+    ///
+    /// Arguments:
+    /// market_order: The market order to place
+    /// user_ref_id: The user-ref-id to associate to this order. Orders may be filtered or canceled by user-ref-id.
+    /// validate: If true, we just validate that the order was well formed and the order doesn't actually hit the books.
+    ///
+    /// Returns:
+    /// See `add_order`
+    pub fn add_market_order(
+        &self,
+        market_order: MarketOrder,
+        user_ref_id: Option<i32>,
+        validate: bool,
+    ) -> crate::Result<Option<oneshot::Receiver<Result<String, KrakenError>>>> {
+        let order = Order {
+            userref: user_ref_id,
+            ..market_order.into()
+        };
+        self.add_order(order, validate)
+    }
+
     /// Submit a limit order over the websockets connection.
     /// This must be a private connection configured with the auth token.
     ///
@@ -284,36 +403,78 @@ impl KrakenWsAPI {
     /// validate: If true, we just validate that the order was well formed and the order doesn't actually hit the books.
     ///
     /// Returns:
-    /// A oneshot::Reciever which yields either the TxID for the placed order, or an error message from kraken.
-    /// The Receiver produces no value if the order could not be successfully placed, and this will be logged.
-    /// The Receiver may be dropped if you don't care about the errors -- these error messages will be logged regardless.
-    /// The return value will be None if the stream is already closed.
+    /// See `add_order`
     pub fn add_limit_order(
         &self,
         limit_order: LimitOrder,
         user_ref_id: Option<i32>,
         validate: bool,
-    ) -> Option<oneshot::Receiver<Result<String, String>>> {
-        let (result_sender, result_receiver) = oneshot::channel();
-        let request = AddOrderRequest {
-            ordertype: OrderType::Limit,
-            bs_type: limit_order.bs_type.into(),
-            volume: limit_order.volume,
-            pair: limit_order.pair,
-            price: limit_order.price,
-            oflags: limit_order.oflags.into_iter().map(OrderFlag::from).collect(),
+    ) -> crate::Result<Option<oneshot::Receiver<Result<String, KrakenError>>>> {
+        let order = Order {
             userref: user_ref_id,
-            validate,
+            ..limit_order.into()
+        };
+        self.add_order(order, validate)
+    }
+
+    /// Submit a request to edit an order over the websockets connection.
+    /// This must be a private connection configured with the auth token.
+    ///
+    /// Editing cancels the order and places a new one, with a new TxID.
+    /// Kraken's websockets API has no equivalent of `AmendOrder`; use the REST API for that.
+    /// The `displayvol`, `deadline` and `cancel_response` fields of the request are not supported
+    /// over websockets, so if any of them is set, the request is not sent, and an `Error::WsUnsupported`
+    /// is returned instead. If an order validator is configured, the edit is checked (and its volume possibly
+    /// rounded) before it is sent.
+    ///
+    /// Arguments:
+    /// request: The edit to make
+    ///
+    /// Returns:
+    /// A oneshot::Reciever which yields either the TxID of the new order, or an error message from kraken.
+    /// If the request is only validated, this yields the TxID of the original order.
+    /// The Receiver produces no value if the request could not be successfully placed, and this will be logged.
+    /// The Receiver may be dropped if you don't care about the errors -- these error messages will be logged regardless.
+    /// The return value will be None if the stream is already closed.
+    pub fn edit_order(
+        &self,
+        request: crate::EditOrderRequest,
+    ) -> crate::Result<Option<oneshot::Receiver<Result<String, KrakenError>>>> {
+        let unsupported = [
+            ("displayvol", !request.displayvol.is_empty()),
+            ("deadline", request.deadline.is_some()),
+            ("cancel_response", request.cancel_response),
+        ];
+        if let Some((field, _)) = unsupported.into_iter().find(|(_, set)| *set) {
+            return Err(crate::Error::WsUnsupported(format!("editOrder {field}")));
+        }
+        let request = match self.order_validator.as_deref() {
+            Some(validator) => validator.apply_edit(request)?,
+            None => request,
+        };
+        let (result_sender, result_receiver) = oneshot::channel();
+        let request = EditOrderRequest {
+            orderid: request.txid,
+            pair: request.pair,
+            volume: request.volume,
+            price: request.price,
+            price2: request.price2,
+            oflags: request.oflags.into_iter().map(OrderFlag::from).collect(),
+            newuserref: request.newuserref,
+            validate: request.validate,
             ..Default::default()
         };
         if self
             .sender
-            .send(LocalRequest::AddOrder { request, result_sender })
+            .send(LocalRequest::EditOrder {
+                request: Box::new(request),
+                result_sender,
+            })
             .is_ok()
         {
-            Some(result_receiver)
+            Ok(Some(result_receiver))
         } else {
-            None
+            Ok(None)
         }
     }
 
@@ -328,7 +489,7 @@ impl KrakenWsAPI {
     /// The Receiver produces no value if the request could not be successfully placed, and this will be logged.
     /// The Receiver may be dropped if you don't care about the errors -- these error messages will be logged regardless.
     /// The return value will be None if the stream is already closed.
-    pub fn cancel_order(&self, tx_id: String) -> Option<oneshot::Receiver<Result<(), String>>> {
+    pub fn cancel_order(&self, tx_id: String) -> Option<oneshot::Receiver<Result<(), KrakenError>>> {
         let (result_sender, result_receiver) = oneshot::channel();
         if self
             .sender
@@ -341,6 +502,28 @@ impl KrakenWsAPI {
         }
     }
 
+    /// Submit requests to cancel a batch of orders over the websockets connection.
+    /// This must be a private connection configured with the auth token.
+    ///
+    /// The batch must follow the same rules as over REST: at most `MAX_CANCEL_BATCH` orders.
+    /// Each order is canceled by a separate request, so that the result for each order is known.
+    ///
+    /// Arguments:
+    /// tx_ids: The TxIds associated to orders, or, user-ref-ids
+    ///
+    /// Returns:
+    /// A oneshot::Receiver for each cancel which was submitted, in order, as for `cancel_order`.
+    /// If the stream closes partway through, the rest of the cancels are not submitted, so there are fewer
+    /// receivers than orders. (There are none if the stream is already closed.)
+    /// An error is returned if the batch is not allowed, and then no cancels are submitted.
+    pub fn cancel_order_batch(
+        &self,
+        tx_ids: Vec<String>,
+    ) -> crate::Result<Vec<oneshot::Receiver<Result<(), KrakenError>>>> {
+        check_cancel_batch(&tx_ids)?;
+        Ok(tx_ids.into_iter().map_while(|tx_id| self.cancel_order(tx_id)).collect())
+    }
+
     /// Submit a request to cancel all orders over the websockets connection.
     /// This must be a private connection configured with the auth token.
     ///
@@ -349,7 +532,7 @@ impl KrakenWsAPI {
     /// The Receiver produces no value if the request could not be successfully placed, and this will be logged.
     /// The Receiver may be dropped if you don't care about the errors -- these error messages will be logged regardless.
     /// The return value will be None if the stream is already closed.
-    pub fn cancel_all_orders(&self) -> Option<oneshot::Receiver<Result<u64, String>>> {
+    pub fn cancel_all_orders(&self) -> Option<oneshot::Receiver<Result<u64, KrakenError>>> {
         let (result_sender, result_receiver) = oneshot::channel();
         if self
             .sender
@@ -361,6 +544,35 @@ impl KrakenWsAPI {
             None
         }
     }
+
+    /// Submit a request to cancel all orders after a timeout over the websockets connection.
+    /// This must be a private connection configured with the auth token.
+    ///
+    /// This is a dead man's switch: unless it is triggered again before the timeout elapses,
+    /// all of our orders are canceled. See also `crate::DeadMansSwitch`.
+    ///
+    /// Arguments:
+    /// timeout: The timeout in seconds. 0 disables the switch.
+    ///
+    /// Returns:
+    /// A oneshot::Reciever which yields either the current time and trigger time, or an error message from kraken.
+    /// The Receiver produces no value if the request could not be successfully placed, and this will be logged.
+    /// The return value will be None if the stream is already closed.
+    pub fn cancel_all_orders_after(
+        &self,
+        timeout: u64,
+    ) -> Option<oneshot::Receiver<Result<CancelAllOrdersAfterResponse, KrakenError>>> {
+        let (result_sender, result_receiver) = oneshot::channel();
+        if self
+            .sender
+            .send(LocalRequest::CancelAllOrdersAfter { timeout, result_sender })
+            .is_ok()
+        {
+            Some(result_receiver)
+        } else {
+            None
+        }
+    }
 }
 
 impl Drop for KrakenWsAPI {
@@ -386,16 +598,26 @@ enum LocalRequest {
     Stop,
     /// Requests to add an order to the order book
     AddOrder {
-        request: AddOrderRequest,
-        result_sender: oneshot::Sender<Result<String, String>>,
+        request: Box<AddOrderRequest>,
+        result_sender: oneshot::Sender<Result<String, KrakenError>>,
+    },
+    /// Requests to edit one of our orders
+    EditOrder {
+        request: Box<EditOrderRequest>,
+        result_sender: oneshot::Sender<Result<String, KrakenError>>,
     },
     /// Requests to cancel one of our orders
     CancelOrder {
         tx_id: String,
-        result_sender: oneshot::Sender<Result<(), String>>,
+        result_sender: oneshot::Sender<Result<(), KrakenError>>,
     },
     /// Requests to cancel all of our orders
     CancelAllOrders {
-        result_sender: oneshot::Sender<Result<u64, String>>,
+        result_sender: oneshot::Sender<Result<u64, KrakenError>>,
+    },
+    /// Requests to cancel all of our orders after a timeout
+    CancelAllOrdersAfter {
+        timeout: u64,
+        result_sender: oneshot::Sender<Result<CancelAllOrdersAfterResponse, KrakenError>>,
     },
 }
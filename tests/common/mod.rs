@@ -0,0 +1,34 @@
+//! Helpers shared by the integration tests, which run against the mock server
+
+#![allow(dead_code)]
+
+use krakenrs::{
+    testing::{MOCK_WS_TOKEN, MockKrakenServer},
+    ws::KrakenWsAPI,
+};
+use rust_decimal::Decimal;
+use std::{
+    str::FromStr,
+    thread,
+    time::{Duration, Instant},
+};
+
+pub const TIMEOUT: Duration = Duration::from_secs(10);
+
+pub fn dec(src: &str) -> Decimal {
+    Decimal::from_str(src).unwrap()
+}
+
+// Poll until a condition holds, or panic after a timeout
+pub fn wait_until(mut cond: impl FnMut() -> bool) {
+    let deadline = Instant::now() + TIMEOUT;
+    while !cond() {
+        assert!(Instant::now() < deadline, "timed out");
+        thread::sleep(Duration::from_millis(10));
+    }
+}
+
+// Connect to the private websockets feed of the mock server
+pub fn private_ws_api(server: &MockKrakenServer) -> KrakenWsAPI {
+    KrakenWsAPI::new(server.ws_config().token(MOCK_WS_TOKEN.into()).build().unwrap()).unwrap()
+}
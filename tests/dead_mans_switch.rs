@@ -0,0 +1,137 @@
+//! End-to-end tests of the dead man's switch, over REST and websockets, against the mock server
+
+mod common;
+
+use common::wait_until;
+use krakenrs::{
+    DeadMansSwitch, DeadMansSwitchConfig, Error, KrakenRestAPI,
+    testing::{MOCK_TRIGGER_TIME, MOCK_WS_TOKEN, MockKrakenServer},
+    ws::KrakenWsAPI,
+};
+use serde_json::json;
+use std::{
+    sync::{
+        Arc, Mutex,
+        atomic::{AtomicBool, Ordering},
+    },
+    time::Duration,
+};
+
+#[test]
+fn test_dead_mans_switch() {
+    let server = MockKrakenServer::start().unwrap();
+    server.respond(
+        "CancelAllOrdersAfter",
+        json!({ "currentTime": "2023-07-06T18:50:48Z", "triggerTime": "2023-07-06T18:50:49Z" }),
+    );
+    let rest_api = Arc::new(KrakenRestAPI::try_from(server.rest_config().build().unwrap()).unwrap());
+    let config = DeadMansSwitchConfig::builder()
+        .timeout(Duration::from_secs(1))
+        .interval(Duration::from_millis(20))
+        .build()
+        .unwrap();
+    let errors = Arc::new(Mutex::new(Vec::new()));
+    let on_error = {
+        let errors = errors.clone();
+        move |err| errors.lock().unwrap().push(err)
+    };
+    let switch = DeadMansSwitch::new(config.clone(), rest_api.clone(), on_error).unwrap();
+    assert_eq!(switch.trigger_time().as_deref(), Some("2023-07-06T18:50:49Z"));
+    wait_until(|| server.requests_for("CancelAllOrdersAfter").len() >= 3);
+
+    // Failures are reported, and the switch keeps trying
+    server.respond_error("CancelAllOrdersAfter", &["EService:Unavailable"]);
+    wait_until(|| errors.lock().unwrap().len() >= 2);
+    assert!(matches!(errors.lock().unwrap()[0], Error::KrakenErrors(_)));
+
+    server.respond(
+        "CancelAllOrdersAfter",
+        json!({ "currentTime": "2023-07-06T18:50:50Z", "triggerTime": "0" }),
+    );
+    drop(switch);
+    let requests = server.requests_for("CancelAllOrdersAfter");
+    assert!(
+        requests[..requests.len() - 1]
+            .iter()
+            .all(|req| req.params["timeout"] == "1")
+    );
+    assert_eq!(requests.last().unwrap().params["timeout"], "0");
+
+    // Over websockets, REST is not used. The interval is long enough for the mock to reply in time.
+    let config = DeadMansSwitchConfig::builder()
+        .timeout(Duration::from_secs(2))
+        .interval(Duration::from_millis(200))
+        .build()
+        .unwrap();
+    let ws_api = Arc::new(KrakenWsAPI::new(server.ws_config().token(MOCK_WS_TOKEN.into()).build().unwrap()).unwrap());
+    let rest_calls = server.requests_for("CancelAllOrdersAfter").len();
+    let switch = DeadMansSwitch::with_ws(config.clone(), rest_api.clone(), ws_api.clone(), |err| {
+        panic!("{}", err)
+    })
+    .unwrap();
+    assert_eq!(switch.trigger_time().as_deref(), Some(MOCK_TRIGGER_TIME));
+    let ws_timeouts = || -> Vec<u64> {
+        server
+            .ws_requests()
+            .iter()
+            .filter(|req| req["event"] == "cancelAllOrdersAfter")
+            .map(|req| req["timeout"].as_u64().unwrap())
+            .collect()
+    };
+    wait_until(|| ws_timeouts().len() >= 3);
+    switch.disarm().unwrap();
+    assert_eq!(ws_timeouts().last(), Some(&0));
+    assert_eq!(server.requests_for("CancelAllOrdersAfter").len(), rest_calls);
+
+    // If the websockets connection stops responding, the timeout is reported and REST is used instead
+    server.set_ws_silent(true);
+    let errors = Arc::new(Mutex::new(Vec::new()));
+    let on_error = {
+        let errors = errors.clone();
+        move |err| errors.lock().unwrap().push(err)
+    };
+    let switch = DeadMansSwitch::with_ws(config, rest_api, ws_api, on_error).unwrap();
+    assert_eq!(switch.trigger_time().as_deref(), Some("0"));
+    wait_until(|| server.requests_for("CancelAllOrdersAfter").len() >= rest_calls + 2);
+    drop(switch);
+    let errors = errors.lock().unwrap();
+    assert!(errors.len() >= 2);
+    assert!(
+        errors
+            .iter()
+            .all(|err| matches!(err, Error::WsTimeout(timeout) if *timeout == Duration::from_millis(100)))
+    );
+    assert_eq!(
+        server.requests_for("CancelAllOrdersAfter").last().unwrap().params["timeout"],
+        "0"
+    );
+}
+
+#[test]
+fn test_dead_mans_switch_panic() {
+    let server = MockKrakenServer::start().unwrap();
+    server.respond(
+        "CancelAllOrdersAfter",
+        json!({ "currentTime": "2023-07-06T18:50:48Z", "triggerTime": "2023-07-06T18:50:49Z" }),
+    );
+    let rest_api = Arc::new(KrakenRestAPI::try_from(server.rest_config().build().unwrap()).unwrap());
+    let config = DeadMansSwitchConfig::builder()
+        .timeout(Duration::from_secs(1))
+        .interval(Duration::from_millis(20))
+        .build()
+        .unwrap();
+    let panicked = Arc::new(AtomicBool::new(false));
+    let on_error = {
+        let panicked = panicked.clone();
+        move |err| {
+            panicked.store(true, Ordering::SeqCst);
+            panic!("{}", err)
+        }
+    };
+    let switch = DeadMansSwitch::new(config, rest_api, on_error).unwrap();
+
+    // A panic in the callback stops the thread, and is returned as an error rather than propagated
+    server.respond_error("CancelAllOrdersAfter", &["EService:Unavailable"]);
+    wait_until(|| panicked.load(Ordering::SeqCst));
+    assert!(matches!(switch.disarm(), Err(Error::Thread(_))));
+}
@@ -0,0 +1,468 @@
+//! End-to-end tests of placing, editing and canceling orders, over REST and websockets, against the mock server
+
+mod common;
+
+use common::{dec, private_ws_api, wait_until};
+use futures::executor::block_on;
+use krakenrs::{
+    AmendOrderRequest, BsType, ConditionalClose, EditOrderRequest, Error, KrakenErrorKind, KrakenRestAPI, Order,
+    OrderFlag, OrderTime, OrderTrigger, OrderValidationError, OrderValidator, RateLimitConfig, RateLimitMode,
+    RetryPolicy, SelfTradePrevention, VerificationTier,
+    testing::{MOCK_WS_TOKEN, MockKrakenServer, WsOrderAck},
+    ws::KrakenWsAPI,
+};
+use serde_json::json;
+use std::time::Duration;
+
+#[test]
+fn test_rest_client_order_ids() {
+    let server = MockKrakenServer::start().unwrap();
+    server.respond_error("AddOrder", &["EService:Busy"]);
+    server.respond(
+        "AddOrder",
+        json!({ "descr": { "order": "buy 0.01 XBTUSD @ limit 29000.0" }, "txid": ["OUF4EM-FRGI2-MQMWZD"] }),
+    );
+    server.respond("CancelOrder", json!({ "count": 1 }));
+
+    // Post-only orders with a client order id are retried, if the policy allows it
+    let policy = RetryPolicy {
+        initial_backoff: Duration::from_millis(5),
+        retry_orders_with_client_id: true,
+        ..Default::default()
+    };
+    let api = KrakenRestAPI::try_from(server.rest_config().retry_policy(policy.clone()).build().unwrap()).unwrap();
+    let order = Order::limit(BsType::Buy, "0.01", "XBTUSD", "29000")
+        .oflag(OrderFlag::Post)
+        .cl_ord_id("my-order-1");
+    let result = api.add_order(order, false).unwrap();
+    assert_eq!(result.txid, vec!["OUF4EM-FRGI2-MQMWZD".to_string()]);
+    let requests = server.requests_for("AddOrder");
+    assert_eq!(requests.len(), 2);
+    assert!(requests.iter().all(|req| req.params["cl_ord_id"] == "my-order-1"));
+
+    assert_eq!(api.cancel_order_by_cl_ord_id("my-order-1".into()).unwrap().count, 1);
+    let params = &server.requests_for("CancelOrder")[0].params;
+    assert_eq!(params["cl_ord_id"], "my-order-1");
+    assert!(!params.contains_key("txid"));
+
+    // Other orders may have filled immediately, so a retry could place them twice
+    let server = MockKrakenServer::start().unwrap();
+    server.respond_error("AddOrder", &["EService:Busy"]);
+    let api = KrakenRestAPI::try_from(server.rest_config().retry_policy(policy).build().unwrap()).unwrap();
+    let order = Order::limit(BsType::Buy, "0.01", "XBTUSD", "29000").cl_ord_id("my-order-2");
+    let err = api.add_order(order, false).unwrap_err();
+    assert!(matches!(err, Error::KrakenErrors(errors) if errors[0].kind == KrakenErrorKind::Busy));
+    assert_eq!(server.requests_for("AddOrder").len(), 1);
+}
+
+#[test]
+fn test_rest_failed_cancel_is_charged_again() {
+    let server = MockKrakenServer::start().unwrap();
+    server.respond(
+        "AddOrder",
+        json!({ "descr": { "order": "buy 0.01 XBTUSD @ limit 29000.0" }, "txid": ["OUF4EM-FRGI2-MQMWZD"] }),
+    );
+    server.respond_error("CancelOrder", &["EService:Unavailable"]);
+
+    let rate_limit = RateLimitConfig {
+        tier: VerificationTier::Starter,
+        mode: RateLimitMode::Error,
+    };
+    let api = KrakenRestAPI::try_from(server.rest_config().rate_limit(rate_limit).build().unwrap()).unwrap();
+    let order = Order::limit(BsType::Buy, "0.01", "XBTUSD", "29000");
+    api.add_order(order, false).unwrap();
+
+    // The order is only forgotten once a cancel succeeds, so each failed attempt pays the penalty,
+    // until the order counter of the pair is full
+    let results: Vec<_> = (0..10)
+        .map(|_| api.cancel_order("OUF4EM-FRGI2-MQMWZD".into()))
+        .collect();
+    assert!(matches!(results[0], Err(Error::KrakenErrors(_))));
+    assert!(matches!(results.last().unwrap(), Err(Error::RateLimited { .. })));
+    assert!(server.requests_for("CancelOrder").len() < 10);
+}
+
+#[test]
+fn test_rest_edit_and_amend() {
+    let server = MockKrakenServer::start().unwrap();
+    server.respond(
+        "EditOrder",
+        json!({
+            "status": "ok",
+            "txid": "OFVXHJ-KPQQQ-RBCUY2",
+            "originaltxid": "OHYO67-6LP66-HMQ437",
+            "volume": "0.02",
+            "price": "29000.0",
+            "orders_cancelled": 1,
+            "descr": { "order": "buy 0.02 XBTUSD @ limit 29000.0" },
+        }),
+    );
+    server.respond("AmendOrder", json!({ "amend_id": "TTW6PD-RC36L-ZZSWNU" }));
+
+    let api = KrakenRestAPI::try_from(server.rest_config().build().unwrap()).unwrap();
+    let result = api
+        .edit_order(EditOrderRequest {
+            txid: "OHYO67-6LP66-HMQ437".into(),
+            pair: "XBTUSD".into(),
+            volume: "0.02".into(),
+            price: "29000".into(),
+            newuserref: Some(7),
+            cancel_response: true,
+            ..Default::default()
+        })
+        .unwrap();
+    assert_eq!(result.txid.as_deref(), Some("OFVXHJ-KPQQQ-RBCUY2"));
+    assert_eq!(result.price, Some(dec("29000.0")));
+    assert_eq!(result.orders_cancelled, 1);
+
+    let result = api
+        .amend_order(AmendOrderRequest {
+            txid: Some("OFVXHJ-KPQQQ-RBCUY2".into()),
+            limit_price: "29100".into(),
+            post_only: true,
+            ..Default::default()
+        })
+        .unwrap();
+    assert_eq!(result.amend_id, "TTW6PD-RC36L-ZZSWNU");
+
+    let params = &server.requests_for("EditOrder")[0].params;
+    assert_eq!(params["txid"], "OHYO67-6LP66-HMQ437");
+    assert_eq!(params["volume"], "0.02");
+    assert_eq!(params["newuserref"], "7");
+    assert_eq!(params["cancel_response"], "true");
+    assert!(!params.contains_key("price2"));
+    assert!(!params.contains_key("validate"));
+    let params = &server.requests_for("AmendOrder")[0].params;
+    assert_eq!(params["txid"], "OFVXHJ-KPQQQ-RBCUY2");
+    assert_eq!(params["limit_price"], "29100");
+    assert_eq!(params["post_only"], "true");
+    assert!(!params.contains_key("order_qty"));
+}
+
+#[test]
+fn test_rest_order_batch() {
+    let server = MockKrakenServer::start().unwrap();
+    server.respond(
+        "AddOrderBatch",
+        json!({
+            "orders": [
+                { "descr": { "order": "buy 0.01 XBTUSD @ limit 29000.0" }, "txid": "OUF4EM-FRGI2-MQMWZD" },
+                { "error": "EOrder:Insufficient funds" },
+            ]
+        }),
+    );
+    server.respond("CancelOrderBatch", json!({ "count": 2 }));
+
+    let api = KrakenRestAPI::try_from(server.rest_config().build().unwrap()).unwrap();
+    let orders = vec![
+        Order::limit(BsType::Buy, "0.01", "XBTUSD", "29000"),
+        Order::limit(BsType::Buy, "100", "XBTUSD", "28900"),
+    ];
+    let result = api.add_order_batch(orders, None, false).unwrap();
+    assert_eq!(result.orders.len(), 2);
+    assert_eq!(result.orders[0].txid.as_deref(), Some("OUF4EM-FRGI2-MQMWZD"));
+    assert!(result.orders[0].error.is_none());
+    assert_eq!(
+        result.orders[1].error.as_ref().unwrap().kind,
+        KrakenErrorKind::InsufficientFunds
+    );
+    let params = &server.requests_for("AddOrderBatch")[0].params;
+    assert_eq!(params["pair"], "XBTUSD");
+    assert_eq!(params["orders[1][volume]"], "100");
+
+    let result = api
+        .cancel_order_batch(vec!["OUF4EM-FRGI2-MQMWZD".into(), "OCYS3K-LH7AE-2GCKHL".into()])
+        .unwrap();
+    assert_eq!(result.count, 2);
+    let params = &server.requests_for("CancelOrderBatch")[0].params;
+    assert_eq!(params["orders[1]"], "OCYS3K-LH7AE-2GCKHL");
+
+    // Invalid batches are rejected without contacting kraken
+    let mixed_pairs = vec![
+        Order::market(BsType::Buy, "0.01", "XBTUSD"),
+        Order::market(BsType::Buy, "0.01", "ETHUSD"),
+    ];
+    assert!(matches!(
+        api.add_order_batch(mixed_pairs, None, false),
+        Err(Error::InvalidBatch(_))
+    ));
+    assert_eq!(server.requests_for("AddOrderBatch").len(), 1);
+}
+
+#[test]
+fn test_order_validation() {
+    let server = MockKrakenServer::start().unwrap();
+    server.respond(
+        "AssetPairs",
+        json!({
+            "XXBTZUSD": {
+                "altname": "XBTUSD",
+                "wsname": "XBT/USD",
+                "pair_decimals": 1,
+                "lot_decimals": 8,
+                "ordermin": "0.0001",
+                "costmin": "0.5",
+                "tick_size": "0.1",
+                "status": "online"
+            }
+        }),
+    );
+    let api = KrakenRestAPI::try_from(server.rest_config().build().unwrap()).unwrap();
+    let validator = OrderValidator::new(api.asset_pairs(vec![]).unwrap())
+        .unwrap()
+        .rounding(true);
+
+    server.respond(
+        "AddOrder",
+        json!({ "descr": { "order": "buy 0.01 XBTUSD @ limit 29000.1" }, "txid": ["OUF4EM-FRGI2-MQMWZD"] }),
+    );
+    let config = server.rest_config().order_validator(validator.clone()).build().unwrap();
+    let api = KrakenRestAPI::try_from(config).unwrap();
+    api.add_order(Order::limit(BsType::Buy, "0.0100000001", "XBTUSD", "29000.15"), false)
+        .unwrap();
+    let params = &server.requests_for("AddOrder")[0].params;
+    assert_eq!(params["volume"], "0.01000000");
+    assert_eq!(params["price"], "29000.1");
+
+    // Orders which fail validation are not sent
+    assert!(matches!(
+        api.add_order(Order::market(BsType::Buy, "0.00001", "XBTUSD"), false),
+        Err(Error::InvalidOrder(OrderValidationError::OrderMinimum { .. }))
+    ));
+    assert_eq!(server.requests_for("AddOrder").len(), 1);
+
+    // Edits and amends are checked too, and their volumes rounded
+    server.respond(
+        "EditOrder",
+        json!({ "txid": "OFVXHJ-KPQ3B-VS7ELA", "originaltxid": "OUF4EM-FRGI2-MQMWZD", "status": "ok" }),
+    );
+    server.respond("AmendOrder", json!({ "amend_id": "TTW6PD-RC36L-ZZSWNU" }));
+    let edit = EditOrderRequest {
+        txid: "OUF4EM-FRGI2-MQMWZD".into(),
+        pair: "XBTUSD".into(),
+        volume: "0.0200000001".into(),
+        price: "29000.2".into(),
+        ..Default::default()
+    };
+    api.edit_order(edit.clone()).unwrap();
+    assert_eq!(server.requests_for("EditOrder")[0].params["volume"], "0.02000000");
+    let edit = EditOrderRequest {
+        price: "29000.25".into(),
+        ..edit
+    };
+    assert!(matches!(
+        api.edit_order(edit.clone()),
+        Err(Error::InvalidOrder(OrderValidationError::PricePrecision { .. }))
+    ));
+    assert_eq!(server.requests_for("EditOrder").len(), 1);
+
+    let amend = AmendOrderRequest {
+        txid: Some("OUF4EM-FRGI2-MQMWZD".into()),
+        limit_price: "29000.25".into(),
+        ..Default::default()
+    };
+    assert!(matches!(
+        api.amend_order(amend.clone()),
+        Err(Error::InvalidOrder(OrderValidationError::MissingPair))
+    ));
+    let amend = AmendOrderRequest {
+        pair: Some("XBTUSD".into()),
+        ..amend
+    };
+    assert!(matches!(
+        api.amend_order(amend.clone()),
+        Err(Error::InvalidOrder(OrderValidationError::PricePrecision { .. }))
+    ));
+    assert!(server.requests_for("AmendOrder").is_empty());
+    api.amend_order(AmendOrderRequest {
+        limit_price: "29000.3".into(),
+        ..amend
+    })
+    .unwrap();
+    let params = &server.requests_for("AmendOrder")[0].params;
+    assert_eq!(params["limit_price"], "29000.3");
+    assert!(!params.contains_key("pair"));
+
+    let config = server
+        .ws_config()
+        .token(MOCK_WS_TOKEN.into())
+        .order_validator(validator)
+        .build()
+        .unwrap();
+    let api = KrakenWsAPI::new(config).unwrap();
+    assert!(matches!(
+        api.add_order(Order::market(BsType::Buy, "0.01", "ETH/USD"), false),
+        Err(Error::InvalidOrder(OrderValidationError::UnknownPair(_)))
+    ));
+    assert!(server.ws_requests().iter().all(|req| req["event"] != "addOrder"));
+    let edit = EditOrderRequest {
+        pair: "XBT/USD".into(),
+        ..edit
+    };
+    assert!(matches!(
+        api.edit_order(edit),
+        Err(Error::InvalidOrder(OrderValidationError::PricePrecision { .. }))
+    ));
+    assert!(server.ws_requests().iter().all(|req| req["event"] != "editOrder"));
+}
+
+#[test]
+fn test_ws_order_options() {
+    let server = MockKrakenServer::start().unwrap();
+    let api = private_ws_api(&server);
+    let stop = Order::stop_loss_limit(BsType::Sell, "0.01", "XBT/USD", "25000", "24900")
+        .trigger(OrderTrigger::Index)
+        .expire_time(OrderTime::FromNow(60))
+        .reduce_only()
+        .self_trade_prevention(SelfTradePrevention::CancelBoth)
+        .close(ConditionalClose::limit("26000"));
+    let result = block_on(api.add_order(stop, true).unwrap().unwrap()).unwrap();
+    assert!(result.is_ok());
+
+    let requests = server.ws_requests();
+    let add_order = requests.iter().find(|req| req["event"] == "addOrder").unwrap();
+    assert_eq!(add_order["ordertype"], "stop-loss-limit");
+    assert_eq!(add_order["price2"], "24900");
+    assert_eq!(add_order["trigger"], "index");
+    assert_eq!(add_order["expiretm"], "+60");
+    assert_eq!(add_order["validate"], "true");
+    assert_eq!(add_order["reduce_only"], "true");
+    assert_eq!(add_order["stptype"], "cancel-both");
+    assert!(add_order.get("leverage").is_none());
+    assert_eq!(add_order["close[ordertype]"], "limit");
+    assert_eq!(add_order["close[price]"], "26000");
+}
+
+#[test]
+fn test_ws_edit_order() {
+    let server = MockKrakenServer::start().unwrap();
+    let api = private_ws_api(&server);
+    server.queue_order_ack(WsOrderAck::AcceptWithTxid("OABCDE-12345-FGHIJK".into()));
+    let order = Order::limit(BsType::Buy, "0.01", "XBT/USD", "30000");
+    let result = block_on(api.add_order(order, false).unwrap().unwrap()).unwrap();
+    assert_eq!(result, Ok("OABCDE-12345-FGHIJK".to_string()));
+
+    // Editing replaces the order with a new txid
+    let edit = EditOrderRequest {
+        txid: "OABCDE-12345-FGHIJK".into(),
+        pair: "XBT/USD".into(),
+        price: "30500".into(),
+        newuserref: Some(3),
+        ..Default::default()
+    };
+    server.queue_order_ack(WsOrderAck::AcceptWithTxid("OEDITD-12345-FGHIJK".into()));
+    let result = block_on(api.edit_order(edit.clone()).unwrap().unwrap()).unwrap();
+    assert_eq!(result, Ok("OEDITD-12345-FGHIJK".to_string()));
+    let result = block_on(api.edit_order(edit.clone()).unwrap().unwrap()).unwrap();
+    assert_eq!(result.unwrap_err().kind, KrakenErrorKind::UnknownOrder);
+
+    // Fields which websockets doesn't support are not ignored
+    let edit = EditOrderRequest {
+        deadline: Some("2023-07-06T18:50:48Z".into()),
+        ..edit
+    };
+    assert!(matches!(api.edit_order(edit), Err(Error::WsUnsupported(_))));
+    let edit_orders: Vec<_> = server
+        .ws_requests()
+        .into_iter()
+        .filter(|req| req["event"] == "editOrder")
+        .collect();
+    assert_eq!(edit_orders.len(), 2);
+    assert_eq!(edit_orders[0]["orderid"], "OABCDE-12345-FGHIJK");
+    assert_eq!(edit_orders[0]["price"], "30500");
+    assert_eq!(edit_orders[0]["newuserref"], "3");
+    assert!(edit_orders[0].get("volume").is_none());
+}
+
+#[test]
+fn test_ws_order_batch() {
+    let server = MockKrakenServer::start().unwrap();
+    let api = private_ws_api(&server);
+
+    // Batches are submitted order by order, with a result for each
+    let batch = vec![
+        Order::limit(BsType::Sell, "0.01", "XBT/USD", "31000"),
+        Order::limit(BsType::Sell, "0.01", "XBT/USD", "32000"),
+    ];
+    server.queue_order_ack(WsOrderAck::Accept);
+    server.queue_order_ack(WsOrderAck::Reject("EOrder:Insufficient funds".into()));
+    let receivers = api.add_order_batch(batch, false).unwrap();
+    let results: Vec<_> = receivers.into_iter().map(|recv| block_on(recv).unwrap()).collect();
+    assert!(results[0].as_ref().unwrap().starts_with("OMOCK"));
+    assert_eq!(
+        results[1].as_ref().unwrap_err().kind,
+        KrakenErrorKind::InsufficientFunds
+    );
+    let receivers = api.cancel_order_batch(vec![results[0].clone().unwrap()]).unwrap();
+    assert_eq!(block_on(receivers.into_iter().next().unwrap()).unwrap(), Ok(()));
+    let count = block_on(api.cancel_all_orders().unwrap()).unwrap();
+    assert_eq!(count, Ok(0));
+
+    // Batches follow the same rules as over REST, and invalid batches are not submitted
+    let mixed_pairs = vec![
+        Order::market(BsType::Buy, "0.01", "XBT/USD"),
+        Order::market(BsType::Buy, "0.01", "ETH/USD"),
+    ];
+    assert!(matches!(
+        api.add_order_batch(mixed_pairs, false),
+        Err(Error::InvalidBatch(_))
+    ));
+    assert!(matches!(api.cancel_order_batch(vec![]), Err(Error::InvalidBatch(_))));
+    // An order which can't be sent rejects the whole batch, including the orders before it
+    let with_client_id = vec![
+        Order::limit(BsType::Buy, "0.01", "XBT/USD", "29000"),
+        Order::limit(BsType::Buy, "0.01", "XBT/USD", "28000").cl_ord_id("my-order-1"),
+        Order::limit(BsType::Buy, "0.01", "XBT/USD", "27000"),
+    ];
+    assert!(matches!(
+        api.add_order_batch(with_client_id, false),
+        Err(Error::WsUnsupported(_))
+    ));
+    let add_orders = server
+        .ws_requests()
+        .into_iter()
+        .filter(|req| req["event"] == "addOrder");
+    assert_eq!(add_orders.count(), 2);
+}
+
+#[test]
+fn test_ws_client_order_ids() {
+    let server = MockKrakenServer::start().unwrap();
+    let api = KrakenWsAPI::new(
+        server
+            .ws_config()
+            .token(MOCK_WS_TOKEN.into())
+            .subscribe_open_orders(true)
+            .build()
+            .unwrap(),
+    )
+    .unwrap();
+    assert!(server.wait_for_subscription("openOrders", None, common::TIMEOUT));
+
+    // Orders with a client order id are rejected, because kraken doesn't accept them over websockets
+    let order = Order::limit(BsType::Buy, "0.01", "XBT/USD", "30000").cl_ord_id("my-order-1");
+    assert!(matches!(api.add_order(order, false), Err(Error::WsUnsupported(_))));
+    assert!(server.ws_requests().iter().all(|req| req["event"] != "addOrder"));
+
+    server.push_open_orders(json!([{
+        "OABCDE-12345-FGHIJK": {
+            "status": "open",
+            "opentm": "1688669448.123456",
+            "descr": { "pair": "XBT/USD", "type": "buy", "ordertype": "limit", "price": "30000.0", "price2": "0.0", "leverage": null, "order": "buy 0.01 XBT/USD @ limit 30000.0", "close": "" },
+            "vol": "0.01",
+            "vol_exec": "0",
+            "cost": "0",
+            "fee": "0",
+            "avg_price": "0",
+            "oflags": "fciq",
+            "misc": "",
+            "userref": 0,
+            "cl_ord_id": "my-order-1",
+        }
+    }]));
+    wait_until(|| api.get_open_orders().contains_key("OABCDE-12345-FGHIJK"));
+    assert_eq!(
+        api.get_open_orders()["OABCDE-12345-FGHIJK"].cl_ord_id.as_deref(),
+        Some("my-order-1")
+    );
+}
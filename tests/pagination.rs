@@ -0,0 +1,348 @@
+//! End-to-end tests of the paginating iterators, against the mock server
+
+mod common;
+
+use common::dec;
+use krakenrs::{
+    Error, KrakenErrorKind, KrakenRestAPI, LedgersRequest, TradesHistoryRequest, WithdrawStatusRequest,
+    testing::MockKrakenServer,
+};
+use serde_json::{Value, json};
+use std::time::Duration;
+
+// A TradesHistory result with a trade at each of the given times
+fn trades_page(times: &[u64], count: u64) -> Value {
+    let trades: serde_json::Map<_, _> = times
+        .iter()
+        .map(|time| {
+            let trade = json!({
+                "ordertxid": "OQCLML-BW3P3-BUCMWZ",
+                "pair": "XXBTZUSD",
+                "time": time,
+                "type": "buy",
+                "ordertype": "limit",
+                "price": "30010.00000",
+                "cost": "600.20000",
+                "fee": "0.00000",
+                "vol": "0.02000000",
+                "margin": "0.00000",
+                "misc": "",
+            });
+            (format!("T{time}"), trade)
+        })
+        .collect();
+    json!({ "trades": trades, "count": count })
+}
+
+// A Ledgers result with an entry at each of the given times
+fn ledgers_page(times: &[u64], count: u64) -> Value {
+    let ledger: serde_json::Map<_, _> = times
+        .iter()
+        .map(|time| {
+            let entry = json!({
+                "refid": "TJKLXX-PGMUI-4NTLXU",
+                "time": time,
+                "type": "trade",
+                "subtype": "",
+                "aclass": "currency",
+                "asset": "ZUSD",
+                "amount": "-24.5000",
+                "fee": "0.0490",
+                "balance": "100.0",
+            });
+            (format!("L{time}"), entry)
+        })
+        .collect();
+    json!({ "ledger": ledger, "count": count })
+}
+
+// A WithdrawStatus entry at a given time
+fn withdrawal(time: u64) -> Value {
+    json!({
+        "method": "Bitcoin",
+        "aclass": "currency",
+        "asset": "XXBT",
+        "refid": format!("FTQcuak-{time}"),
+        "txid": null,
+        "info": "mzp6yUVMRxfasyfwzTZjjy38dHqMX7Z3GR",
+        "amount": "0.72485000",
+        "fee": "0.00015000",
+        "time": time,
+        "status": "Pending",
+    })
+}
+
+#[test]
+fn test_ofs_pagination() {
+    let server = MockKrakenServer::start().unwrap();
+    server.respond("TradesHistory", trades_page(&[1000, 1003], 5));
+    server.respond("TradesHistory", trades_page(&[1001, 1004, 1002], 5));
+    server.respond("TradesHistory", trades_page(&[], 5));
+
+    let api = KrakenRestAPI::try_from(server.rest_config().build().unwrap()).unwrap();
+    let times: Vec<_> = api
+        .trades_history_iter(TradesHistoryRequest::default())
+        .pause(Duration::ZERO)
+        .map(|result| result.unwrap().1.time)
+        .collect();
+    // Each page is sorted most recent first
+    let expected: Vec<_> = [1003, 1000, 1004, 1002, 1001].into_iter().map(Into::into).collect();
+    assert_eq!(times, expected);
+
+    // The offset advances by the size of each page, until it reaches the count
+    let requests = server.requests_for("TradesHistory");
+    let offsets: Vec<_> = requests.iter().map(|req| req.params["ofs"].as_str()).collect();
+    assert_eq!(offsets, ["0", "2", "5"]);
+    // The end is pinned to the newest trade of the first page
+    assert!(!requests[0].params.contains_key("end"));
+    assert!(requests[1..].iter().all(|req| req.params["end"] == "1003"));
+}
+
+#[test]
+fn test_ofs_pagination_new_items() {
+    let server = MockKrakenServer::start().unwrap();
+    // Serve trades newest first, two per page, like kraken, with a new trade arriving after the first page
+    let mut times = vec![1000, 1001, 1002, 1003, 1004];
+    let mut pages = 0;
+    server.respond_with("TradesHistory", move |params| {
+        if pages == 1 {
+            times.push(1005);
+        }
+        pages += 1;
+        let end: Option<u64> = params.get("end").map(|end| end.parse().unwrap());
+        let mut matching: Vec<u64> = times
+            .iter()
+            .copied()
+            .filter(|time| end.is_none_or(|end| *time <= end))
+            .collect();
+        matching.sort_by(|a, b| b.cmp(a));
+        let ofs: usize = params["ofs"].parse().unwrap();
+        let page: Vec<u64> = matching.iter().copied().skip(ofs).take(2).collect();
+        trades_page(&page, matching.len() as u64)
+    });
+
+    let api = KrakenRestAPI::try_from(server.rest_config().build().unwrap()).unwrap();
+    let times: Vec<_> = api
+        .trades_history_iter(TradesHistoryRequest::default())
+        .pause(Duration::ZERO)
+        .map(|result| result.unwrap().1.time)
+        .collect();
+    // The new trade doesn't shift the later pages, so no trade is yielded twice
+    let expected: Vec<_> = [1004, 1003, 1002, 1001, 1000].into_iter().map(Into::into).collect();
+    assert_eq!(times, expected);
+}
+
+#[test]
+fn test_ofs_pagination_until() {
+    let server = MockKrakenServer::start().unwrap();
+    server.respond("TradesHistory", trades_page(&[1000, 1003], 12));
+    server.respond("TradesHistory", trades_page(&[], 12));
+
+    let api = KrakenRestAPI::try_from(server.rest_config().build().unwrap()).unwrap();
+    let request = TradesHistoryRequest {
+        start: Some("900".into()),
+        ofs: Some(10),
+        ..Default::default()
+    };
+    let times: Vec<_> = api
+        .trades_history_iter(request)
+        .pause(Duration::ZERO)
+        .until(dec("1002.5"))
+        .map(|result| result.unwrap().1.time)
+        .collect();
+    assert_eq!(times, vec![dec("1000")]);
+
+    // The request is sent with its own parameters, and until as its end
+    let requests = server.requests_for("TradesHistory");
+    assert_eq!(requests.len(), 2);
+    assert_eq!(requests[0].params["start"], "900");
+    assert_eq!(requests[0].params["ofs"], "10");
+    assert_eq!(requests[1].params["ofs"], "12");
+    assert!(requests.iter().all(|req| req.params["end"] == "1002.5"));
+}
+
+#[test]
+fn test_last_pagination_until() {
+    let server = MockKrakenServer::start().unwrap();
+    server.respond(
+        "Trades",
+        json!({
+            "XXBTZUSD": [
+                ["30000.0", "0.1", 1000.5, "b", "l", "", 1],
+                ["30001.0", "0.1", 1001.5, "s", "l", "", 2],
+            ],
+            "last": "1001500000000",
+        }),
+    );
+    server.respond(
+        "Trades",
+        json!({ "XXBTZUSD": [["30002.0", "0.1", 1002.5, "b", "m", "", 3]], "last": "1002500000000" }),
+    );
+    server.respond("Trades", json!({ "XXBTZUSD": [], "last": "1002500000000" }));
+
+    let api = KrakenRestAPI::try_from(server.rest_config().build().unwrap()).unwrap();
+    let ids: Vec<_> = api
+        .recent_trades_since("XXBTZUSD".into(), None)
+        .pause(Duration::ZERO)
+        .map(|result| result.unwrap().trade_id)
+        .collect();
+    assert_eq!(ids, [1, 2, 3]);
+    // Each page starts from the last of the previous page, until there is nothing new
+    let requests = server.requests_for("Trades");
+    assert_eq!(requests.len(), 3);
+    assert!(!requests[0].params.contains_key("since"));
+    assert_eq!(requests[1].params["since"], "1001500000000");
+    assert_eq!(requests[2].params["since"], "1002500000000");
+
+    // Trades are chronological, so no more pages are fetched after the first trade past until
+    let server = MockKrakenServer::start().unwrap();
+    server.respond(
+        "Trades",
+        json!({
+            "XXBTZUSD": [
+                ["30000.0", "0.1", 1000.5, "b", "l", "", 1],
+                ["30001.0", "0.1", 1001.5, "s", "l", "", 2],
+            ],
+            "last": "1001500000000",
+        }),
+    );
+    let api = KrakenRestAPI::try_from(server.rest_config().build().unwrap()).unwrap();
+    let trades: Vec<_> = api
+        .recent_trades_since("XXBTZUSD".into(), None)
+        .pause(Duration::ZERO)
+        .until(dec("1001"))
+        .map(|result| result.unwrap())
+        .collect();
+    assert_eq!(trades.len(), 1);
+    assert_eq!(trades[0].timestamp, dec("1000.5"));
+    assert_eq!(server.requests_for("Trades").len(), 1);
+}
+
+#[test]
+fn test_cursor_pagination() {
+    let server = MockKrakenServer::start().unwrap();
+    server.respond(
+        "WithdrawStatus",
+        json!({ "withdrawals": [withdrawal(1002), withdrawal(1001)], "next_cursor": "MTc0NjQ1NDQ1Nw" }),
+    );
+    server.respond(
+        "WithdrawStatus",
+        json!({ "withdrawals": [withdrawal(1000)], "next_cursor": null }),
+    );
+
+    let api = KrakenRestAPI::try_from(server.rest_config().build().unwrap()).unwrap();
+    let request = WithdrawStatusRequest {
+        asset: Some("XBT".into()),
+        ..Default::default()
+    };
+    let times: Vec<_> = api
+        .withdraw_status_iter(request)
+        .pause(Duration::ZERO)
+        .map(|result| result.unwrap().time)
+        .collect();
+    assert_eq!(times, [1002, 1001, 1000]);
+
+    let requests = server.requests_for("WithdrawStatus");
+    assert_eq!(requests.len(), 2);
+    assert_eq!(requests[0].params["cursor"], "true");
+    assert_eq!(requests[1].params["cursor"], "MTc0NjQ1NDQ1Nw");
+    assert!(requests.iter().all(|req| req.params["asset"] == "XBT"));
+}
+
+#[test]
+fn test_pagination_error() {
+    let server = MockKrakenServer::start().unwrap();
+    server.respond("Ledgers", ledgers_page(&[1001, 1000], 100));
+    server.respond_error("Ledgers", &["EService:Unavailable"]);
+
+    let api = KrakenRestAPI::try_from(server.rest_config().build().unwrap()).unwrap();
+    let mut iter = api.ledgers_iter(LedgersRequest::default()).pause(Duration::ZERO);
+    assert_eq!(iter.next().unwrap().unwrap().1.time, dec("1001"));
+    assert_eq!(iter.next().unwrap().unwrap().1.time, dec("1000"));
+    // The error is yielded, and ends the iteration
+    match iter.next() {
+        Some(Err(Error::KrakenErrors(errors))) => assert_eq!(errors[0].kind, KrakenErrorKind::Unavailable),
+        other => panic!("unexpected result: {:?}", other.map(|result| result.map(|_| ()))),
+    }
+    assert!(iter.next().is_none());
+    assert_eq!(server.requests_for("Ledgers").len(), 2);
+}
+
+#[cfg(feature = "async")]
+#[tokio::test]
+async fn test_async_ofs_pagination() {
+    use futures::{StreamExt, TryStreamExt};
+    use krakenrs::AsyncKrakenRestAPI;
+
+    let server = MockKrakenServer::start().unwrap();
+    server.respond("TradesHistory", trades_page(&[1000, 1003], 5));
+    server.respond("TradesHistory", trades_page(&[1001, 1004, 1002], 5));
+    server.respond("TradesHistory", trades_page(&[], 5));
+
+    let api = AsyncKrakenRestAPI::try_from(server.rest_config().build().unwrap()).unwrap();
+    let times: Vec<_> = api
+        .trades_history_iter(TradesHistoryRequest::default())
+        .pause(Duration::ZERO)
+        .until(dec("1003.5"))
+        .map_ok(|(_, trade)| trade.time)
+        .try_collect()
+        .await
+        .unwrap();
+    // Trades after until are skipped, but pagination continues to the end
+    let expected: Vec<_> = [1003, 1000, 1002, 1001].into_iter().map(Into::into).collect();
+    assert_eq!(times, expected);
+
+    let requests = server.requests_for("TradesHistory");
+    let offsets: Vec<_> = requests.iter().map(|req| req.params["ofs"].as_str()).collect();
+    assert_eq!(offsets, ["0", "2", "5"]);
+    assert!(requests.iter().all(|req| req.params["end"] == "1003.5"));
+
+    // An error is yielded, and ends the stream
+    let server = MockKrakenServer::start().unwrap();
+    server.respond("Ledgers", ledgers_page(&[1000], 100));
+    server.respond_error("Ledgers", &["EService:Unavailable"]);
+    let api = AsyncKrakenRestAPI::try_from(server.rest_config().build().unwrap()).unwrap();
+    let results: Vec<_> = api
+        .ledgers_iter(LedgersRequest::default())
+        .pause(Duration::ZERO)
+        .collect()
+        .await;
+    assert_eq!(results.len(), 2);
+    assert_eq!(results[0].as_ref().unwrap().1.time, dec("1000"));
+    assert!(matches!(&results[1], Err(Error::KrakenErrors(errors)) if errors[0].kind == KrakenErrorKind::Unavailable));
+}
+
+#[cfg(feature = "async")]
+#[tokio::test]
+async fn test_async_cursor_pagination() {
+    use futures::TryStreamExt;
+    use krakenrs::AsyncKrakenRestAPI;
+
+    let server = MockKrakenServer::start().unwrap();
+    server.respond(
+        "WithdrawStatus",
+        json!({ "withdrawals": [withdrawal(1002), withdrawal(1001)], "next_cursor": "MTc0NjQ1NDQ1Nw" }),
+    );
+    server.respond(
+        "WithdrawStatus",
+        json!({ "withdrawals": [withdrawal(1000)], "next_cursor": null }),
+    );
+
+    let api = AsyncKrakenRestAPI::try_from(server.rest_config().build().unwrap()).unwrap();
+    let start = std::time::Instant::now();
+    let times: Vec<_> = api
+        .withdraw_status_iter(WithdrawStatusRequest::default())
+        .pause(Duration::from_millis(50))
+        .map_ok(|status| status.time)
+        .try_collect()
+        .await
+        .unwrap();
+    assert_eq!(times, [1002, 1001, 1000]);
+    // The pause is waited for between pages
+    assert!(start.elapsed() >= Duration::from_millis(50));
+
+    let requests = server.requests_for("WithdrawStatus");
+    assert_eq!(requests.len(), 2);
+    assert_eq!(requests[0].params["cursor"], "true");
+    assert_eq!(requests[1].params["cursor"], "MTc0NjQ1NDQ1Nw");
+}
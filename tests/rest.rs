@@ -0,0 +1,102 @@
+//! End-to-end tests of the REST client against the mock server: public and signed private requests, and retries
+
+mod common;
+
+use base64ct::{Base64, Encoding};
+use common::dec;
+use krakenrs::{
+    BsType, Error, KrakenCredentials, KrakenErrorKind, KrakenRestAPI, LimitOrder, RetryPolicy,
+    testing::MockKrakenServer,
+};
+use serde_json::json;
+use std::time::Duration;
+
+#[test]
+fn test_rest_public() {
+    let server = MockKrakenServer::start().unwrap();
+    server.respond(
+        "Time",
+        json!({ "unixtime": 1688669448, "rfc1123": "Thu, 06 Jul 23 18:50:48 +0000" }),
+    );
+
+    let api = KrakenRestAPI::try_from(server.rest_config().build().unwrap()).unwrap();
+    assert_eq!(api.time().unwrap().unixtime, 1688669448);
+
+    let requests = server.requests();
+    assert_eq!(requests.len(), 1);
+    assert_eq!(requests[0].method, "Time");
+    assert!(!requests[0].private);
+
+    // Unscripted methods are errors
+    match api.system_status() {
+        Err(Error::KrakenErrors(errors)) => assert_eq!(errors[0].kind, KrakenErrorKind::UnknownMethod),
+        other => panic!("unexpected result: {:?}", other.map(|_| ())),
+    }
+}
+
+#[test]
+fn test_rest_private_signature() {
+    let server = MockKrakenServer::start().unwrap();
+    server.respond("Balance", json!({ "ZUSD": "171288.6158", "XXBT": "0.0011" }));
+    server.respond_error("Balance", &["EService:Unavailable"]);
+
+    let api = KrakenRestAPI::try_from(server.rest_config().build().unwrap()).unwrap();
+    let balance = api.get_account_balance().unwrap();
+    assert_eq!(balance["XXBT"], dec("0.0011"));
+    // The last scripted response repeats
+    for _ in 0..2 {
+        assert!(matches!(api.get_account_balance(), Err(Error::KrakenErrors(_))));
+    }
+
+    let requests = server.requests_for("Balance");
+    assert_eq!(requests.len(), 3);
+    assert!(requests.iter().all(|req| req.private && req.rejected.is_none()));
+    assert!(requests[0].params.contains_key("nonce"));
+
+    // A client with the wrong secret is rejected
+    let bad_creds = KrakenCredentials {
+        key: server.creds().key,
+        secret: Base64::encode_string(b"wrong-secret"),
+    };
+    let bad_api = KrakenRestAPI::try_from(server.rest_config().creds(bad_creds).build().unwrap()).unwrap();
+    match bad_api.get_account_balance() {
+        Err(Error::KrakenErrors(errors)) => assert_eq!(errors[0].kind, KrakenErrorKind::InvalidSignature),
+        other => panic!("unexpected result: {:?}", other),
+    }
+    assert_eq!(
+        server.requests().last().unwrap().rejected.as_deref(),
+        Some("EAPI:Invalid signature")
+    );
+}
+
+#[test]
+fn test_rest_retries() {
+    let server = MockKrakenServer::start().unwrap();
+    server.respond_error("Balance", &["EService:Unavailable"]);
+    server.respond("Balance", json!({ "ZUSD": "1.0" }));
+    server.respond_error("AddOrder", &["EService:Busy"]);
+
+    let policy = RetryPolicy {
+        initial_backoff: Duration::from_millis(5),
+        ..Default::default()
+    };
+    let api = KrakenRestAPI::try_from(server.rest_config().retry_policy(policy).build().unwrap()).unwrap();
+    assert_eq!(api.get_account_balance().unwrap()["ZUSD"], dec("1.0"));
+
+    // The retry was signed again, with a fresh nonce
+    let requests = server.requests_for("Balance");
+    assert_eq!(requests.len(), 2);
+    assert!(requests.iter().all(|req| req.rejected.is_none()));
+    assert_ne!(requests[0].params["nonce"], requests[1].params["nonce"]);
+
+    // Orders are not retried
+    let order = LimitOrder {
+        bs_type: BsType::Buy,
+        volume: "0.01".into(),
+        pair: "XBTUSD".into(),
+        price: "30000".into(),
+        oflags: Default::default(),
+    };
+    assert!(api.add_limit_order(order, None, false).is_err());
+    assert_eq!(server.requests_for("AddOrder").len(), 1);
+}
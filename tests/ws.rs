@@ -0,0 +1,102 @@
+//! End-to-end tests of the websockets client against the mock server: book checksums, and orders
+
+mod common;
+
+use common::{TIMEOUT, dec, wait_until};
+use futures::executor::block_on;
+use krakenrs::{
+    BsType, KrakenErrorKind, LimitOrder,
+    testing::{MOCK_WS_TOKEN, MockKrakenServer, WsOrderAck},
+    ws::KrakenWsAPI,
+};
+use serde_json::json;
+
+#[test]
+fn test_ws_book_checksums() {
+    let server = MockKrakenServer::start().unwrap();
+    server.set_book(
+        "XBT/USD",
+        &[("30001.0", "1.5"), ("30002.0", "0.25")],
+        &[("29999.5", "2.0"), ("29998.0", "0.1")],
+    );
+
+    let api = KrakenWsAPI::new(
+        server
+            .ws_config()
+            .subscribe_book(vec!["XBT/USD".into()])
+            .build()
+            .unwrap(),
+    )
+    .unwrap();
+
+    wait_until(|| api.get_book("XBT/USD").unwrap().bid.len() == 2);
+    let book = api.get_book("XBT/USD").unwrap();
+    assert_eq!(book.ask[&dec("30001.0")].volume, dec("1.5"));
+
+    server.update_book("XBT/USD", &[("30001.0", "0.00000000")], &[("30000.0", "3.0")]);
+    wait_until(|| api.get_book("XBT/USD").unwrap().bid.len() == 3);
+    let book = api.get_book("XBT/USD").unwrap();
+    assert_eq!(book.ask.len(), 1);
+    assert!(!book.checksum_failed);
+
+    server.update_book_with_checksum("XBT/USD", &[("30003.0", "1.0")], &[], 12345);
+    wait_until(|| api.get_book("XBT/USD").unwrap().checksum_failed);
+}
+
+#[test]
+fn test_ws_orders() {
+    let server = MockKrakenServer::start().unwrap();
+    let api = KrakenWsAPI::new(
+        server
+            .ws_config()
+            .token(MOCK_WS_TOKEN.into())
+            .subscribe_open_orders(true)
+            .build()
+            .unwrap(),
+    )
+    .unwrap();
+    assert!(server.wait_for_subscription("openOrders", None, TIMEOUT));
+
+    let order = LimitOrder {
+        bs_type: BsType::Buy,
+        volume: "0.01".into(),
+        pair: "XBT/USD".into(),
+        price: "30000".into(),
+        oflags: Default::default(),
+    };
+
+    server.queue_order_ack(WsOrderAck::AcceptWithTxid("OABCDE-12345-FGHIJK".into()));
+    server.queue_order_ack(WsOrderAck::Reject("EOrder:Insufficient funds".into()));
+
+    let result = block_on(api.add_limit_order(order.clone(), None, false).unwrap().unwrap()).unwrap();
+    assert_eq!(result, Ok("OABCDE-12345-FGHIJK".to_string()));
+    let result = block_on(api.add_limit_order(order.clone(), None, false).unwrap().unwrap()).unwrap();
+    assert_eq!(result.unwrap_err().kind, KrakenErrorKind::InsufficientFunds);
+    let result = block_on(api.add_limit_order(order, None, false).unwrap().unwrap()).unwrap();
+    assert!(result.unwrap().starts_with("OMOCK"));
+
+    let requests = server.ws_requests();
+    let add_orders: Vec<_> = requests.iter().filter(|req| req["event"] == "addOrder").collect();
+    assert_eq!(add_orders.len(), 3);
+    assert_eq!(add_orders[0]["price"], "30000");
+
+    let count = block_on(api.cancel_all_orders().unwrap()).unwrap();
+    assert_eq!(count, Ok(2));
+
+    server.push_open_orders(json!([{
+        "OABCDE-12345-FGHIJK": {
+            "status": "open",
+            "opentm": "1688669448.123456",
+            "descr": { "pair": "XBT/USD", "type": "buy", "ordertype": "limit", "price": "30000.0", "price2": "0.0", "leverage": null, "order": "buy 0.01 XBT/USD @ limit 30000.0", "close": "" },
+            "vol": "0.01",
+            "vol_exec": "0",
+            "cost": "0",
+            "fee": "0",
+            "avg_price": "0",
+            "oflags": "fciq",
+            "misc": "",
+            "userref": 0,
+        }
+    }]));
+    wait_until(|| api.get_open_orders().contains_key("OABCDE-12345-FGHIJK"));
+}